@@ -0,0 +1,216 @@
+use crate::float::EPSILON;
+use crate::matrix::Matrix4;
+use crate::ray;
+use crate::tuple::{Tuple, TupleMethods};
+
+// An axis-aligned box that completely encloses a shape. Either corner may be
+// infinite for shapes like planes that go on forever.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+// Like a regular product, except that nothing times zero is always zero,
+// so that a transform with zeroes in it can't turn an infinite extent
+// into NaN.
+fn scale_extent(factor: f64, extent: f64) -> f64 {
+    if factor == 0. {
+        0.
+    } else {
+        factor * extent
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple, max: Tuple) -> BoundingBox {
+        BoundingBox { min, max }
+    }
+
+    // A box with nothing in it, which any other box will swallow up when
+    // combined with it.
+    pub fn empty() -> BoundingBox {
+        BoundingBox {
+            min: Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Tuple::point(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+        }
+    }
+
+    pub fn infinite() -> BoundingBox {
+        BoundingBox {
+            min: Tuple::point(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+            max: Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn add_point(&mut self, point: Tuple) {
+        for (axis, &value) in point.iter().enumerate().take(3) {
+            self.min[axis] = self.min[axis].min(value);
+            self.max[axis] = self.max[axis].max(value);
+        }
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let mut combined = *self;
+        for axis in 0..3 {
+            combined.min[axis] = combined.min[axis].min(other.min[axis]);
+            combined.max[axis] = combined.max[axis].max(other.max[axis]);
+        }
+        combined
+    }
+
+    // Anything that goes on forever along some axis counts as infinitely
+    // big, even if it's flat along another.
+    pub fn volume(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        if (0..3).any(|axis| self.max[axis] - self.min[axis] == f64::INFINITY) {
+            return f64::INFINITY;
+        }
+        (0..3).map(|axis| self.max[axis] - self.min[axis]).product()
+    }
+
+    // Finds the box enclosing this one once it has been transformed, by
+    // picking out the smallest and largest contribution of every matrix
+    // entry along each axis rather than transforming all eight corners.
+    pub fn transform(&self, matrix: Matrix4) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let mut transformed = BoundingBox::new(
+            Tuple::point(matrix[0][3], matrix[1][3], matrix[2][3]),
+            Tuple::point(matrix[0][3], matrix[1][3], matrix[2][3]),
+        );
+        for row in 0..3 {
+            for col in 0..3 {
+                let a = scale_extent(matrix[row][col], self.min[col]);
+                let b = scale_extent(matrix[row][col], self.max[col]);
+                transformed.min[row] += a.min(b);
+                transformed.max[row] += a.max(b);
+            }
+        }
+        transformed
+    }
+
+    // The usual slab test, slightly padded so that rays grazing the edge of
+    // a shape aren't thrown away before the shape itself gets a say.
+    pub fn intersects(&self, ray: &ray::Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let mut tmin = -f64::INFINITY;
+        let mut tmax = f64::INFINITY;
+        for axis in 0..3 {
+            let minimum = self.min[axis] - EPSILON;
+            let maximum = self.max[axis] + EPSILON;
+            let origin = ray.origin[axis];
+            let direction = ray.direction[axis];
+            if direction == 0. {
+                if origin < minimum || origin > maximum {
+                    return false;
+                }
+            } else {
+                let t1 = (minimum - origin) / direction;
+                let t2 = (maximum - origin) / direction;
+                tmin = tmin.max(t1.min(t2));
+                tmax = tmax.min(t1.max(t2));
+            }
+        }
+        tmin <= tmax
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_1_SQRT_2, PI, SQRT_2};
+
+    use crate::{float, transform};
+    use crate::matrix::Matrix4Methods;
+    use crate::ray::Ray;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    use super::*;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+    }
+
+    #[test]
+    fn test_add_point() {
+        let mut bounds = BoundingBox::empty();
+        assert!(bounds.is_empty());
+        bounds.add_point(Tuple::point(-5., 2., 0.));
+        bounds.add_point(Tuple::point(7., 0., 1.5));
+        assert_eq!(bounds.min, Tuple::point(-5., 0., 0.));
+        assert_eq!(bounds.max, Tuple::point(7., 2., 1.5));
+        assert!(!bounds.is_empty());
+    }
+
+    #[test]
+    fn test_union() {
+        let first = BoundingBox::new(Tuple::point(-5., -2., 0.), Tuple::point(7., 4., 4.));
+        let second = BoundingBox::new(Tuple::point(8., -7., -2.), Tuple::point(14., 2., 8.));
+        let combined = first.union(&second);
+        assert_eq!(combined.min, Tuple::point(-5., -7., -2.));
+        assert_eq!(combined.max, Tuple::point(14., 4., 8.));
+        assert_eq!(BoundingBox::empty().union(&first), first);
+    }
+
+    #[test]
+    fn test_volume() {
+        assert_eq!(unit_box().volume(), 8.);
+        assert_eq!(BoundingBox::empty().volume(), 0.);
+        assert_eq!(BoundingBox::infinite().volume(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_transform() {
+        let matrix = transform::rotation_x(PI/4.).multiply_matrix(transform::rotation_y(PI/4.));
+        let transformed = unit_box().transform(matrix);
+        let expected_min = Tuple::point(-SQRT_2, -1. - FRAC_1_SQRT_2, -1. - FRAC_1_SQRT_2);
+        let expected_max = Tuple::point(SQRT_2, 1. + FRAC_1_SQRT_2, 1. + FRAC_1_SQRT_2);
+        for axis in 0..3 {
+            assert!(float::is_equal(transformed.min[axis], expected_min[axis]));
+            assert!(float::is_equal(transformed.max[axis], expected_max[axis]));
+        }
+    }
+
+    #[test]
+    fn test_transform_infinite_box() {
+        let plane_bounds = BoundingBox::new(
+            Tuple::point(-f64::INFINITY, 0., -f64::INFINITY),
+            Tuple::point(f64::INFINITY, 0., f64::INFINITY),
+        );
+        let transformed = plane_bounds.transform(transform::translation(0., -1., 0.));
+        assert_eq!(transformed.min, Tuple::point(-f64::INFINITY, -1., -f64::INFINITY));
+        assert_eq!(transformed.max, Tuple::point(f64::INFINITY, -1., f64::INFINITY));
+    }
+
+    #[test]
+    fn test_intersects() {
+        let bounds = BoundingBox::new(Tuple::point(5., -2., 0.), Tuple::point(11., 4., 7.));
+        let test_cases = vec![
+            (Tuple::point(15., 1., 2.), Tuple::vector(-1., 0., 0.), true),
+            (Tuple::point(-5., -1., 4.), Tuple::vector(1., 0., 0.), true),
+            (Tuple::point(7., 6., 5.), Tuple::vector(0., -1., 0.), true),
+            (Tuple::point(8., 2., 12.), Tuple::vector(0., 0., -1.), true),
+            (Tuple::point(9., -1., -8.), Tuple::vector(0., 1., 4.).normalize(), true),
+            (Tuple::point(9., -1., -8.), Tuple::vector(0., 0., 1.), true),
+            (Tuple::point(12., 5., 4.), Tuple::vector(1., 0., 0.), false),
+            (Tuple::point(9., -1., -8.), Tuple::vector(1., 0., 0.), false),
+            (Tuple::point(12., 2., 4.), Tuple::vector(0., -1., 0.), false),
+        ];
+
+        for (origin, direction, expected) in test_cases {
+            let ray = Ray::new(origin, direction);
+            assert_eq!(bounds.intersects(&ray), expected);
+        }
+    }
+}
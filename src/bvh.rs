@@ -0,0 +1,205 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::Intersections;
+use crate::object::Object;
+use crate::ray;
+
+// Nodes holding this many objects or fewer aren't split any further.
+const MAX_LEAF_SIZE: usize = 4;
+
+enum BvhNode {
+    Leaf {
+        bounds: BoundingBox,
+        objects: Vec<usize>,
+    },
+    Branch {
+        bounds: BoundingBox,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+// A bounding volume hierarchy over the objects in a world, referring to
+// them by their index. Objects that go on forever, like planes, can't be
+// usefully boxed up, so they are kept to one side and always tested.
+pub struct Bvh {
+    root: Option<BvhNode>,
+    unbounded: Vec<usize>,
+}
+
+fn centroid(bounds: &BoundingBox, axis: usize) -> f64 {
+    (bounds.min[axis] + bounds.max[axis]) / 2.
+}
+
+// Splits the objects in half along whichever axis their centers are most
+// spread out over.
+fn build_node(mut entries: Vec<(usize, BoundingBox)>) -> BvhNode {
+    let bounds = entries
+        .iter()
+        .fold(BoundingBox::empty(), |bounds, (_, b)| bounds.union(b));
+    if entries.len() <= MAX_LEAF_SIZE {
+        return BvhNode::Leaf {
+            bounds,
+            objects: entries.into_iter().map(|(index, _)| index).collect(),
+        };
+    }
+
+    let mut centroids = BoundingBox::empty();
+    for (_, b) in entries.iter() {
+        let mut center = b.min;
+        for (axis, coordinate) in center.iter_mut().enumerate().take(3) {
+            *coordinate = centroid(b, axis);
+        }
+        centroids.add_point(center);
+    }
+    let axis = (0..3)
+        .max_by(|&a1, &a2| {
+            let extent1 = centroids.max[a1] - centroids.min[a1];
+            let extent2 = centroids.max[a2] - centroids.min[a2];
+            extent1.total_cmp(&extent2)
+        })
+        .unwrap();
+
+    entries.sort_by(|(_, b1), (_, b2)| centroid(b1, axis).total_cmp(&centroid(b2, axis)));
+    let right = entries.split_off(entries.len() / 2);
+    BvhNode::Branch {
+        bounds,
+        left: Box::new(build_node(entries)),
+        right: Box::new(build_node(right)),
+    }
+}
+
+impl Bvh {
+    pub fn build(objects: &[Object]) -> Bvh {
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            if object.is_bounded() {
+                bounded.push((index, object.bounds()));
+            } else {
+                unbounded.push(index);
+            }
+        }
+
+        let root = if bounded.is_empty() {
+            None
+        } else {
+            Some(build_node(bounded))
+        };
+        Bvh { root, unbounded }
+    }
+
+    // Intersects the ray with every object it might hit for which `include`
+    // holds, skipping whole subtrees whose bounds the ray misses. The
+    // intersections are left unsorted.
+    pub fn intersect<F>(&self, ray: &ray::Ray, objects: &[Object], include: F) -> Intersections
+    where
+        F: Fn(&Object) -> bool,
+    {
+        let mut candidates = self.unbounded.clone();
+        if let Some(root) = &self.root {
+            collect_candidates(root, ray, &mut candidates);
+        }
+
+        let mut all_intersections = Intersections::new();
+        for index in candidates {
+            let object = &objects[index];
+            if include(object) {
+                object.extend_intersections(ray, &mut all_intersections);
+            }
+        }
+        all_intersections
+    }
+}
+
+fn collect_candidates(node: &BvhNode, ray: &ray::Ray, candidates: &mut Vec<usize>) {
+    if !node.bounds().intersects(ray) {
+        return;
+    }
+    match node {
+        BvhNode::Leaf { objects, .. } => candidates.extend(objects.iter().cloned()),
+        BvhNode::Branch { left, right, .. } => {
+            collect_candidates(left, ray, candidates);
+            collect_candidates(right, ray, candidates);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{material, matrix, transform};
+    use crate::bvh::Bvh;
+    use crate::instance::Instanced;
+    use crate::object::Object;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    fn row_of_spheres(count: usize) -> Vec<Object> {
+        (0..count)
+            .map(|i| {
+                let t = transform::translation(3. * i as f64, 0., 0.);
+                Object::Sphere(Sphere::new(t, material::DEFAULT_MATERIAL))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_intersect_finds_only_nearby_objects() {
+        let objects = row_of_spheres(50);
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(Tuple::point(30., 0., -5.), Tuple::vector(0., 0., 1.));
+        let intersections = bvh.intersect(&ray, &objects, |_| true);
+        let ts: Vec<f64> = intersections.iter().map(|i| i.t).collect();
+        assert_eq!(ts, [4., 6.]);
+        assert!(intersections.iter().all(|i| i.object_id == objects[10].get_id()));
+    }
+
+    #[test]
+    fn test_intersect_always_tests_unbounded_objects() {
+        let mut objects = row_of_spheres(10);
+        objects.push(Object::Plane(Plane::new(transform::translation(0., -1., 0.), material::DEFAULT_MATERIAL)));
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(Tuple::point(100., 5., 0.), Tuple::vector(0., -1., 0.));
+        let ts: Vec<f64> = bvh.intersect(&ray, &objects, |_| true).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [6.]);
+    }
+
+    #[test]
+    fn test_build_with_instances() {
+        let shared = Arc::new(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let mut objects: Vec<Object> = (0..6)
+            .map(|i| {
+                let t = transform::translation(3. * i as f64, 0., 0.);
+                Object::Instanced(Instanced::new(shared.clone(), t, material::DEFAULT_MATERIAL))
+            })
+            .collect();
+        objects.push(Object::Sphere(Sphere::new(transform::translation(0., 5., 0.), material::DEFAULT_MATERIAL)));
+        let bvh = Bvh::build(&objects);
+        assert!(bvh.unbounded.is_empty());
+        let ray = Ray::new(Tuple::point(6., 0., -5.), Tuple::vector(0., 0., 1.));
+        let intersections = bvh.intersect(&ray, &objects, |_| true);
+        assert_eq!(intersections.len(), 2);
+        assert!(intersections.iter().all(|i| i.object_id == objects[2].get_id()));
+    }
+
+    #[test]
+    fn test_intersect_skips_excluded_objects() {
+        let objects = vec![Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL))];
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        assert_eq!(bvh.intersect(&ray, &objects, |_| true).len(), 2);
+        assert!(bvh.intersect(&ray, &objects, |_| false).is_empty());
+    }
+}
@@ -1,10 +1,26 @@
-use crate::canvas::Canvas;
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Error, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::canvas::{Accumulator, Canvas};
+use crate::color::Color;
+use crate::hook::{NoHook, RayCounter, RayKind, RenderHook};
+use crate::{intersection, noise, transform};
+use crate::intersection::Computations;
 use crate::matrix::{Matrix4, Matrix4Methods};
+use crate::object::Object;
 use crate::ray::Ray;
 use crate::tuple::{Tuple, TupleMethods};
 use crate::world::World;
 use crate::world;
 
+#[derive(Serialize, Deserialize)]
 pub struct Camera {
     pub view: Matrix4,
     pub view_inverse: Matrix4,
@@ -14,8 +30,158 @@ pub struct Camera {
     pub half_width: f64,
     pub half_height: f64,
     pub pixel_size: f64,
+    pub suppress_performance_warnings: bool,
+    // How many threads render_parallel uses; None means one per logical core.
+    pub render_threads: Option<usize>,
+    // Rays traced through each pixel, jittered about and averaged together
+    // to smooth out jagged edges.
+    #[serde(default = "default_samples_per_pixel")]
+    pub samples_per_pixel: usize,
+    #[serde(default = "default_max_reflections")]
+    pub max_reflections: usize,
+    #[serde(default)]
+    pub render_mode: RenderMode,
+}
+
+fn default_samples_per_pixel() -> usize {
+    1
+}
+
+fn default_max_reflections() -> usize {
+    world::MAX_RECURSIONS
+}
+
+// What gets drawn for each pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum RenderMode {
+    // Fully lit, with reflections and refractions.
+    #[default]
+    Shaded,
+    // The world space normal at each hit encoded as a color, for debugging.
+    Normals,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RenderError {
+    EmptyScene,
+    InvalidCamera(String),
+    // A ray kept bouncing around without ever settling on a color.
+    InfiniteRecursion,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::EmptyScene => write!(f, "there are no objects in the scene"),
+            RenderError::InvalidCamera(reason) => write!(f, "invalid camera: {}", reason),
+            RenderError::InfiniteRecursion => write!(f, "rays were reflected or refracted without end"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+// How long each phase of a render took, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderTiming {
+    pub total_ms: u64,
+    pub setup_ms: u64,
+    pub trace_ms: u64,
+    pub shade_ms: u64,
+    pub output_ms: u64,
+}
+
+// Lays the phases out as a table, for printing after a render.
+impl RenderTiming {
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+}
+
+impl fmt::Display for RenderTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("setup", self.setup_ms),
+            ("trace", self.trace_ms),
+            ("shade", self.shade_ms),
+            ("output", self.output_ms),
+            ("total", self.total_ms),
+        ];
+        writeln!(f, "{:<8}{:>10}", "phase", "ms")?;
+        for (phase, ms) in rows.iter() {
+            writeln!(f, "{:<8}{:>10}", phase, ms)?;
+        }
+        Ok(())
+    }
+}
+
+// Splits the time spent on each pixel between tracing and shading. Rays
+// are reported as hitting or missing as soon as their intersections are
+// found, so the time leading up to that is tracing, and the time leading
+// up to any other event is shading.
+struct PhaseTimer {
+    last_event: Instant,
+    trace: Duration,
+    shade: Duration,
+}
+
+impl PhaseTimer {
+    fn new() -> PhaseTimer {
+        PhaseTimer {
+            last_event: Instant::now(),
+            trace: Duration::ZERO,
+            shade: Duration::ZERO,
+        }
+    }
+
+    fn start_pixel(&mut self) {
+        self.last_event = Instant::now();
+    }
+
+    fn end_tracing(&mut self) {
+        let now = Instant::now();
+        self.trace += now - self.last_event;
+        self.last_event = now;
+    }
+
+    fn end_shading(&mut self) {
+        let now = Instant::now();
+        self.shade += now - self.last_event;
+        self.last_event = now;
+    }
+}
+
+impl RenderHook for PhaseTimer {
+    fn on_hit(&mut self, _ray: &Ray, _t: f64, _object: &Object) {
+        self.end_tracing();
+    }
+
+    fn on_miss(&mut self, _ray: &Ray) {
+        self.end_tracing();
+    }
+
+    fn on_shadow_test(&mut self, _point: Tuple, _is_shadowed: bool) {
+        self.end_shading();
+    }
+
+    fn on_total_internal_reflection(&mut self, _comp: &Computations) {
+        self.end_shading();
+    }
+
+    fn on_shade(&mut self, _comp: &Computations, _color: Color) {
+        self.end_shading();
+    }
 }
 
+// Scenes with more objects than this are slow to render when every ray
+// has to be tested against every object.
+const MANY_OBJECTS: usize = 1000;
+
+// Without the `rayon` feature every sample is traced on a single thread,
+// so this many samples per pixel or more is slow.
+#[cfg(not(feature = "rayon"))]
+const MANY_SAMPLES: usize = 16;
+
 impl Camera {
     pub fn new(view: Matrix4, horizontal_size: usize, vertical_size: usize, field_of_view: f64) -> Camera {
         let half_view = (field_of_view / 2.).tan();
@@ -40,13 +206,49 @@ impl Camera {
             half_width: half_width,
             half_height: half_height,
             pixel_size: pixel_size,
+            suppress_performance_warnings: false,
+            render_threads: None,
+            samples_per_pixel: 1,
+            max_reflections: world::MAX_RECURSIONS,
+            render_mode: RenderMode::Shaded,
         }
     }
 
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.render_threads = Some(threads);
+        self
+    }
+
+    pub fn with_samples(mut self, n: usize) -> Self {
+        self.samples_per_pixel = n;
+        self
+    }
+
+    pub fn with_max_reflections(mut self, n: usize) -> Self {
+        self.max_reflections = n;
+        self
+    }
+
+    pub fn with_render_mode(mut self, mode: RenderMode) -> Self {
+        self.render_mode = mode;
+        self
+    }
+
+    // Builds a camera positioned at `from` and pointed toward `to`.
+    pub fn look_at(from: Tuple, to: Tuple, up: Tuple, horizontal_size: usize, vertical_size: usize, field_of_view: f64) -> Camera {
+        Camera::new(transform::look_at(from, to, up), horizontal_size, vertical_size, field_of_view)
+    }
+
     pub fn ray_at(&self, pixel_x: usize, pixel_y: usize) -> Ray {
-        // The offset from the edge of the canvas to the pixel's center
-        let offset_x = (pixel_x as f64 + 0.5) * self.pixel_size;
-        let offset_y = (pixel_y as f64 + 0.5) * self.pixel_size;
+        self.ray_through(pixel_x as f64 + 0.5, pixel_y as f64 + 0.5)
+    }
+
+    // Casts a ray through any point on the canvas, measured in pixels from
+    // its top left corner.
+    fn ray_through(&self, canvas_x: f64, canvas_y: f64) -> Ray {
+        // The offset from the edge of the canvas to the point
+        let offset_x = canvas_x * self.pixel_size;
+        let offset_y = canvas_y * self.pixel_size;
 
         // The untransformed coordinates of the pixel in world space.
         // (Remember that the camera looks toward -z, so +x is to the *left*.)
@@ -63,26 +265,424 @@ impl Camera {
         Ray::new(origin, direction)
     }
 
-    pub fn render(&self, world: World) -> Canvas {
+    // Returns warnings about scenes that are likely to render slowly.
+    pub fn performance_warnings(&self, world: &World) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.suppress_performance_warnings {
+            return warnings;
+        }
+        if world.len() > MANY_OBJECTS && !world.has_bvh() {
+            warnings.push(format!(
+                "Warning: rendering {} objects without a BVH; this may be slow. Call World::build_bvh first.",
+                world.len()
+            ));
+        }
+        #[cfg(not(feature = "rayon"))]
+        if self.samples_per_pixel > MANY_SAMPLES {
+            warnings.push(format!(
+                "Warning: taking {} samples per pixel on a single thread; this may be slow. Enable the rayon feature to render in parallel.",
+                self.samples_per_pixel
+            ));
+        }
+        warnings
+    }
+
+    fn warn_about_performance(&self, world: &World) {
+        for warning in self.performance_warnings(world) {
+            eprintln!("{}", warning);
+        }
+    }
+
+    // Renders the world after making sure there's something to render and
+    // somewhere to render it to. The world is only borrowed, so it isn't
+    // precomputed here; call World::precompute first to speed up scenes
+    // with lots of objects.
+    pub fn render(&self, world: &World) -> Result<Canvas, RenderError> {
+        self.check_preconditions(world)?;
+        Ok(self.render_unchecked(world))
+    }
+
+    fn check_preconditions(&self, world: &World) -> Result<(), RenderError> {
+        if world.is_empty() {
+            return Err(RenderError::EmptyScene);
+        }
+        if self.horizontal_size == 0 || self.vertical_size == 0 {
+            return Err(RenderError::InvalidCamera(format!(
+                "the canvas can't be {}x{} pixels",
+                self.horizontal_size, self.vertical_size
+            )));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(RenderError::InvalidCamera(String::from("there must be at least one sample per pixel")));
+        }
+        Ok(())
+    }
+
+    pub fn render_unchecked(&self, world: &World) -> Canvas {
+        self.warn_about_performance(world);
         let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
-        for y in 0..self.vertical_size - 1 {
-            for x in 0..self.horizontal_size - 1 {
+        for y in 0..self.vertical_size {
+            self.render_row(world, &mut canvas, y);
+        }
+        canvas
+    }
+
+    // Renders the world one pixel at a time, reporting every ray traced to
+    // the hook.
+    pub fn render_hooked<H: RenderHook>(&self, world: &World, hook: &mut H) -> Canvas {
+        let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
                 let ray = self.ray_at(x, y);
-                let color = world.color_at(&ray, world::MAX_RECURSIONS);
+                hook.on_primary_ray(x, y, &ray);
+                hook.on_ray_cast(RayKind::Primary);
+                let color = world.color_at_with(&ray, self.max_reflections, hook);
+                canvas.set_pixel(x, y, color);
+            }
+        }
+        canvas
+    }
+
+    // Renders the world like render_unchecked, while keeping track of where
+    // the time goes. Setup covers the allocation done before the first ray
+    // goes out; precompute the world beforehand, since that isn't timed.
+    // Tracing is finding what each ray hits, primary or secondary, and
+    // shading is everything else that goes into a pixel's color.
+    pub fn render_timed(&self, world: &World) -> (Canvas, RenderTiming) {
+        let start = Instant::now();
+        self.warn_about_performance(world);
+        let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
+        let mut colors = Vec::with_capacity(self.horizontal_size * self.vertical_size);
+        let setup = start.elapsed();
+
+        let mut timer = PhaseTimer::new();
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
+                timer.start_pixel();
+                colors.push(self.color_at_pixel_with(world, x, y, &mut timer));
+                timer.end_shading();
+            }
+        }
+
+        let output_start = Instant::now();
+        for (i, color) in colors.into_iter().enumerate() {
+            canvas.set_pixel(i % self.horizontal_size, i / self.horizontal_size, color);
+        }
+        let output = output_start.elapsed();
+
+        let timing = RenderTiming {
+            total_ms: start.elapsed().as_millis() as u64,
+            setup_ms: setup.as_millis() as u64,
+            trace_ms: timer.trace.as_millis() as u64,
+            shade_ms: timer.shade.as_millis() as u64,
+            output_ms: output.as_millis() as u64,
+        };
+        (canvas, timing)
+    }
+
+    // Renders rows concurrently, either on Rayon's global thread pool or
+    // on a pool of its own when the number of threads has been set.
+    #[cfg(feature = "rayon")]
+    pub fn render_parallel(&self, world: &World) -> Canvas {
+        self.warn_about_performance(world);
+        match self.render_threads {
+            None => self.render_rows_in_parallel(world),
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .expect("failed to create render thread pool");
+                pool.install(|| self.render_rows_in_parallel(world))
+            },
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    fn render_rows_in_parallel(&self, world: &World) -> Canvas {
+        use rayon::prelude::*;
+
+        let rows: Vec<Vec<Color>> = (0..self.vertical_size)
+            .into_par_iter()
+            .map(|y| {
+                (0..self.horizontal_size)
+                    .map(|x| self.color_at_pixel(world, x, y))
+                    .collect()
+            })
+            .collect();
+
+        let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
+        for (y, row) in rows.into_iter().enumerate() {
+            for (x, color) in row.into_iter().enumerate() {
                 canvas.set_pixel(x, y, color);
             }
         }
         canvas
     }
+
+    // Renders the world over and over, with a differently jittered ray
+    // through each pixel every time, averaging the passes together. The
+    // callback gets the average so far after each pass, along with how
+    // many passes it's made of, to show progress or save snapshots.
+    pub fn render_accumulating(&self, world: &World, iterations: usize, on_iteration: impl Fn(&Canvas, usize)) -> Canvas {
+        self.warn_about_performance(world);
+        let accumulator = Mutex::new(Accumulator::new(self.horizontal_size, self.vertical_size));
+        for iteration in 0..iterations {
+            self.accumulate_pass(world, iteration, &accumulator);
+            on_iteration(&accumulator.lock().unwrap().to_canvas(), iteration + 1);
+        }
+        let accumulator = accumulator.into_inner().unwrap();
+        accumulator.to_canvas()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn accumulate_pass(&self, world: &World, iteration: usize, accumulator: &Mutex<Accumulator>) {
+        use rayon::prelude::*;
+
+        (0..self.vertical_size)
+            .into_par_iter()
+            .for_each(|y| self.accumulate_row(world, iteration, accumulator, y));
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn accumulate_pass(&self, world: &World, iteration: usize, accumulator: &Mutex<Accumulator>) {
+        for y in 0..self.vertical_size {
+            self.accumulate_row(world, iteration, accumulator, y);
+        }
+    }
+
+    // Traces a whole row before taking the lock, so that threads working on
+    // other rows aren't kept waiting.
+    fn accumulate_row(&self, world: &World, iteration: usize, accumulator: &Mutex<Accumulator>, y: usize) {
+        let row: Vec<Color> = (0..self.horizontal_size)
+            .map(|x| {
+                let mut state = (((y as u64) << 32) ^ x as u64) ^ (iteration as u64).rotate_left(48);
+                let mut jitter = || (noise::splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64;
+                let ray = self.ray_through(x as f64 + jitter(), y as f64 + jitter());
+                self.color_along(world, &ray)
+            })
+            .collect();
+
+        let mut accumulator = accumulator.lock().unwrap();
+        for (x, color) in row.into_iter().enumerate() {
+            accumulator.add_sample(x, y, color);
+        }
+    }
+
+    fn render_row(&self, world: &World, canvas: &mut Canvas, y: usize) {
+        for x in 0..self.horizontal_size {
+            let color = self.color_at_pixel(world, x, y);
+            canvas.set_pixel(x, y, color);
+        }
+    }
+
+    // Averages the samples taken for a pixel, jittered by an amount that
+    // only depends on where the pixel is, so renders are repeatable.
+    fn color_at_pixel(&self, world: &World, x: usize, y: usize) -> Color {
+        self.color_at_pixel_with(world, x, y, &mut NoHook)
+    }
+
+    fn color_at_pixel_with<H: RenderHook>(&self, world: &World, x: usize, y: usize, hook: &mut H) -> Color {
+        if self.samples_per_pixel <= 1 {
+            return self.color_along_with(world, &self.ray_at(x, y), hook);
+        }
+
+        let mut total = Color::new(0., 0., 0.);
+        for ray in self.pixel_rays(x, y) {
+            total = total.add(self.color_along_with(world, &ray, hook));
+        }
+        total.multiply(1. / self.samples_per_pixel as f64)
+    }
+
+    // The rays sent out through a pixel, one per sample.
+    fn pixel_rays(&self, x: usize, y: usize) -> Vec<Ray> {
+        if self.samples_per_pixel <= 1 {
+            return vec![self.ray_at(x, y)];
+        }
+
+        let mut state = ((y as u64) << 32) ^ x as u64;
+        let mut jitter = || (noise::splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64;
+        (0..self.samples_per_pixel)
+            .map(|_| self.ray_through(x as f64 + jitter(), y as f64 + jitter()))
+            .collect()
+    }
+
+    // Works out how many rays a render would trace, following every ray
+    // the same way but without shading anything, to get an idea of how
+    // long a render will take before starting it.
+    pub fn count_rays(&self, world: &World) -> u64 {
+        let mut counter = RayCounter::default();
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
+                for ray in self.pixel_rays(x, y) {
+                    self.color_along_with(world, &ray, &mut counter);
+                }
+            }
+        }
+        counter.total()
+    }
+
+    fn color_along(&self, world: &World, ray: &Ray) -> Color {
+        self.color_along_with(world, ray, &mut NoHook)
+    }
+
+    fn color_along_with<H: RenderHook>(&self, world: &World, ray: &Ray, hook: &mut H) -> Color {
+        hook.on_ray_cast(RayKind::Primary);
+        match self.render_mode {
+            RenderMode::Shaded => world.color_at_with(ray, self.max_reflections, hook),
+            RenderMode::Normals => normal_color(world, ray, hook),
+        }
+    }
+
+    // Renders the world while recording each finished row in a checkpoint
+    // file, so that an interrupted render can pick up where it left off.
+    // The checkpoint is removed once the whole canvas is done.
+    pub fn render_checkpoint(&self, world: &World, checkpoint_path: &str) -> Result<Canvas, Error> {
+        self.warn_about_performance(world);
+        let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
+
+        // Recover whatever rows were completed last time; a partially
+        // written last line is thrown away and that row rendered again.
+        let mut completed_rows = 0;
+        if Path::new(checkpoint_path).exists() {
+            for line in fs::read_to_string(checkpoint_path)?.lines() {
+                if completed_rows == self.vertical_size {
+                    break;
+                }
+                match parse_checkpoint_row(line, self.horizontal_size) {
+                    Some(row) => {
+                        for (x, color) in row.into_iter().enumerate() {
+                            canvas.set_pixel(x, completed_rows, color);
+                        }
+                        completed_rows += 1;
+                    },
+                    None => break,
+                }
+            }
+        }
+
+        // Rewrite the checkpoint with only the good rows before appending.
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(checkpoint_path)?;
+        for y in 0..completed_rows {
+            writeln!(file, "{}", format_checkpoint_row(&canvas, y))?;
+        }
+
+        for y in completed_rows..self.vertical_size {
+            self.render_row(world, &mut canvas, y);
+            writeln!(file, "{}", format_checkpoint_row(&canvas, y))?;
+            file.flush()?;
+        }
+
+        drop(file);
+        fs::remove_file(checkpoint_path)?;
+        Ok(canvas)
+    }
+
+    // Debug pass that encodes the world space normal at each primary hit
+    // as a color; misses are mid grey.
+    pub fn render_normals(&self, world: &World) -> Canvas {
+        let mut canvas = Canvas::new(self.horizontal_size, self.vertical_size);
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
+                let color = normal_color(world, &self.ray_at(x, y), &mut NoHook);
+                canvas.set_pixel(x, y, color);
+            }
+        }
+        canvas
+    }
+
+    // Debug pass that returns the distance to the nearest primary hit for
+    // each pixel, in row major order; misses are infinitely far away.
+    pub fn render_depth(&self, world: &World) -> Vec<f64> {
+        let mut depths = Vec::with_capacity(self.horizontal_size * self.vertical_size);
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
+                let ray = self.ray_at(x, y);
+                let intersections = world.intersect(&ray);
+                let depth = match intersection::hit(&intersections) {
+                    Some(hit) => hit.t,
+                    None => f64::INFINITY,
+                };
+                depths.push(depth);
+            }
+        }
+        depths
+    }
+
+    // Returns the ID of the object seen through each pixel, in row major
+    // order, or None where the primary ray misses everything.
+    pub fn render_object_ids(&self, world: &World) -> Vec<Option<u64>> {
+        let mut ids = Vec::with_capacity(self.horizontal_size * self.vertical_size);
+        for y in 0..self.vertical_size {
+            for x in 0..self.horizontal_size {
+                let ray = self.ray_at(x, y);
+                let intersections = world.intersect(&ray);
+                let id = intersection::hit(&intersections)
+                    .map(|hit| hit.object_id);
+                ids.push(id);
+            }
+        }
+        ids
+    }
+}
+
+fn normal_color<H: RenderHook>(world: &World, ray: &Ray, hook: &mut H) -> Color {
+    let intersections = world.intersect(ray);
+    match intersection::hit(&intersections) {
+        Some(hit) => {
+            let object = hit.object(world);
+            hook.on_hit(ray, hit.t, object);
+            let normal = object.normal_at(ray.position_at(hit.t));
+            Color::new(
+                (normal[0] + 1.) / 2.,
+                (normal[1] + 1.) / 2.,
+                (normal[2] + 1.) / 2.,
+            )
+        },
+        None => {
+            hook.on_miss(ray);
+            Color::new(0.5, 0.5, 0.5)
+        },
+    }
+}
+
+// Colors are written with Rust's shortest round-tripping float formatting,
+// so resumed rows are bit for bit identical to freshly rendered ones.
+fn format_checkpoint_row(canvas: &Canvas, y: usize) -> String {
+    (0..canvas.width)
+        .map(|x| {
+            let color = canvas.get_pixel(x, y);
+            format!("{} {} {}", color.r, color.g, color.b)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_checkpoint_row(line: &str, width: usize) -> Option<Vec<Color>> {
+    let values = line
+        .split_whitespace()
+        .map(|value| value.parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    if values.len() != width * 3 {
+        return None;
+    }
+    Some(values
+        .chunks(3)
+        .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2]))
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use std::f64::consts::PI;
     use crate::{color, float, light, material, matrix, sphere, transform, tuple};
-    use crate::color::Color;
     use crate::material::Coloring::SolidColor;
+    use crate::light::EnvironmentLight;
     use crate::object::Object;
+    use crate::world::Background;
     use super::*;
 
     #[test]
@@ -142,6 +742,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -154,10 +755,215 @@ mod tests {
         );
 
         let objects = vec![s1, s2];
-        return World {
-            light: light,
-            objects: objects,
-        };
+        return World::new(light, objects);
+    }
+
+    #[test]
+    fn test_look_at_constructor() {
+        let from = Tuple::point(0., 2., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let camera = Camera::look_at(from, to, up, 201, 101, PI/2.);
+        let expected_value = Camera::new(transform::look_at(from, to, up), 201, 101, PI/2.);
+        assert!(camera.view.is_equal(expected_value.view));
+        assert_eq!(camera.horizontal_size, 201);
+        assert_eq!(camera.vertical_size, 101);
+        assert!(float::is_equal(camera.pixel_size, expected_value.pixel_size));
+    }
+
+    #[test]
+    fn test_performance_warnings_for_many_objects() {
+        let objects = (0..1001)
+            .map(|_| Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL)))
+            .collect();
+        let world = World::new(test_world().light, objects);
+        let mut camera = checkpoint_camera();
+        assert!(camera.performance_warnings(&test_world()).is_empty());
+
+        let warnings = camera.performance_warnings(&world);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("1001 objects"));
+
+        let mut accelerated_world = World::new(test_world().light, world.objects().cloned().collect());
+        accelerated_world.build_bvh();
+        assert!(camera.performance_warnings(&accelerated_world).is_empty());
+
+        camera.suppress_performance_warnings = true;
+        assert!(camera.performance_warnings(&world).is_empty());
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    #[test]
+    fn test_performance_warnings_for_many_samples() {
+        let world = test_world();
+        assert!(checkpoint_camera().with_samples(16).performance_warnings(&world).is_empty());
+
+        let warnings = checkpoint_camera().with_samples(17).performance_warnings(&world);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("17 samples per pixel"));
+
+        let mut camera = checkpoint_camera().with_samples(17);
+        camera.suppress_performance_warnings = true;
+        assert!(camera.performance_warnings(&world).is_empty());
+    }
+
+    #[test]
+    fn test_with_threads() {
+        let camera = checkpoint_camera();
+        assert_eq!(camera.render_threads, None);
+        assert_eq!(camera.with_threads(2).render_threads, Some(2));
+    }
+
+    #[test]
+    fn test_builder_methods() {
+        let camera = Camera::new(matrix::IDENTITY, 800, 800, PI/2.)
+            .with_samples(16)
+            .with_max_reflections(10)
+            .with_render_mode(RenderMode::Normals);
+        assert_eq!(camera.samples_per_pixel, 16);
+        assert_eq!(camera.max_reflections, 10);
+        assert_eq!(camera.render_mode, RenderMode::Normals);
+    }
+
+    #[test]
+    fn test_render_with_samples() {
+        let world = test_world();
+        let camera = checkpoint_camera();
+        let single = camera.render(&world).unwrap();
+        let sampled = checkpoint_camera().with_samples(8).render(&world).unwrap();
+
+        // The background looks the same however it's sampled, but shading
+        // across the sphere gets averaged over each pixel.
+        assert_eq!(sampled.get_pixel(0, 0), single.get_pixel(0, 0));
+        let differing = (0..60)
+            .flat_map(|y| (0..11).map(move |x| (x, y)))
+            .filter(|&(x, y)| sampled.get_pixel(x, y) != single.get_pixel(x, y))
+            .count();
+        assert!(differing > 0);
+    }
+
+    #[test]
+    fn test_render_with_max_reflections() {
+        let mirror = material::Material { reflective: 0.5, ..material::DEFAULT_MATERIAL };
+        let world = World::new(
+            test_world().light,
+            vec![
+                Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, mirror)),
+                Object::Sphere(sphere::Sphere::new(
+                    transform::translation(0., 0., -10.).multiply_matrix(transform::scaling(3., 3., 3.)),
+                    material::DEFAULT_MATERIAL,
+                )),
+            ],
+        );
+        let camera = checkpoint_camera();
+        let reflecting = camera.render(&world).unwrap();
+        let flat = checkpoint_camera().with_max_reflections(0).render(&world).unwrap();
+        assert!(reflecting.get_pixel(5, 30) != flat.get_pixel(5, 30));
+    }
+
+    // Counts every ray a full render casts, for checking count_rays.
+    #[derive(Default)]
+    struct CastCounter {
+        casts: u64,
+        shadow_tests: u64,
+    }
+
+    impl RenderHook for CastCounter {
+        fn on_ray_cast(&mut self, _kind: RayKind) {
+            self.casts += 1;
+        }
+
+        fn on_shadow_test(&mut self, _point: Tuple, _is_shadowed: bool) {
+            self.shadow_tests += 1;
+        }
+    }
+
+    #[test]
+    fn test_count_rays() {
+        let mirror = material::Material { reflective: 0.5, ..material::DEFAULT_MATERIAL };
+        let glass = material::Material { transparency: 0.9, refractive: 1.5, ..material::DEFAULT_MATERIAL };
+        let world = World::new(
+            test_world().light,
+            vec![
+                Object::Sphere(sphere::Sphere::new(transform::translation(-1., 0., 0.), mirror)),
+                Object::Sphere(sphere::Sphere::new(transform::translation(1., 0., 0.), glass)),
+            ],
+        );
+        let camera = checkpoint_camera();
+        let mut counter = CastCounter::default();
+        camera.render_hooked(&world, &mut counter);
+        assert_eq!(camera.count_rays(&world), counter.casts);
+
+        let pixels = (camera.horizontal_size * camera.vertical_size) as u64;
+        assert!(camera.count_rays(&world) > pixels);
+        assert_eq!(camera.with_render_mode(RenderMode::Normals).count_rays(&world), pixels);
+        assert_eq!(checkpoint_camera().with_samples(3).with_render_mode(RenderMode::Normals).count_rays(&world), 3 * pixels);
+    }
+
+    #[test]
+    fn test_count_rays_with_environment() {
+        let canvas = Canvas::new(4, 2);
+        let environment = EnvironmentLight::new(canvas, 1.0, matrix::IDENTITY);
+        let world = test_world().with_background(Background::Environment(Box::new(environment)));
+        let camera = checkpoint_camera();
+        let mut counter = CastCounter::default();
+        camera.render_hooked(&world, &mut counter);
+        assert_eq!(camera.count_rays(&world), counter.casts);
+
+        // Every shaded hit checks for both the light and the environment.
+        let plain = checkpoint_camera().count_rays(&test_world());
+        assert_eq!(camera.count_rays(&world), plain + counter.shadow_tests);
+    }
+
+    #[test]
+    fn test_render_accumulating() {
+        let world = test_world();
+        let camera = checkpoint_camera();
+        let passes = std::cell::RefCell::new(vec![]);
+        let canvas = camera.render_accumulating(&world, 4, |canvas, passes_so_far| {
+            passes.borrow_mut().push((passes_so_far, canvas.get_pixel(0, 0)));
+        });
+
+        // The corner of the canvas only ever sees the background.
+        let passes = passes.into_inner();
+        assert_eq!(passes.iter().map(|(count, _)| *count).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(passes.iter().all(|(_, color)| *color == color::BLACK));
+        assert_eq!(canvas.get_pixel(0, 0), color::BLACK);
+
+        // In the middle of the sphere the shading barely changes across a
+        // pixel, so the jittered average stays close to a plain render.
+        let center = camera.render(&world).unwrap().get_pixel(5, 30);
+        assert!(canvas.get_pixel(5, 30).r > 0.);
+        assert!((canvas.get_pixel(5, 30).r - center.r).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_render_with_normals_mode() {
+        let world = test_world();
+        let camera = checkpoint_camera().with_render_mode(RenderMode::Normals);
+        let canvas = camera.render(&world).unwrap();
+        let expected = camera.render_normals(&world);
+        for y in 0..60 {
+            for x in 0..11 {
+                assert_eq!(canvas.get_pixel(x, y), expected.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_parallel_matches_render() {
+        let camera = checkpoint_camera();
+        let expected_value = camera.render(&test_world()).unwrap();
+        let world = test_world();
+        for camera in [checkpoint_camera(), checkpoint_camera().with_threads(2)] {
+            let canvas = camera.render_parallel(&world);
+            for y in 0..camera.vertical_size {
+                for x in 0..camera.horizontal_size {
+                    assert_eq!(canvas.get_pixel(x, y), expected_value.get_pixel(x, y));
+                }
+            }
+        }
     }
 
     #[test]
@@ -166,10 +972,193 @@ mod tests {
         let from = Tuple::point(0., 0., -5.);
         let to = Tuple::point(0., 0., 0.);
         let up = Tuple::vector(0., 1., 0.);
-        let view = transform::view(from, to, up);
+        let view = transform::look_at(from, to, up);
         let camera = Camera::new(view, 11, 11, PI/2.);
-        let canvas = camera.render(world);
+        let canvas = camera.render(&world).unwrap();
         let expected_value = Color::new(0.38066, 0.47583, 0.2855);
         assert_eq!(canvas.get_pixel(5, 5), expected_value);
     }
+
+    #[test]
+    fn test_render_last_row_and_column() {
+        // The view is narrow enough for the sphere to fill every pixel.
+        let world = test_world();
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let view = transform::look_at(from, to, up);
+        let camera = Camera::new(view, 5, 5, PI/16.);
+        let canvas = camera.render(&world).unwrap();
+        for i in 0..5 {
+            assert_ne!(canvas.get_pixel(4, i), color::BLACK);
+            assert_ne!(canvas.get_pixel(i, 4), color::BLACK);
+        }
+    }
+
+    #[test]
+    fn test_render_timed() {
+        let mut world = test_world();
+        world.precompute();
+        let view = transform::look_at(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+        );
+        let camera = Camera::new(view, 11, 11, PI/2.).with_samples(4);
+        let (canvas, timing) = camera.render_timed(&world);
+        let expected = camera.render(&world).unwrap();
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_eq!(canvas.get_pixel(x, y), expected.get_pixel(x, y));
+            }
+        }
+        assert!(timing.setup_ms + timing.trace_ms + timing.shade_ms + timing.output_ms <= timing.total_ms);
+
+        let camera = camera.with_render_mode(RenderMode::Normals);
+        let (canvas, _) = camera.render_timed(&world);
+        assert_eq!(canvas.get_pixel(5, 5), camera.render(&world).unwrap().get_pixel(5, 5));
+
+        let table = timing.to_string();
+        assert_eq!(table.lines().count(), 6);
+        assert!(table.lines().last().unwrap().starts_with("total"));
+    }
+
+    #[test]
+    fn test_render_checks_preconditions() {
+        let camera = checkpoint_camera();
+        assert_eq!(camera.render(&World::empty()).err(), Some(RenderError::EmptyScene));
+
+        let camera = Camera::new(matrix::IDENTITY, 0, 10, PI/2.);
+        let error = camera.render(&test_world()).err().unwrap();
+        assert!(matches!(error, RenderError::InvalidCamera(_)));
+        assert_eq!(error.to_string(), "invalid camera: the canvas can't be 0x10 pixels");
+
+        let camera = checkpoint_camera().with_samples(0);
+        let error = camera.render(&test_world()).err().unwrap();
+        assert!(matches!(error, RenderError::InvalidCamera(_)));
+    }
+
+    fn checkpoint_camera() -> Camera {
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        Camera::look_at(from, to, up, 11, 60, PI/2.)
+    }
+
+    #[test]
+    fn test_render_checkpoint_without_checkpoint() {
+        let world = test_world();
+        let camera = checkpoint_camera();
+        let checkpoint_path = "test_render_checkpoint_without_checkpoint.txt";
+        let canvas = camera.render_checkpoint(&world, checkpoint_path).unwrap();
+        let expected_value = camera.render(&test_world()).unwrap();
+        for y in 0..camera.vertical_size {
+            for x in 0..camera.horizontal_size {
+                assert_eq!(canvas.get_pixel(x, y), expected_value.get_pixel(x, y));
+            }
+        }
+        assert!(!Path::new(checkpoint_path).exists());
+    }
+
+    #[test]
+    fn test_render_checkpoint_resumes_interrupted_render() {
+        let world = test_world();
+        let camera = checkpoint_camera();
+        let expected_value = camera.render(&test_world()).unwrap();
+
+        // Simulate a render that was interrupted while writing row 50
+        let checkpoint_path = "test_render_checkpoint_resumes_interrupted_render.txt";
+        let mut contents = String::new();
+        for y in 0..50 {
+            contents.push_str(&format_checkpoint_row(&expected_value, y));
+            contents.push('\n');
+        }
+        contents.push_str("0.5 0.5");
+        fs::write(checkpoint_path, contents).unwrap();
+
+        let canvas = camera.render_checkpoint(&world, checkpoint_path).unwrap();
+        for y in 0..camera.vertical_size {
+            for x in 0..camera.horizontal_size {
+                assert_eq!(canvas.get_pixel(x, y), expected_value.get_pixel(x, y));
+            }
+        }
+        assert!(!Path::new(checkpoint_path).exists());
+    }
+
+    #[test]
+    fn test_render_checkpoint_skips_completed_rows() {
+        let world = test_world();
+        let camera = checkpoint_camera();
+
+        // A completed row is taken as is rather than rendered again
+        let checkpoint_path = "test_render_checkpoint_skips_completed_rows.txt";
+        let red_row = vec!["1 0 0"; camera.horizontal_size].join(" ");
+        fs::write(checkpoint_path, format!("{}\n", red_row)).unwrap();
+
+        let canvas = camera.render_checkpoint(&world, checkpoint_path).unwrap();
+        assert_eq!(canvas.get_pixel(5, 0), Color::new(1., 0., 0.));
+        let expected_value = camera.render(&test_world()).unwrap();
+        assert_eq!(canvas.get_pixel(5, 30), expected_value.get_pixel(5, 30));
+        assert!(!Path::new(checkpoint_path).exists());
+    }
+
+    #[test]
+    fn test_render_normals() {
+        let world = test_world();
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let view = transform::look_at(from, to, up);
+        let camera = Camera::new(view, 11, 11, PI/2.);
+        let canvas = camera.render_normals(&world);
+        // The center pixel sees the front of the sphere, whose normal points
+        // straight back at the camera.
+        assert_eq!(canvas.get_pixel(5, 5), Color::new(0.5, 0.5, 0.));
+        assert_eq!(canvas.get_pixel(0, 0), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_render_depth() {
+        let world = test_world();
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let view = transform::look_at(from, to, up);
+        let camera = Camera::new(view, 11, 11, PI/2.);
+        let depths = camera.render_depth(&world);
+        assert_eq!(depths.len(), 121);
+        assert!(float::is_equal(depths[5 + 5*11], 4.));
+        assert_eq!(depths[0], f64::INFINITY);
+    }
+
+    #[test]
+    fn test_render_object_ids() {
+        let light = light::Light::new(
+            tuple::Tuple::point(-10., 10., -10.),
+            color::WHITE,
+        );
+        let left = Object::Sphere(
+            sphere::Sphere::new(transform::translation(-1.5, 0., 0.), material::DEFAULT_MATERIAL)
+        );
+        let right = Object::Sphere(
+            sphere::Sphere::new(transform::translation(1.5, 0., 0.), material::DEFAULT_MATERIAL)
+        );
+        let left_id = left.get_id();
+        let right_id = right.get_id();
+        assert_ne!(left_id, right_id);
+        let world = World::new(light, vec![left, right]);
+
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let view = transform::look_at(from, to, up);
+        let camera = Camera::new(view, 21, 11, PI/2.);
+        let ids = camera.render_object_ids(&world);
+        assert_eq!(ids.len(), 21 * 11);
+        assert!(ids.iter().all(|&id| id.is_none() || id == Some(left_id) || id == Some(right_id)));
+
+        assert_eq!(ids[5 + 5*21], Some(left_id));
+        assert_eq!(ids[15 + 5*21], Some(right_id));
+        assert_eq!(ids[10 + 5*21], None);
+    }
 }
\ No newline at end of file
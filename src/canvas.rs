@@ -1,5 +1,8 @@
-use crate::color;
+use serde::{Deserialize, Serialize};
 
+use crate::{color, float};
+
+#[derive(Serialize, Deserialize)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
@@ -16,16 +19,153 @@ impl Canvas {
     }
 
     pub fn get_pixel(&self, x: usize, y: usize) -> color::Color {
-        self.pixels[x + y*self.height]
+        self.pixels[x + y*self.width]
     }
 
     pub fn set_pixel(&mut self, x: usize, y: usize, c: color::Color) {
-        self.pixels[x + y*self.height] = c;
+        self.pixels[x + y*self.width] = c;
+    }
+
+    pub fn to_grayscale(&self) -> Canvas {
+        Canvas {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|c| c.to_grayscale()).collect(),
+        }
+    }
+
+    pub fn max_channel_value(&self) -> f64 {
+        self.pixels
+            .iter()
+            .map(|c| c.r.max(c.g).max(c.b))
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn mean_luminance(&self) -> f64 {
+        if self.pixels.is_empty() {
+            return 0.;
+        }
+        let total: f64 = self.pixels.iter().map(|c| c.luminance()).sum();
+        total / self.pixels.len() as f64
+    }
+
+    // Counts pixels by luminance in equal width buckets between 0 and 1;
+    // anything brighter than 1 lands in the last bucket.
+    pub fn pixel_histogram(&self, buckets: usize) -> Vec<u32> {
+        let mut histogram = vec![0; buckets];
+        if buckets == 0 {
+            return histogram;
+        }
+        for &c in self.pixels.iter() {
+            let bucket = (float::saturate(c.luminance()) * buckets as f64) as usize;
+            histogram[bucket.min(buckets - 1)] += 1;
+        }
+        histogram
+    }
+
+    // Lays `src` over this canvas using Porter-Duff "over", weighting each
+    // of its pixels by the matching entry in `alpha`, in row major order.
+    pub fn composite(&mut self, src: &Canvas, alpha: &[f64]) {
+        assert_eq!((src.width, src.height), (self.width, self.height));
+        assert_eq!(alpha.len(), src.width * src.height);
+        for (i, dst) in self.pixels.iter_mut().enumerate() {
+            *dst = over(src.pixels[i], *dst, alpha[i]);
+        }
+    }
+
+    // Same as composite, but with every source pixel the same color.
+    pub fn composite_solid(&mut self, color: color::Color, alpha: &[f64]) {
+        assert_eq!(alpha.len(), self.width * self.height);
+        for (dst, &a) in self.pixels.iter_mut().zip(alpha.iter()) {
+            *dst = over(color, *dst, a);
+        }
+    }
+
+    // Fakes depth of field by averaging each pixel with its neighbors inside
+    // a disc whose radius grows by one pixel for every unit of distance from
+    // the focal plane, up to `max_blur_radius`. The depths are expected in
+    // row major order, as returned by Camera::render_depth.
+    pub fn apply_dof_blur(&self, depth: &[f64], focal_depth: f64, max_blur_radius: usize) -> Canvas {
+        assert_eq!(depth.len(), self.width * self.height);
+        let mut blurred = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let offset = (depth[x + y*self.width] - focal_depth).abs();
+                let radius = offset.round().min(max_blur_radius as f64) as usize;
+                blurred.set_pixel(x, y, self.disc_average(x, y, radius));
+            }
+        }
+        blurred
+    }
+
+    fn disc_average(&self, x: usize, y: usize, radius: usize) -> color::Color {
+        let mut total = color::BLACK;
+        let mut count = 0;
+        let (radius_i, x_i, y_i) = (radius as i64, x as i64, y as i64);
+        for dy in -radius_i..=radius_i {
+            for dx in -radius_i..=radius_i {
+                let (sx, sy) = (x_i + dx, y_i + dy);
+                if dx*dx + dy*dy > radius_i*radius_i ||
+                    sx < 0 || sy < 0 || sx >= self.width as i64 || sy >= self.height as i64 {
+                    continue;
+                }
+                total = total.add(self.get_pixel(sx as usize, sy as usize));
+                count += 1;
+            }
+        }
+        total.multiply(1. / count as f64)
+    }
+}
+
+// Adds up any number of samples for each pixel, so that a render can be
+// refined a pass at a time and looked at along the way.
+pub struct Accumulator {
+    width: usize,
+    height: usize,
+    samples: Vec<[f64; 3]>,
+    counts: Vec<u32>,
+}
+
+impl Accumulator {
+    pub fn new(w: usize, h: usize) -> Accumulator {
+        Accumulator {
+            width: w,
+            height: h,
+            samples: vec![[0.; 3]; w*h],
+            counts: vec![0; w*h],
+        }
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, c: color::Color) {
+        let index = x + y*self.width;
+        let sample = &mut self.samples[index];
+        sample[0] += c.r;
+        sample[1] += c.g;
+        sample[2] += c.b;
+        self.counts[index] += 1;
+    }
+
+    // The average of the samples for each pixel so far. Pixels without any
+    // samples yet are left black.
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (pixel, (sample, &count)) in canvas.pixels.iter_mut().zip(self.samples.iter().zip(self.counts.iter())) {
+            if count > 0 {
+                *pixel = color::Color::from_array(*sample).multiply(1. / count as f64);
+            }
+        }
+        canvas
     }
 }
 
+fn over(src: color::Color, dst: color::Color, alpha: f64) -> color::Color {
+    src.multiply(alpha).add(dst.multiply(1. - alpha))
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::float;
+    use crate::color::Color;
     use super::*;
 
     #[test]
@@ -43,4 +183,147 @@ mod tests {
         canvas.set_pixel(2, 3, red);
         assert_eq!(canvas.get_pixel(2, 3), red);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_set_pixel_non_square() {
+        let red = color::Color::new(1., 0., 0.);
+
+        let mut wide = Canvas::new(20, 10);
+        wide.set_pixel(0, 1, red);
+        assert_eq!(wide.get_pixel(0, 1), red);
+        assert_eq!(wide.get_pixel(10, 0), color::BLACK);
+
+        let mut tall = Canvas::new(10, 20);
+        tall.set_pixel(9, 19, red);
+        assert_eq!(tall.get_pixel(9, 19), red);
+    }
+
+    #[test]
+    fn test_max_channel_value() {
+        let mut canvas = Canvas::new(4, 4);
+        assert_eq!(canvas.max_channel_value(), 0.);
+        canvas.set_pixel(1, 2, color::Color::new(0.2, 1.7, 0.4));
+        canvas.set_pixel(3, 0, color::Color::new(0.9, 0.1, 1.2));
+        assert_eq!(canvas.max_channel_value(), 1.7);
+    }
+
+    #[test]
+    fn test_to_grayscale() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(0, 0, color::Color::new(0., 1., 0.));
+        canvas.set_pixel(1, 0, color::WHITE);
+        let gray = canvas.to_grayscale();
+        assert_eq!(gray.width, 2);
+        assert_eq!(gray.height, 1);
+        assert_eq!(gray.get_pixel(0, 0), color::Color::new(0.7152, 0.7152, 0.7152));
+        assert_eq!(gray.get_pixel(1, 0), color::WHITE);
+    }
+
+    #[test]
+    fn test_mean_luminance() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, color::WHITE);
+        canvas.set_pixel(1, 0, color::Color::new(1., 0., 0.));
+        let expected_value = (1. + 0.2126) / 4.;
+        assert!(float::is_equal(canvas.mean_luminance(), expected_value));
+    }
+
+    #[test]
+    fn test_pixel_histogram() {
+        let mut canvas = Canvas::new(4, 1);
+        canvas.set_pixel(1, 0, color::GREY);
+        canvas.set_pixel(2, 0, color::WHITE);
+        canvas.set_pixel(3, 0, color::Color::new(3., 3., 3.));
+        assert_eq!(canvas.pixel_histogram(4), vec![1, 0, 1, 2]);
+        assert_eq!(canvas.pixel_histogram(1), vec![4]);
+        assert!(canvas.pixel_histogram(0).is_empty());
+    }
+
+    fn single_white_pixel() -> Canvas {
+        let mut canvas = Canvas::new(11, 11);
+        canvas.set_pixel(5, 5, color::WHITE);
+        canvas
+    }
+
+    #[test]
+    fn test_apply_dof_blur_in_focus() {
+        let canvas = single_white_pixel();
+        let depth = vec![4.; 121];
+        let blurred = canvas.apply_dof_blur(&depth, 4., 3);
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_eq!(blurred.get_pixel(x, y), canvas.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_dof_blur_out_of_focus() {
+        let canvas = single_white_pixel();
+        let depth = vec![6.; 121];
+        let blurred = canvas.apply_dof_blur(&depth, 4., 3);
+
+        // A disc of radius two covers thirteen pixels
+        let spread = color::WHITE.multiply(1. / 13.);
+        assert_eq!(blurred.get_pixel(5, 5), spread);
+        assert_eq!(blurred.get_pixel(7, 5), spread);
+        assert_eq!(blurred.get_pixel(6, 6), spread);
+        assert_eq!(blurred.get_pixel(7, 6), color::BLACK);
+        assert_eq!(blurred.get_pixel(8, 5), color::BLACK);
+    }
+
+    #[test]
+    fn test_apply_dof_blur_limits_radius() {
+        let canvas = single_white_pixel();
+        let mut depth = vec![f64::INFINITY; 121];
+        depth[0] = 4.;
+        let blurred = canvas.apply_dof_blur(&depth, 4., 1);
+
+        // A disc of radius one covers five pixels
+        let spread = color::WHITE.multiply(1. / 5.);
+        assert_eq!(blurred.get_pixel(5, 5), spread);
+        assert_eq!(blurred.get_pixel(5, 6), spread);
+        assert_eq!(blurred.get_pixel(6, 6), color::BLACK);
+        assert_eq!(blurred.get_pixel(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn test_composite() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.composite_solid(color::WHITE, &[1.; 4]);
+
+        let mut src = Canvas::new(2, 2);
+        src.set_pixel(1, 0, Color::new(1., 0., 0.));
+        src.set_pixel(0, 1, Color::new(0., 0., 1.));
+        canvas.composite(&src, &[0., 1., 0.25, 0.5]);
+
+        assert_eq!(canvas.get_pixel(0, 0), color::WHITE);
+        assert_eq!(canvas.get_pixel(1, 0), Color::new(1., 0., 0.));
+        assert_eq!(canvas.get_pixel(0, 1), Color::new(0.75, 0.75, 1.));
+        assert_eq!(canvas.get_pixel(1, 1), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_composite_solid() {
+        let mut canvas = Canvas::new(3, 1);
+        canvas.set_pixel(2, 0, Color::new(0., 1., 0.));
+        canvas.composite_solid(Color::new(1., 0., 0.), &[0., 0.5, 0.5]);
+        assert_eq!(canvas.get_pixel(0, 0), color::BLACK);
+        assert_eq!(canvas.get_pixel(1, 0), Color::new(0.5, 0., 0.));
+        assert_eq!(canvas.get_pixel(2, 0), Color::new(0.5, 0.5, 0.));
+    }
+
+    #[test]
+    fn test_accumulator_averages_samples() {
+        let mut accumulator = Accumulator::new(3, 2);
+        accumulator.add_sample(1, 1, Color::new(1., 0., 0.5));
+        accumulator.add_sample(1, 1, Color::new(0., 0.5, 0.5));
+        accumulator.add_sample(2, 0, Color::new(0.2, 0.4, 0.6));
+        let canvas = accumulator.to_canvas();
+        assert_eq!(canvas.width, 3);
+        assert_eq!(canvas.height, 2);
+        assert_eq!(canvas.get_pixel(1, 1), Color::new(0.5, 0.25, 0.5));
+        assert_eq!(canvas.get_pixel(2, 0), Color::new(0.2, 0.4, 0.6));
+        assert_eq!(canvas.get_pixel(0, 0), color::BLACK);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::float;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
+use crate::{float, ppm};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Color {
     pub r: f64,
     pub g: f64,
@@ -9,6 +11,10 @@ pub struct Color {
 
 pub const BLACK: Color = Color{r: 0.0, g: 0.0, b: 0.0};
 pub const WHITE: Color = Color{r: 1.0, g: 1.0, b: 1.0};
+pub const GREY: Color = Color{r: 0.5, g: 0.5, b: 0.5};
+// Colors are used as light intensities and filters too, where black lets
+// nothing through.
+pub const TRANSPARENT: Color = Color{r: 0.0, g: 0.0, b: 0.0};
 
 impl Color {
     pub fn new(r: f64, g: f64, b: f64) -> Color {
@@ -30,6 +36,69 @@ impl Color {
     pub fn hadamard(&self, other: Color) -> Color {
         Color::new(self.r * other.r, self.g * other.g, self.b * other.b)
     }
+
+    pub fn to_array(self) -> [f64; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    pub fn from_array(arr: [f64; 3]) -> Color {
+        Color::new(arr[0], arr[1], arr[2])
+    }
+
+    pub fn to_array4(self, alpha: f64) -> [f64; 4] {
+        [self.r, self.g, self.b, alpha]
+    }
+
+    // Scales and clamps each channel exactly as when saving an image.
+    pub fn to_u8_array(self) -> [u8; 3] {
+        [ppm::scale_and_clamp(self.r), ppm::scale_and_clamp(self.g), ppm::scale_and_clamp(self.b)]
+    }
+
+    // Approximates the color of a black body at the given temperature,
+    // using Tanner Helland's curve fit, which holds between 1000K and 40000K.
+    // Around 6600K comes out white; cooler is redder and hotter is bluer.
+    pub fn from_kelvin(kelvin: f64) -> Color {
+        let temperature = kelvin.clamp(1000., 40000.) / 100.;
+
+        let r = if temperature <= 66. {
+            255.
+        } else {
+            329.698727446 * (temperature - 60.).powf(-0.1332047592)
+        };
+        let g = if temperature <= 66. {
+            99.4708025861 * temperature.ln() - 161.1195681661
+        } else {
+            288.1221695283 * (temperature - 60.).powf(-0.0755148492)
+        };
+        let b = if temperature >= 66. {
+            255.
+        } else if temperature <= 19. {
+            0.
+        } else {
+            138.5177312231 * (temperature - 10.).ln() - 305.0447927307
+        };
+
+        Color::new(
+            float::clamp(r, 0., 255.) / 255.,
+            float::clamp(g, 0., 255.) / 255.,
+            float::clamp(b, 0., 255.) / 255.,
+        )
+    }
+
+    // Brings every channel back into the displayable range between 0 and 1.
+    pub fn clamp(self) -> Color {
+        Color::new(float::saturate(self.r), float::saturate(self.g), float::saturate(self.b))
+    }
+
+    // Relative luminance, weighting each channel by how bright it looks.
+    pub fn luminance(&self) -> f64 {
+        0.2126*self.r + 0.7152*self.g + 0.0722*self.b
+    }
+
+    pub fn to_grayscale(self) -> Color {
+        let l = self.luminance();
+        Color::new(l, l, l)
+    }
 }
 
 impl PartialEq for Color {
@@ -74,4 +143,76 @@ mod tests {
         let c2 = Color::new(0.9, 1., 0.1);
         assert_eq!(c1.hadamard(c2), Color::new(0.9, 0.2, 0.04));
     }
+
+    #[test]
+    fn test_from_kelvin() {
+        let test_cases = vec![
+            (6600., Color::new(1., 1., 1.)),
+            (1850., Color::new(1., 0.506327, 0.)),
+            (10000., Color::new(0.790997, 0.855179, 1.)),
+            // Temperatures outside the fitted range are clamped to it.
+            (500., Color::new(1., 0.266355, 0.)),
+        ];
+
+        for (kelvin, expected_color) in test_cases {
+            assert_eq!(Color::from_kelvin(kelvin), expected_color, "{}K", kelvin);
+        }
+
+        // Cooler light is always redder than hotter light.
+        let candle = Color::from_kelvin(1850.);
+        let daylight = Color::from_kelvin(5500.);
+        assert!(candle.b < daylight.b && candle.g < daylight.g);
+    }
+
+    #[test]
+    fn test_array_round_trips() {
+        let test_cases = vec![
+            (BLACK, [0., 0., 0.]),
+            (WHITE, [1., 1., 1.]),
+            (Color::new(0.9, 0.6, 0.75), [0.9, 0.6, 0.75]),
+            (Color::new(-0.5, 2., 0.25), [-0.5, 2., 0.25]),
+        ];
+
+        for (color, array) in test_cases {
+            assert_eq!(color.to_array(), array);
+            assert_eq!(Color::from_array(array), color);
+            assert_eq!(Color::from_array(color.to_array()), color);
+            assert_eq!(color.to_array4(0.5), [array[0], array[1], array[2], 0.5]);
+        }
+    }
+
+    #[test]
+    fn test_to_u8_array() {
+        assert_eq!(BLACK.to_u8_array(), [0, 0, 0]);
+        assert_eq!(WHITE.to_u8_array(), [255, 255, 255]);
+        assert_eq!(Color::new(-0.5, 0.5, 1.5).to_u8_array(), [0, 128, 255]);
+    }
+
+    #[test]
+    fn test_clamp() {
+        let color = Color::new(-0.5, 0.5, 1.5).clamp();
+        assert_eq!(color, Color::new(0., 0.5, 1.));
+    }
+
+    #[test]
+    fn test_luminance() {
+        let test_cases = vec![
+            (WHITE, 1.0),
+            (BLACK, 0.0),
+            (Color::new(1., 0., 0.), 0.2126),
+            (Color::new(0., 1., 0.), 0.7152),
+            (Color::new(0., 0., 1.), 0.0722),
+        ];
+        for (color, expected_value) in test_cases {
+            assert!(float::is_equal(color.luminance(), expected_value));
+        }
+    }
+
+    #[test]
+    fn test_to_grayscale() {
+        let color = Color::new(0.5, 0.25, 1.);
+        let l = 0.2126*0.5 + 0.7152*0.25 + 0.0722;
+        assert_eq!(color.to_grayscale(), Color::new(l, l, l));
+        assert_eq!(WHITE.to_grayscale(), WHITE);
+    }
 }
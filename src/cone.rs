@@ -1,16 +1,31 @@
-use crate::{float, material, matrix, ray, tuple};
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{float, material, matrix, object, ray, tuple};
 use crate::float::EPSILON;
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
 use crate::material::Material;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::shape::Shape;
 use crate::tuple::{Tuple, TupleMethods};
 
-#[derive(Clone)]
+// Two hits on the walls, plus one on each cap.
+pub const MAX_INTERSECTIONS: usize = 4;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Cone {
     pub transform: matrix::Matrix4,
     pub inverse_transform: matrix::Matrix4,
     pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    #[serde(with = "crate::float::extended")]
     pub minimum: f64,
+    #[serde(with = "crate::float::extended")]
     pub maximum: f64,
     pub is_closed: bool,
 }
@@ -21,6 +36,8 @@ impl Cone {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
             minimum: -f64::INFINITY,
             maximum: f64::INFINITY,
             is_closed: false,
@@ -32,6 +49,8 @@ impl Cone {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
             minimum: minimum,
             maximum: maximum,
             is_closed: true,
@@ -47,33 +66,29 @@ impl Cone {
         (x*x + z*z) <= y.abs()
     }
 
-    fn intersect_caps(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect_caps(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         // Caps only matter if the cylinder is closed, and might possibly be
         // intersected by the ray.
         if !self.is_closed || local_ray.direction[1].abs() < float::EPSILON {
-            vec![]
-        } else {
-            let mut ts = vec![];
-
-            // Check for an intersection with the lower end cap by intersecting
-            // the ray with the plane at cylinder minimum.
-            let t1 = (self.minimum - local_ray.origin[1]) / local_ray.direction[1];
-            if self.check_cap(local_ray, t1, self.minimum) {
-                ts.push(t1);
-            }
+            return;
+        }
 
-            // Now check for an intersection with the upper end cap by intersecting
-            // the ray with the plane at cylinder maximum.
-            let t2 = (self.maximum - local_ray.origin[1]) / local_ray.direction[1];
-            if self.check_cap(local_ray, t2, self.maximum) {
-                ts.push(t2);
-            }
+        // Check for an intersection with the lower end cap by intersecting
+        // the ray with the plane at cylinder minimum.
+        let t1 = (self.minimum - local_ray.origin[1]) / local_ray.direction[1];
+        if self.check_cap(local_ray, t1, self.minimum) {
+            intersections.push(Intersection::new(t1, object_id));
+        }
 
-            ts
+        // Now check for an intersection with the upper end cap by intersecting
+        // the ray with the plane at cylinder maximum.
+        let t2 = (self.maximum - local_ray.origin[1]) / local_ray.direction[1];
+        if self.check_cap(local_ray, t2, self.maximum) {
+            intersections.push(Intersection::new(t2, object_id));
         }
     }
 
-    fn intersect_walls(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect_walls(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         let a = local_ray.direction[0]*local_ray.direction[0] -
             local_ray.direction[1]*local_ray.direction[1] +
             local_ray.direction[2]*local_ray.direction[2];
@@ -86,54 +101,44 @@ impl Cone {
 
         if a.abs() < float::EPSILON && b.abs() < float::EPSILON {
             // Ray is parallel to cones but intersects neither
-            vec![]
         } else if a.abs() < float::EPSILON && b.abs() > float::EPSILON {
             // Ray is parallel to cones but intersects one of them
-            vec![-c/2./b]
+            intersections.push(Intersection::new(-c/2./b, object_id));
         } else {
             let discriminant = b*b - 4. * a * c;
 
             if discriminant < 0. {
                 // Ray does not intersect the cylinder
-                vec![]
             } else if discriminant == 0.0 {
                 // Ray is potentially tangent to cylinder
                 let t = -b / (2. * a);
                 let y = local_ray.origin[1] + local_ray.direction[1]*t;
                 if y > self.minimum && y < self.maximum {
-                    vec![t]
-                } else {
-                    vec![]
+                    intersections.push(Intersection::new(t, object_id));
                 }
             } else {
                 // Ray _does_ potentially intersect the cylinder twice
                 let t1 = (-b - discriminant.sqrt()) / (2. * a);
                 let t2 = (-b + discriminant.sqrt()) / (2. * a);
 
-                let mut ts = vec![];
                 let y1 = local_ray.origin[1] + local_ray.direction[1]*t1;
                 if y1 > self.minimum && y1 < self.maximum {
-                    ts.push(t1);
+                    intersections.push(Intersection::new(t1, object_id));
                 }
 
                 let y2 = local_ray.origin[1] + local_ray.direction[1]*t2;
                 if y2 > self.minimum && y2 < self.maximum {
-                    ts.push(t2);
+                    intersections.push(Intersection::new(t2, object_id));
                 }
-
-                ts
             }
         }
     }
 }
 
 impl Shape for Cone {
-    fn intersect(&self, local_ray: &ray::Ray) -> Vec<f64> {
-        let mut wall_ts = self.intersect_walls(local_ray);
-        let mut caps_ts = self.intersect_caps(local_ray);
-
-        wall_ts.append(&mut caps_ts);
-        wall_ts
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        self.intersect_walls(local_ray, object_id, intersections);
+        self.intersect_caps(local_ray, object_id, intersections);
     }
 
     fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
@@ -144,20 +149,65 @@ impl Shape for Cone {
             Tuple::vector(0., 1., 0.)
         } else if distance < 1. && local_point[1] <= self.minimum + EPSILON {
             Tuple::vector(0., -1., 0.)
-        } else if local_point[0] > 0. {
+        } else if local_point[1] > 0. {
+            // On the upper nappe the surface flares out above the point, so
+            // the normal tips down; on the lower one it tips up.
             Tuple::vector(local_point[0], -distance.sqrt(), local_point[2])
         } else {
             Tuple::vector(local_point[0], distance.sqrt(), local_point[2])
         }
     }
+
+    fn is_bounded(&self) -> bool {
+        self.minimum.is_finite() && self.maximum.is_finite()
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let radius = self.minimum.abs().max(self.maximum.abs());
+        BoundingBox::new(
+            Tuple::point(-radius, self.minimum, -radius),
+            Tuple::point(radius, self.maximum, radius),
+        )
+    }
+
+    // Picks the walls or one of the caps in proportion to their areas. The
+    // walls widen with |y|, so the area below a height grows as y|y|, and
+    // heights are drawn to match by undoing that with a signed square root.
+    // Cones that go on forever can only be sampled around one ring.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        let phi = 2. * PI * rng.random::<f64>();
+        let on_wall = |y: f64| Tuple::point(y.abs() * phi.cos(), y, y.abs() * phi.sin());
+        if !self.is_bounded() {
+            return on_wall(0_f64.clamp(self.minimum, self.maximum));
+        }
+
+        let swept = |y: f64| y * y.abs();
+        let wall_area = 2_f64.sqrt() * PI * (swept(self.maximum) - swept(self.minimum));
+        let (bottom_area, top_area) = if self.is_closed {
+            (PI * self.minimum * self.minimum, PI * self.maximum * self.maximum)
+        } else {
+            (0., 0.)
+        };
+        let pick = rng.random::<f64>() * (wall_area + bottom_area + top_area);
+        if pick < wall_area {
+            let target = swept(self.minimum) + (swept(self.maximum) - swept(self.minimum)) * rng.random::<f64>();
+            on_wall(target.signum() * target.abs().sqrt())
+        } else {
+            let y = if pick < wall_area + bottom_area { self.minimum } else { self.maximum };
+            let r = y.abs() * rng.random::<f64>().sqrt();
+            Tuple::point(r * phi.cos(), y, r * phi.sin())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cone::Cone;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
     use crate::{float, material, matrix};
     use crate::ray::Ray;
-    use crate::shape::Shape;
+    use crate::shape::{intersect_ts, Shape};
     use crate::tuple::{Tuple, TupleMethods};
 
     #[test]
@@ -174,7 +224,7 @@ mod tests {
         ];
         for (origin, direction, expected_ts) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cone.intersect(&ray);
+            let ts = intersect_ts(&cone, &ray);
             assert!(ts.iter().zip(expected_ts).all(|(&a, b)| float::is_equal(a, b)));
         }
     }
@@ -190,7 +240,7 @@ mod tests {
             Tuple::point(0., 0., -1.),
             Tuple::vector(0., 1., 1.).normalize(),
         );
-        let ts = cone.intersect(&ray);
+        let ts = intersect_ts(&cone, &ray);
         assert_eq!(ts.len(), 1);
         assert!(float::is_equal(ts[0], 0.35355));
     }
@@ -210,7 +260,7 @@ mod tests {
         ];
         for (origin, direction, expected_count) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cone.intersect(&ray);
+            let ts = intersect_ts(&cone, &ray);
             assert_eq!(ts.len(), expected_count);
         }
     }
@@ -233,4 +283,51 @@ mod tests {
             assert!(normal.is_equal(expected_value));
         }
     }
+
+    #[test]
+    fn test_normal_at_both_nappes() {
+        let cone = Cone::new_infinite(
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        );
+
+        let test_cases = vec![
+            (Tuple::point(-1., 1., 0.), Tuple::vector(-1., -1., 0.)),
+            (Tuple::point(0., 2., -2.), Tuple::vector(0., -2., -2.)),
+            (Tuple::point(1., -1., 0.), Tuple::vector(1., 1., 0.)),
+            (Tuple::point(0., -3., 3.), Tuple::vector(0., 3., 3.)),
+        ];
+
+        for (point, expected_value) in test_cases {
+            let normal = cone.normal_at(point);
+            assert!(normal.is_equal(expected_value));
+        }
+    }
+
+    #[test]
+    fn test_random_point() {
+        let cone = Cone::new_capped(matrix::IDENTITY, material::DEFAULT_MATERIAL, -1., 2.);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            let point = cone.random_point(&mut rng);
+            let distance = (point[0]*point[0] + point[2]*point[2]).sqrt();
+            if point[1] == -1. {
+                assert!(distance <= 1.);
+                counts[0] += 1;
+            } else if point[1] == 2. {
+                assert!(distance <= 2.);
+                counts[2] += 1;
+            } else {
+                assert!(float::is_equal(distance, point[1].abs()));
+                counts[1] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&count| count > 50));
+        // The top cap is four times the size of the bottom one.
+        assert!(counts[2] > 2 * counts[0]);
+
+        let infinite = Cone::new_infinite(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        assert!(infinite.random_point(&mut rng).is_equal(Tuple::point(0., 0., 0.)));
+    }
 }
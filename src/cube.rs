@@ -1,15 +1,26 @@
-use crate::{float, material, matrix, ray, tuple};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{float, material, matrix, object, ray, tuple};
 use crate::float::EPSILON;
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
 use crate::material::Material;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::shape::Shape;
 use crate::tuple::{Tuple, TupleMethods};
 
-#[derive(Clone)]
+// A ray enters and leaves a cube at most once each.
+pub const MAX_INTERSECTIONS: usize = 2;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Cube {
     pub transform: matrix::Matrix4,
     pub inverse_transform: matrix::Matrix4,
     pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
 }
 
 impl Cube {
@@ -18,8 +29,27 @@ impl Cube {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
         }
     }
+
+    // The tangent and bitangent at a point on the surface, for building the
+    // basis that bump and normal maps are applied in. Each face has its own
+    // fixed pair of directions lying in it.
+    pub fn tangent_at(&self, local_point: Tuple) -> (Tuple, Tuple) {
+        let normal = self.normal_at(local_point).normalize();
+        let tangent = if normal[0] > 0. {
+            Tuple::vector(0., 0., -1.)
+        } else if normal[0] < 0. {
+            Tuple::vector(0., 0., 1.)
+        } else if normal[2] < 0. {
+            Tuple::vector(-1., 0., 0.)
+        } else {
+            Tuple::vector(1., 0., 0.)
+        };
+        (tangent, tangent.cross(normal))
+    }
 }
 
 fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
@@ -45,17 +75,16 @@ fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
 }
 
 impl Shape for Cube {
-    fn intersect(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         let (xtmin, xtmax) = check_axis(local_ray.origin[0], local_ray.direction[0]);
         let (ytmin, ytmax) = check_axis(local_ray.origin[1], local_ray.direction[1]);
         let (ztmin, ztmax) = check_axis(local_ray.origin[2], local_ray.direction[2]);
         let tmin = xtmin.max(ytmin).max(ztmin);
         let tmax = xtmax.min(ytmax).min(ztmax);
 
-        if tmin > tmax {
-            vec![]
-        } else {
-            vec![tmin, tmax]
+        if tmin <= tmax {
+            intersections.push(Intersection::new(tmin, object_id));
+            intersections.push(Intersection::new(tmax, object_id));
         }
     }
 
@@ -64,12 +93,36 @@ impl Shape for Cube {
             .max(local_point[1].abs())
             .max(local_point[2].abs());
 
+        // Only the sign of the largest component matters, so that points
+        // a little off the surface still get a unit normal. On edges and
+        // corners x wins over y, which wins over z.
         if float::is_equal(maxc, local_point[0].abs()) {
-            Tuple::vector(local_point[0], 0., 0.)
+            Tuple::vector(local_point[0].signum(), 0., 0.)
         } else if float::is_equal(maxc, local_point[1].abs()) {
-            Tuple::vector(0., local_point[1], 0.)
+            Tuple::vector(0., local_point[1].signum(), 0.)
         } else {
-            Tuple::vector(0., 0., local_point[2])
+            Tuple::vector(0., 0., local_point[2].signum())
+        }
+    }
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+    }
+
+    // All six faces are the same size, so each is equally likely.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        let face = rng.random_range(0..6);
+        let u = 2. * rng.random::<f64>() - 1.;
+        let v = 2. * rng.random::<f64>() - 1.;
+        let side = if face % 2 == 0 { 1. } else { -1. };
+        match face / 2 {
+            0 => Tuple::point(side, u, v),
+            1 => Tuple::point(u, side, v),
+            _ => Tuple::point(u, v, side),
         }
     }
 }
@@ -77,9 +130,12 @@ impl Shape for Cube {
 #[cfg(test)]
 mod tests {
     use crate::cube::{Cube, material};
-    use crate::matrix;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::{float, matrix};
     use crate::ray::Ray;
-    use crate::shape::Shape;
+    use crate::shape::{intersect_ts, Shape};
     use crate::tuple::{Tuple, TupleMethods};
 
     #[test]
@@ -99,7 +155,7 @@ mod tests {
         ];
         for (origin, direction) in test_cases {
             let ray = Ray::new(origin, direction);
-            let ts = cube.intersect(&ray);
+            let ts = intersect_ts(&cube, &ray);
             assert_eq!(ts.len(), 2);
             assert_eq!(ts[0], 4.);
             assert_eq!(ts[1], 6.);
@@ -117,7 +173,7 @@ mod tests {
             Tuple::point(0., 0.5, 0.),
             Tuple::vector(0., 0., 1.),
         );
-        let ts = cube.intersect(&ray);
+        let ts = intersect_ts(&cube, &ray);
         assert_eq!(ts.len(), 2);
         assert_eq!(ts[0], -1.);
         assert_eq!(ts[1], 1.)
@@ -145,4 +201,49 @@ mod tests {
             assert!(normal.is_equal(expected_value));
         }
     }
+
+    #[test]
+    fn test_normal_at_faces_edges_and_corners() {
+        let cube = Cube::new(
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        );
+        let test_cases = vec![
+            // Faces, from points just outside the cube
+            (Tuple::point(1.5, 0.2, 0.1), Tuple::vector(1., 0., 0.)),
+            (Tuple::point(-1.5, 0.2, 0.1), Tuple::vector(-1., 0., 0.)),
+            (Tuple::point(0.1, 1.5, 0.2), Tuple::vector(0., 1., 0.)),
+            (Tuple::point(0.1, -1.5, 0.2), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0.1, 0.2, 1.5), Tuple::vector(0., 0., 1.)),
+            (Tuple::point(0.1, 0.2, -1.5), Tuple::vector(0., 0., -1.)),
+            // Edges
+            (Tuple::point(1., 1., 0.3), Tuple::vector(1., 0., 0.)),
+            (Tuple::point(-0.3, -1., 1.), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(-1., 0.3, -1.), Tuple::vector(-1., 0., 0.)),
+            // Corners
+            (Tuple::point(1., 1., 1.), Tuple::vector(1., 0., 0.)),
+            (Tuple::point(-1., 1., -1.), Tuple::vector(-1., 0., 0.)),
+            (Tuple::point(1.5, -1.5, 1.5), Tuple::vector(1., 0., 0.)),
+        ];
+
+        for (point, expected_value) in test_cases {
+            let normal = cube.normal_at(point);
+            assert!(normal.is_equal(expected_value));
+        }
+    }
+
+    #[test]
+    fn test_random_point() {
+        let cube = Cube::new(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut faces = [0; 6];
+        for _ in 0..600 {
+            let point = cube.random_point(&mut rng);
+            assert!((0..3).all(|axis| point[axis].abs() <= 1.));
+            let normal = cube.normal_at(point);
+            let axis = (0..3).find(|&axis| float::is_equal(normal[axis].abs(), 1.)).unwrap();
+            faces[2*axis + (normal[axis] < 0.) as usize] += 1;
+        }
+        assert!(faces.iter().all(|&count| count > 50));
+    }
 }
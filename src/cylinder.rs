@@ -1,19 +1,40 @@
 use std::cmp::min;
-use crate::{float, material, matrix, ray, tuple};
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{float, material, matrix, object, ray, tuple};
 use crate::float::EPSILON;
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
 use crate::material::Material;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::shape::Shape;
 use crate::tuple::{Tuple, TupleMethods};
 
-#[derive(Clone)]
+// Two hits on the walls, plus one on each cap.
+pub const MAX_INTERSECTIONS: usize = 4;
+
+fn default_radius() -> f64 {
+    1.
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Cylinder {
     pub transform: matrix::Matrix4,
     pub inverse_transform: matrix::Matrix4,
     pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    #[serde(with = "crate::float::extended")]
     pub minimum: f64,
+    #[serde(with = "crate::float::extended")]
     pub maximum: f64,
     pub is_closed: bool,
+    #[serde(default = "default_radius")]
+    pub radius: f64,
 }
 
 impl Cylinder {
@@ -22,9 +43,12 @@ impl Cylinder {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
             minimum: -f64::INFINITY,
             maximum: f64::INFINITY,
             is_closed: false,
+            radius: 1.,
         }
     }
 
@@ -33,9 +57,12 @@ impl Cylinder {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
             minimum: minimum,
             maximum: maximum,
             is_closed: false,
+            radius: 1.,
         }
     }
 
@@ -44,124 +71,179 @@ impl Cylinder {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
             minimum: minimum,
             maximum: maximum,
             is_closed: true,
+            radius: 1.,
+        }
+    }
+
+    pub fn new_with_radius(radius: f64, transform: Matrix4, material: Material, minimum: f64, maximum: f64, is_closed: bool) -> Cylinder {
+        Cylinder {
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+            material,
+            id: object::next_id(),
+            visible: true,
+            minimum,
+            maximum,
+            is_closed,
+            radius,
+        }
+    }
+
+    // The tangent and bitangent at a point on the surface, for building the
+    // basis that bump and normal maps are applied in. On the walls the
+    // tangent runs around the cylinder and the bitangent up along it.
+    pub fn tangent_at(&self, local_point: Tuple) -> (Tuple, Tuple) {
+        let normal = self.normal_at(local_point);
+        if normal[0] == 0. && normal[2] == 0. {
+            let tangent = Tuple::vector(1., 0., 0.);
+            (tangent, tangent.cross(normal))
+        } else {
+            let phi = local_point[2].atan2(local_point[0]);
+            (Tuple::vector(-phi.sin(), 0., phi.cos()), Tuple::vector(0., 1., 0.))
         }
     }
 
     // This is a helper function to reduce code duplication,
-    // checks to see if the intersection at `t` is within a radius
-    // of 1 (the radius of your cylinders) from the y axis.
+    // checks to see if the intersection at `t` is within the
+    // radius of the cylinder from the y axis.
     fn check_cap(&self, local_ray: &ray::Ray, t: f64) -> bool {
         let x = local_ray.origin[0] + t * local_ray.direction[0];
         let z = local_ray.origin[2] + t * local_ray.direction[2];
-        (x*x + z*z) <= 1.
+        (x*x + z*z) <= self.radius*self.radius
     }
 
-    fn intersect_caps(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect_caps(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         // Caps only matter if the cylinder is closed, and might possibly be
         // intersected by the ray.
         if !self.is_closed || local_ray.direction[1].abs() < float::EPSILON {
-            vec![]
-        } else {
-            let mut ts = vec![];
-
-            // Check for an intersection with the lower end cap by intersecting
-            // the ray with the plane at cylinder minimum.
-            let t1 = (self.minimum - local_ray.origin[1]) / local_ray.direction[1];
-            if self.check_cap(local_ray, t1) {
-                ts.push(t1);
-            }
+            return;
+        }
 
-            // Now check for an intersection with the upper end cap by intersecting
-            // the ray with the plane at cylinder maximum.
-            let t2 = (self.maximum - local_ray.origin[1]) / local_ray.direction[1];
-            if self.check_cap(local_ray, t2) {
-                ts.push(t2);
-            }
+        // Check for an intersection with the lower end cap by intersecting
+        // the ray with the plane at cylinder minimum.
+        let t1 = (self.minimum - local_ray.origin[1]) / local_ray.direction[1];
+        if self.check_cap(local_ray, t1) {
+            intersections.push(Intersection::new(t1, object_id));
+        }
 
-            ts
+        // Now check for an intersection with the upper end cap by intersecting
+        // the ray with the plane at cylinder maximum.
+        let t2 = (self.maximum - local_ray.origin[1]) / local_ray.direction[1];
+        if self.check_cap(local_ray, t2) {
+            intersections.push(Intersection::new(t2, object_id));
         }
     }
 
-    fn intersect_walls(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect_walls(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         let a = local_ray.direction[0]*local_ray.direction[0] +
             local_ray.direction[2]*local_ray.direction[2];
 
         if a.abs() < float::EPSILON {
             // Ray is parallel to the y axis
-            vec![]
         } else {
             let b = 2. * local_ray.origin[0]*local_ray.direction[0] +
                 2. * local_ray.origin[2]*local_ray.direction[2];
             let c = local_ray.origin[0]*local_ray.origin[0] +
-                local_ray.origin[2]*local_ray.origin[2] - 1.;
+                local_ray.origin[2]*local_ray.origin[2] - self.radius*self.radius;
             let discriminant = b*b - 4. * a * c;
 
             if discriminant < 0. {
                 // Ray does not intersect the cylinder
-                vec![]
             } else if discriminant == 0.0 {
                 // Ray is potentially tangent to cylinder
                 let t = -b / (2. * a);
                 let y = local_ray.origin[1] + local_ray.direction[1]*t;
                 if y > self.minimum && y < self.maximum {
-                    vec![t]
-                } else {
-                    vec![]
+                    intersections.push(Intersection::new(t, object_id));
                 }
             } else {
                 // Ray _does_ potentially intersect the cylinder twice
                 let t1 = (-b - discriminant.sqrt()) / (2. * a);
                 let t2 = (-b + discriminant.sqrt()) / (2. * a);
 
-                let mut ts = vec![];
                 let y1 = local_ray.origin[1] + local_ray.direction[1]*t1;
                 if y1 > self.minimum && y1 < self.maximum {
-                    ts.push(t1);
+                    intersections.push(Intersection::new(t1, object_id));
                 }
 
                 let y2 = local_ray.origin[1] + local_ray.direction[1]*t2;
                 if y2 > self.minimum && y2 < self.maximum {
-                    ts.push(t2);
+                    intersections.push(Intersection::new(t2, object_id));
                 }
-
-                ts
             }
         }
     }
 }
 
 impl Shape for Cylinder {
-    fn intersect(&self, local_ray: &ray::Ray) -> Vec<f64> {
-        let mut wall_ts = self.intersect_walls(local_ray);
-        let mut caps_ts = self.intersect_caps(local_ray);
-
-        wall_ts.append(&mut caps_ts);
-        wall_ts
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        self.intersect_walls(local_ray, object_id, intersections);
+        self.intersect_caps(local_ray, object_id, intersections);
     }
 
     fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
         let distance = local_point[0] * local_point[0] +
             local_point[2] * local_point[2];
 
-        if distance < 1. && local_point[1] >= self.maximum - EPSILON {
+        let radius_squared = self.radius * self.radius;
+        if distance < radius_squared && local_point[1] >= self.maximum - EPSILON {
             Tuple::vector(0., 1., 0.)
-        } else if distance < 1. && local_point[1] <= self.minimum + EPSILON {
+        } else if distance < radius_squared && local_point[1] <= self.minimum + EPSILON {
             Tuple::vector(0., -1., 0.)
         } else {
             Tuple::vector(local_point[0], 0., local_point[2])
         }
     }
+
+    fn is_bounded(&self) -> bool {
+        self.minimum.is_finite() && self.maximum.is_finite()
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-self.radius, self.minimum, -self.radius),
+            Tuple::point(self.radius, self.maximum, self.radius),
+        )
+    }
+
+    // Picks the walls or one of the caps in proportion to their areas.
+    // Cylinders that go on forever can only be sampled around one ring.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        let phi = 2. * PI * rng.random::<f64>();
+        if !self.is_bounded() {
+            let y = 0_f64.clamp(self.minimum, self.maximum);
+            return Tuple::point(self.radius * phi.cos(), y, self.radius * phi.sin());
+        }
+
+        let wall_area = 2. * PI * self.radius * (self.maximum - self.minimum);
+        let cap_area = if self.is_closed { PI * self.radius * self.radius } else { 0. };
+        let pick = rng.random::<f64>() * (wall_area + 2. * cap_area);
+        if pick < wall_area {
+            let y = self.minimum + (self.maximum - self.minimum) * rng.random::<f64>();
+            Tuple::point(self.radius * phi.cos(), y, self.radius * phi.sin())
+        } else {
+            // Taking the square root spreads the points evenly over the cap
+            // rather than bunching them up in the middle.
+            let r = self.radius * rng.random::<f64>().sqrt();
+            let y = if pick < wall_area + cap_area { self.minimum } else { self.maximum };
+            Tuple::point(r * phi.cos(), y, r * phi.sin())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cylinder::{Cylinder, material};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
     use crate::{float, matrix};
     use crate::ray::Ray;
-    use crate::shape::Shape;
+    use crate::shape::{intersect_ts, Shape};
     use crate::tuple::{Tuple, TupleMethods};
 
     #[test]
@@ -178,7 +260,7 @@ mod tests {
         ];
         for (origin, direction) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cylinder.intersect(&ray);
+            let ts = intersect_ts(&cylinder, &ray);
             assert_eq!(ts.len(), 0);
         }
     }
@@ -197,7 +279,7 @@ mod tests {
         ];
         for (origin, direction, expected_ts) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cylinder.intersect(&ray);
+            let ts = intersect_ts(&cylinder, &ray);
             assert!(ts.iter().zip(expected_ts).all(|(&a, b)| float::is_equal(a, b)));
         }
     }
@@ -220,7 +302,7 @@ mod tests {
         ];
         for (origin, direction, expected_count) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cylinder.intersect(&ray);
+            let ts = intersect_ts(&cylinder, &ray);
             assert_eq!(ts.len(), expected_count);
         }
     }
@@ -242,7 +324,7 @@ mod tests {
         ];
         for (origin, direction, expected_count) in test_cases {
             let ray = Ray::new(origin, direction.normalize());
-            let ts = cylinder.intersect(&ray);
+            let ts = intersect_ts(&cylinder, &ray);
             assert_eq!(ts.len(), expected_count);
         }
     }
@@ -289,4 +371,71 @@ mod tests {
             assert!(normal.is_equal(expected_value));
         }
     }
+
+    #[test]
+    fn test_intersect_with_radius() {
+        let cylinder = Cylinder::new_with_radius(
+            2.,
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+            -1.,
+            1.,
+            true,
+        );
+
+        let test_cases = vec![
+            (Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.), vec![3., 7.]),
+            (Tuple::point(2., 0., -5.), Tuple::vector(0., 0., 1.), vec![5.]),
+            (Tuple::point(1.5, 3., 0.), Tuple::vector(0., -1., 0.), vec![2., 4.]),
+            (Tuple::point(2.5, 0., -5.), Tuple::vector(0., 0., 1.), vec![]),
+        ];
+        for (origin, direction, expected) in test_cases {
+            let ray = Ray::new(origin, direction);
+            let mut ts = intersect_ts(&cylinder, &ray);
+            ts.sort_by(|t1, t2| t1.partial_cmp(t2).unwrap());
+            assert_eq!(ts.len(), expected.len());
+            for (t, expected_t) in ts.iter().zip(expected.iter()) {
+                assert!(float::is_equal(*t, *expected_t));
+            }
+        }
+
+        let normal = cylinder.normal_at(Tuple::point(1.5, 1., 0.));
+        assert!(normal.is_equal(Tuple::vector(0., 1., 0.)));
+    }
+
+    #[test]
+    fn test_tangent_at_wall_runs_around_cylinder() {
+        let cylinder = Cylinder::new_infinite(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let (tangent, bitangent) = cylinder.tangent_at(Tuple::point(1., 3., 0.));
+        assert!(tangent.is_equal(Tuple::vector(0., 0., 1.)));
+        assert!(bitangent.is_equal(Tuple::vector(0., 1., 0.)));
+    }
+
+    #[test]
+    fn test_random_point() {
+        let cylinder = Cylinder::new_capped(matrix::IDENTITY, material::DEFAULT_MATERIAL, 0., 2.);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            let point = cylinder.random_point(&mut rng);
+            let distance = (point[0]*point[0] + point[2]*point[2]).sqrt();
+            if point[1] == 0. {
+                assert!(distance <= 1.);
+                counts[0] += 1;
+            } else if point[1] == 2. {
+                assert!(distance <= 1.);
+                counts[2] += 1;
+            } else {
+                assert!(float::is_equal(distance, 1.) && point[1] > 0. && point[1] < 2.);
+                counts[1] += 1;
+            }
+        }
+        // The walls are four times the size of each cap.
+        assert!(counts[1] > 3 * counts[0] && counts[1] > 3 * counts[2]);
+        assert!(counts[0] > 50 && counts[2] > 50);
+
+        let infinite = Cylinder::new_infinite(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let point = infinite.random_point(&mut rng);
+        assert!(float::is_equal(point[0]*point[0] + point[2]*point[2], 1.));
+    }
 }
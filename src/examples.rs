@@ -34,6 +34,7 @@ pub fn purple_sphere() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let sphere = Object::Sphere(
             Sphere::new(
@@ -60,6 +61,7 @@ pub fn chapter_seven_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Sphere(
         Sphere::new(
@@ -100,6 +102,7 @@ pub fn chapter_seven_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let middle_sphere = Object::Sphere(
         Sphere::new(
@@ -119,6 +122,7 @@ pub fn chapter_seven_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let right_sphere = Object::Sphere(
         Sphere::new(
@@ -138,6 +142,7 @@ pub fn chapter_seven_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let left_sphere = Object::Sphere(
         Sphere::new(
@@ -164,6 +169,7 @@ pub fn chapter_nine_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Plane(
         Plane::new(
@@ -182,6 +188,7 @@ pub fn chapter_nine_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let middle_sphere = Object::Sphere(
         Sphere::new(
@@ -201,6 +208,7 @@ pub fn chapter_nine_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let right_sphere = Object::Sphere(
         Sphere::new(
@@ -220,6 +228,7 @@ pub fn chapter_nine_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let left_sphere = Object::Sphere(
         Sphere::new(
@@ -255,6 +264,7 @@ pub fn chapter_ten_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let striped_sphere = Object::Sphere(
         Sphere::new(
@@ -283,6 +293,7 @@ pub fn chapter_ten_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let gradient_sphere = Object::Sphere(
         Sphere::new(
@@ -310,6 +321,7 @@ pub fn chapter_ten_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let checkered_sphere = Object::Sphere(
         Sphere::new(
@@ -336,6 +348,7 @@ pub fn chapter_ten_scene() -> World {
         reflective: 0.0,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Plane(
         Plane::new(
@@ -362,6 +375,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.0,
         transparency: 0.9,
         refractive: 1.52,
+        casts_shadow: true,
     };
     let glass_ball = Object::Sphere(
         Sphere::new(
@@ -379,6 +393,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.9,
         transparency: 0.0,
         refractive: 0.0,
+        casts_shadow: true,
     };
     let green_metallic_ball = Object::Sphere(
         Sphere::new(
@@ -396,6 +411,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.9,
         transparency: 0.0,
         refractive: 0.0,
+        casts_shadow: true,
     };
     let red_metallic_ball = Object::Sphere(
         Sphere::new(
@@ -413,6 +429,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.1,
         transparency: 0.0,
         refractive: 0.0,
+        casts_shadow: true,
     };
     let orange_ball = Object::Sphere(
         Sphere::new(
@@ -430,6 +447,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.1,
         transparency: 0.0,
         refractive: 0.0,
+        casts_shadow: true,
     };
     let yellow_ball = Object::Sphere(
         Sphere::new(
@@ -456,6 +474,7 @@ pub fn chapter_eleven_scene() -> World {
         reflective: 0.4,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Plane(
         Plane::new(
@@ -500,6 +519,7 @@ pub fn chapter_twelve_scene() -> World {
         reflective: 0.1,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let cube = Object::Cube(
         Cube::new(
@@ -526,6 +546,7 @@ pub fn chapter_twelve_scene() -> World {
         reflective: 0.4,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Plane(
         Plane::new(
@@ -563,6 +584,7 @@ pub fn chapter_thirteen_scene() -> World {
         reflective: 0.1,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let cylinder = Object::Cylinder(
         Cylinder::new_capped(
@@ -590,6 +612,7 @@ pub fn chapter_thirteen_scene() -> World {
         reflective: 0.1,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let transform = transform::translation(2., 2., 0.)
         .multiply_matrix(transform::scaling(1., 2., 1.));
@@ -619,6 +642,7 @@ pub fn chapter_thirteen_scene() -> World {
         reflective: 0.4,
         transparency: 0.0,
         refractive: 1.0,
+        casts_shadow: true,
     };
     let floor = Object::Plane(
         Plane::new(
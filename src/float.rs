@@ -1,5 +1,35 @@
 pub(crate) const EPSILON: f64 = 0.00001;
 
+// JSON has no way to represent infinity, so unbounded values such as the
+// limits of an infinite cylinder are written out as strings instead; use
+// with #[serde(with = "crate::float::extended")].
+pub mod extended {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Extended {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&value.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Extended::deserialize(deserializer)? {
+            Extended::Number(value) => Ok(value),
+            Extended::Text(text) => text.parse::<f64>().map_err(D::Error::custom),
+        }
+    }
+}
+
 pub fn is_equal(a: f64, b: f64) -> bool {
     if (a - b).abs() < EPSILON {
         true
@@ -8,6 +38,16 @@ pub fn is_equal(a: f64, b: f64) -> bool {
     }
 }
 
+// Keeps a value between the two limits, which must be the right way round.
+pub fn clamp(v: f64, min: f64, max: f64) -> f64 {
+    debug_assert!(min <= max, "clamp limits {} and {} are the wrong way round", min, max);
+    v.max(min).min(max)
+}
+
+pub fn saturate(v: f64) -> f64 {
+    clamp(v, 0.0, 1.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -20,4 +60,32 @@ mod tests {
         assert_eq!(is_equal(0., 1.), false);
         assert_eq!(is_equal(0., 0.00001), false);
     }
+
+    #[test]
+    fn test_clamp() {
+        let test_cases = vec![
+            (-3., -2., 5., -2.),
+            (0.5, -2., 5., 0.5),
+            (7., -2., 5., 5.),
+            (f64::INFINITY, -2., 5., 5.),
+        ];
+
+        for (v, min, max, expected) in test_cases {
+            assert_eq!(clamp(v, min, max), expected);
+        }
+    }
+
+    #[test]
+    fn test_saturate() {
+        let test_cases = vec![
+            (-0.1, 0.),
+            (0.25, 0.25),
+            (1.5, 1.),
+            (f64::NAN, 0.),
+        ];
+
+        for (v, expected) in test_cases {
+            assert_eq!(saturate(v), expected);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{material, matrix, object, ray};
+use crate::intersection::Intersections;
+use crate::material::Material;
+use crate::matrix::{Matrix4, Matrix4Methods};
+use crate::object::Object;
+
+// A group is simply a collection of objects that can be placed in a scene
+// as a unit. Children keep their own transforms, and intersections always
+// refer to the child that was hit rather than to the group itself. The
+// group's own transform is baked into its children whenever it changes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub transform: Matrix4,
+    pub inverse_transform: Matrix4,
+    pub children: Vec<Object>,
+    pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+}
+
+impl Group {
+    pub fn new(children: Vec<Object>) -> Group {
+        Group {
+            transform: matrix::IDENTITY,
+            inverse_transform: matrix::IDENTITY,
+            children,
+            material: Material::new(),
+            id: object::next_id(),
+            visible: true,
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        // Undo the old group transform before applying the new one.
+        let change = transform.multiply_matrix(self.inverse_transform);
+        for child in self.children.iter_mut() {
+            let child_transform = change.multiply_matrix(child.get_transform());
+            child.set_transform(child_transform);
+        }
+        self.transform = transform;
+        self.inverse_transform = transform.inverse().unwrap();
+    }
+
+    pub fn extend_intersections(&self, world_ray: &ray::Ray, intersections: &mut Intersections) {
+        for child in self.children.iter() {
+            child.extend_intersections(world_ray, intersections);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{material, transform};
+    use crate::group::Group;
+    use crate::matrix::Matrix4Methods;
+    use crate::object::Object;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    #[test]
+    fn test_intersect_empty_group() {
+        let group = Object::Group(Group::new(vec![]));
+        let ray = Ray::new(
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 0., 1.),
+        );
+        assert_eq!(group.intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_intersect_refers_to_children() {
+        let s1 = Object::Sphere(
+            Sphere::new(transform::translation(0., 0., -3.), material::DEFAULT_MATERIAL)
+        );
+        let s2 = Object::Sphere(
+            Sphere::new(transform::translation(5., 0., 0.), material::DEFAULT_MATERIAL)
+        );
+        let s3 = Object::Sphere(
+            Sphere::new(transform::translation(0., 0., 3.), material::DEFAULT_MATERIAL)
+        );
+        let (s1_id, s3_id) = (s1.get_id(), s3.get_id());
+        let group = Object::Group(Group::new(vec![s1, s2, s3]));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -10.),
+            Tuple::vector(0., 0., 1.),
+        );
+        let intersections = group.intersect(&ray);
+        assert_eq!(intersections.len(), 4);
+        assert_eq!(intersections[0].object_id, s1_id);
+        assert_eq!(intersections[1].object_id, s1_id);
+        assert_eq!(intersections[2].object_id, s3_id);
+        assert_eq!(intersections[3].object_id, s3_id);
+    }
+
+    #[test]
+    fn test_set_transform_moves_children() {
+        let s1 = Object::Sphere(
+            Sphere::new(transform::translation(5., 0., 0.), material::DEFAULT_MATERIAL)
+        );
+        let mut group = Object::Group(Group::new(vec![s1]));
+        group.set_transform(transform::translation(-5., 0., 0.));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -10.),
+            Tuple::vector(0., 0., 1.),
+        );
+        let ts: Vec<f64> = group.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [9., 11.]);
+
+        // Setting the transform again replaces the old one rather than compounding it
+        group.set_transform(transform::translation(-5., 0., 2.));
+        let ts: Vec<f64> = group.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [11., 13.]);
+        assert!(group.get_transform().is_equal(transform::translation(-5., 0., 2.)));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::color::Color;
+use crate::intersection::Computations;
+use crate::object::Object;
+use crate::ray::Ray;
+use crate::tuple::Tuple;
+
+// Callbacks fired while a scene is rendered, for peeking at what the
+// renderer is up to. Every method does nothing by default, so a hook only
+// needs to implement the events it cares about.
+pub trait RenderHook {
+    // A ray leaving the camera through the center of the pixel at (x, y).
+    fn on_primary_ray(&mut self, _x: usize, _y: usize, _ray: &Ray) {}
+
+    // Any ray, primary or secondary, that hit something.
+    fn on_hit(&mut self, _ray: &Ray, _t: f64, _object: &Object) {}
+
+    // Any ray that hit nothing and picked up the background instead.
+    fn on_miss(&mut self, _ray: &Ray) {}
+
+    // A check for whether anything blocks the light from reaching a point.
+    fn on_shadow_test(&mut self, _point: Tuple, _is_shadowed: bool) {}
+
+    // A ray that couldn't get out of a transparent object and was reflected
+    // back inside it instead.
+    fn on_total_internal_reflection(&mut self, _comp: &Computations) {}
+
+    // The final color worked out for a hit, including reflection and
+    // refraction.
+    fn on_shade(&mut self, _comp: &Computations, _color: Color) {}
+
+    // Any ray about to be cast, before finding out what it hits.
+    fn on_ray_cast(&mut self, _kind: RayKind) {}
+
+    // Whether colors need working out at all. A hook that only cares which
+    // rays get cast can say no, and the lighting is skipped, along with
+    // shadow and environment rays, which are reported but never traced.
+    fn wants_colors(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayKind {
+    Primary,
+    Shadow,
+    // Checks whether anything blocks the environment along the normal.
+    Environment,
+    Reflected,
+    Refracted,
+}
+
+// The hook used for regular renders. Since every call on it is an empty
+// function, they all compile away to nothing.
+pub struct NoHook;
+
+impl RenderHook for NoHook {}
+
+// Writes down everything that happens as a line of text, for working out
+// why a particular pixel came out the way it did.
+#[derive(Default)]
+pub struct EventLog {
+    pub events: Vec<String>,
+}
+
+impl RenderHook for EventLog {
+    fn on_primary_ray(&mut self, x: usize, y: usize, ray: &Ray) {
+        self.events.push(format!("primary ray through pixel ({}, {}) from {:?} toward {:?}", x, y, ray.origin, ray.direction));
+    }
+
+    fn on_hit(&mut self, ray: &Ray, t: f64, object: &Object) {
+        self.events.push(format!("ray from {:?} toward {:?} hit object {} at t = {}", ray.origin, ray.direction, object.get_id(), t));
+    }
+
+    fn on_miss(&mut self, ray: &Ray) {
+        self.events.push(format!("ray from {:?} toward {:?} missed everything", ray.origin, ray.direction));
+    }
+
+    fn on_shadow_test(&mut self, point: Tuple, is_shadowed: bool) {
+        let outcome = if is_shadowed { "in shadow" } else { "lit" };
+        self.events.push(format!("point {:?} is {}", point, outcome));
+    }
+
+    fn on_total_internal_reflection(&mut self, comp: &Computations) {
+        self.events.push(format!("total internal reflection inside object {} at {:?}", comp.object_id, comp.point));
+    }
+
+    fn on_shade(&mut self, comp: &Computations, color: Color) {
+        self.events.push(format!("shaded object {} at {:?} as {:?}", comp.object_id, comp.point, color));
+    }
+}
+
+// Tallies the rays a render casts, without working out any colors.
+// Secondary rays are the reflected and refracted ones; shadow rays include
+// the checks for whether anything blocks the environment.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RayCounter {
+    pub primary: u64,
+    pub shadow: u64,
+    pub secondary: u64,
+}
+
+impl RayCounter {
+    pub fn total(&self) -> u64 {
+        self.primary + self.shadow + self.secondary
+    }
+}
+
+impl RenderHook for RayCounter {
+    fn on_ray_cast(&mut self, kind: RayKind) {
+        match kind {
+            RayKind::Primary => self.primary += 1,
+            RayKind::Shadow | RayKind::Environment => self.shadow += 1,
+            RayKind::Reflected | RayKind::Refracted => self.secondary += 1,
+        }
+    }
+
+    fn wants_colors(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::examples;
+    use crate::hook::RenderHook;
+    use crate::intersection::Computations;
+    use crate::object::Object;
+    use crate::ray::Ray;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    // Records the ray tree traced for a single pixel.
+    struct PixelTracer {
+        pixel: (usize, usize),
+        tracing: bool,
+        primary_rays: usize,
+        hits: Vec<(f64, u64)>,
+        misses: usize,
+        shades: Vec<Color>,
+    }
+
+    impl PixelTracer {
+        fn new(x: usize, y: usize) -> PixelTracer {
+            PixelTracer {
+                pixel: (x, y),
+                tracing: false,
+                primary_rays: 0,
+                hits: vec![],
+                misses: 0,
+                shades: vec![],
+            }
+        }
+    }
+
+    impl RenderHook for PixelTracer {
+        fn on_primary_ray(&mut self, x: usize, y: usize, _ray: &Ray) {
+            self.primary_rays += 1;
+            self.tracing = (x, y) == self.pixel;
+        }
+
+        fn on_hit(&mut self, _ray: &Ray, t: f64, object: &Object) {
+            if self.tracing {
+                self.hits.push((t, object.get_id()));
+            }
+        }
+
+        fn on_miss(&mut self, _ray: &Ray) {
+            if self.tracing {
+                self.misses += 1;
+            }
+        }
+
+        fn on_shade(&mut self, _comp: &Computations, color: Color) {
+            if self.tracing {
+                self.shades.push(color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_hooked_traces_pixel() {
+        let world = examples::chapter_twelve_scene();
+        let camera = Camera::look_at(
+            Tuple::point(0., 1.5, -5.),
+            Tuple::point(0., 1., 0.),
+            Tuple::vector(0., 1., 0.),
+            11,
+            11,
+            PI/3.,
+        );
+        let mut tracer = PixelTracer::new(5, 5);
+        let canvas = camera.render_hooked(&world, &mut tracer);
+
+        assert_eq!(tracer.primary_rays, 121);
+        assert!(!tracer.hits.is_empty());
+        assert_eq!(tracer.shades.len(), tracer.hits.len());
+        // The first shade reported is from deepest in the ray tree; the
+        // last is the color of the pixel itself.
+        assert_eq!(*tracer.shades.last().unwrap(), canvas.get_pixel(5, 5));
+    }
+
+    #[test]
+    fn test_render_hooked_matches_render() {
+        let camera = Camera::look_at(
+            Tuple::point(0., 1.5, -5.),
+            Tuple::point(0., 1., 0.),
+            Tuple::vector(0., 1., 0.),
+            11,
+            11,
+            PI/3.,
+        );
+        let hooked = camera.render_hooked(&examples::chapter_twelve_scene(), &mut PixelTracer::new(0, 0));
+        let expected = camera.render(&examples::chapter_twelve_scene()).unwrap();
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_eq!(hooked.get_pixel(x, y), expected.get_pixel(x, y));
+            }
+        }
+    }
+}
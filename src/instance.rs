@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{material, matrix, object, ray, tuple};
+use crate::bounds::BoundingBox;
+use crate::intersection::IntersectionBuffer;
+use crate::material::Material;
+use crate::matrix::{Matrix4, Matrix4Methods};
+use crate::shape::Shape;
+
+// Places a copy of some shared geometry in the scene with a transform and
+// material of its own, so that many instances of a complicated shape can
+// be scattered around without cloning it each time. Only the shape's own
+// geometry in object space is used; any transform or material it carries
+// is ignored in favor of the instance's.
+#[derive(Clone)]
+pub struct Instanced {
+    pub shared: Arc<dyn Shape + Send + Sync>,
+    pub transform: matrix::Matrix4,
+    pub inverse_transform: matrix::Matrix4,
+    pub material: material::Material,
+    pub id: u64,
+    pub visible: bool,
+}
+
+impl Instanced {
+    pub fn new(shared: Arc<dyn Shape + Send + Sync>, transform: Matrix4, material: Material) -> Instanced {
+        Instanced {
+            shared,
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+            material,
+            id: object::next_id(),
+            visible: true,
+        }
+    }
+}
+
+impl Shape for Instanced {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        self.shared.intersect(local_ray, object_id, intersections)
+    }
+
+    fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
+        self.shared.normal_at(local_point)
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.shared.is_bounded()
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        self.shared.local_bounds()
+    }
+
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        self.shared.random_point(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{material, matrix, transform};
+    use crate::instance::Instanced;
+    use crate::object::Object;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    #[test]
+    fn test_many_instances_of_shared_sphere() {
+        let shared: Arc<dyn Shape + Send + Sync> = Arc::new(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let instances: Vec<Object> = (0..100)
+            .map(|i| {
+                let t = transform::translation(3. * i as f64, 0., 0.);
+                Object::Instanced(Instanced::new(shared.clone(), t, material::DEFAULT_MATERIAL))
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&shared), 101);
+
+        for (i, instance) in instances.iter().enumerate() {
+            let ray = Ray::new(
+                Tuple::point(3. * i as f64, 0., -5.),
+                Tuple::vector(0., 0., 1.),
+            );
+            let ts: Vec<f64> = instance.intersect(&ray).iter().map(|i| i.t).collect();
+            assert_eq!(ts, [4., 6.]);
+
+            let missing_ray = Ray::new(
+                Tuple::point(3. * i as f64 + 1.5, 0., -5.),
+                Tuple::vector(0., 0., 1.),
+            );
+            assert!(instance.intersect(&missing_ray).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_normal_at_uses_instance_transform() {
+        let shared = Arc::new(Sphere::new(transform::translation(10., 0., 0.), material::DEFAULT_MATERIAL));
+        let instance = Object::Instanced(Instanced::new(
+            shared,
+            transform::translation(0., 1., 0.),
+            material::DEFAULT_MATERIAL,
+        ));
+        let normal = instance.normal_at(Tuple::point(1., 1., 0.));
+        assert!(normal.is_equal(Tuple::vector(1., 0., 0.)));
+    }
+}
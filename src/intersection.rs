@@ -3,83 +3,74 @@ use crate::object::Object;
 use crate::ray::Ray;
 use crate::tuple::Tuple;
 use crate::tuple::TupleMethods;
+use crate::world::World;
 
-#[derive(Clone)]
-pub struct Intersection<'scene> {
+// Most rays only cross a handful of surfaces, so with the `smallvec` feature
+// enabled intersection lists live on the stack until they outgrow it.
+#[cfg(feature = "smallvec")]
+pub type Intersections = smallvec::SmallVec<[Intersection; 8]>;
+#[cfg(not(feature = "smallvec"))]
+pub type Intersections = Vec<Intersection>;
+
+// The most intersections any single primitive shape can produce.
+pub const MAX_SHAPE_INTERSECTIONS: usize = 4;
+
+// With the `arrayvec` feature enabled, intersections with a single primitive
+// shape are gathered on the stack before joining the rest.
+#[cfg(feature = "arrayvec")]
+pub type ShapeIntersections = arrayvec::ArrayVec<Intersection, MAX_SHAPE_INTERSECTIONS>;
+
+// Where a ray crosses the surface of an object. The object is referred to
+// by its ID rather than borrowed, so that intersections can be kept around
+// and passed about freely; look it up again in the world when it's needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Intersection {
     pub t: f64,
-    pub object: &'scene Object,
+    pub object_id: u64,
 }
 
-impl Intersection<'_> {
-    pub fn new(t: f64, object: &Object) -> Intersection {
+impl Intersection {
+    pub fn new(t: f64, object_id: u64) -> Intersection {
         Intersection {
-            t: t,
-            object: &object,
+            t,
+            object_id,
         }
     }
 
-    pub fn prepare_computations(&self, ray: &Ray, all_intersections: Vec<Intersection>) -> Computations {
-        let point = ray.position_at(self.t);
-        let eye = ray.direction.negate();
-        let mut normal = self.object.normal_at(point);
-
-        let is_inside: bool;
-        if normal.dot(eye) < 0. {
-            is_inside = true;
-            normal = normal.negate();
-        } else {
-            is_inside = false;
-        }
-
-        let over_point = point.add(normal.multiply(float::EPSILON));
-        let under_point = point.subtract(normal.multiply(float::EPSILON));
-        let reflected = ray.direction.reflect(normal);
-
-        let (n1, n2) = self.refractive_indices_for(all_intersections);
-
-        Computations {
-            t: self.t,
-            point: point,
-            eye: eye,
-            normal: normal,
-            reflected: reflected,
-            is_inside: is_inside,
-            object: self.object,
-            over_point: over_point,
-            under_point: under_point,
-            n1: n1,
-            n2: n2,
-        }
+    pub fn object<'a>(&self, world: &'a World) -> &'a Object {
+        world
+            .get_object(self.object_id)
+            .expect("intersections must refer to objects in the world")
     }
 
-    pub fn refractive_indices_for(&self, all_intersections: Vec<Intersection>) -> (f64, f64) {
+    pub fn refractive_indices_for(&self, all_intersections: &[Intersection], world: &World) -> (f64, f64) {
+        let refractive_index = |container: Option<&u64>| match container {
+            Some(&id) => world.get_object(id).unwrap().get_material().refractive,
+            None => 1.0,
+        };
+
         let mut n1 = 1.0;
         let mut n2 = 1.0;
-        let mut containers: Vec<Intersection> = vec![];
-        for intersection in all_intersections {
-            let t = intersection.t;
-            if t == self.t {
-                n1 = match containers.last() {
-                    Some(i) => i.object.get_material().refractive,
-                    None => 1.0,
-                };
+        let mut containers: Vec<u64> = vec![];
+        for &intersection in all_intersections {
+            // Surfaces can coincide, so the object has to match as well.
+            let is_this_hit = intersection == *self;
+            if is_this_hit {
+                n1 = refractive_index(containers.last());
             }
             match containers
                 .iter()
-                .position(|container| container.object.is_equal(intersection.object)) {
+                .position(|&container| container == intersection.object_id) {
                 Some(index) => {
                     containers.remove(index);
                     ()
                 },
                 None => {
-                    containers.push(intersection)
+                    containers.push(intersection.object_id)
                 },
             };
-            if t == self.t {
-                n2 = match containers.last() {
-                    Some(i) => i.object.get_material().refractive,
-                    None => 1.0,
-                };
+            if is_this_hit {
+                n2 = refractive_index(containers.last());
                 break;
             }
         }
@@ -88,22 +79,122 @@ impl Intersection<'_> {
     }
 }
 
-pub struct Computations<'scene> {
+// Somewhere shapes can add intersections as they find them, so that they
+// never need a list of their own.
+pub trait IntersectionBuffer {
+    fn push(&mut self, intersection: Intersection);
+}
+
+impl IntersectionBuffer for Intersections {
+    fn push(&mut self, intersection: Intersection) {
+        Intersections::push(self, intersection);
+    }
+}
+
+// Pushing more intersections than a primitive shape can produce panics,
+// which the check on MAX_SHAPE_INTERSECTIONS rules out.
+#[cfg(feature = "arrayvec")]
+impl IntersectionBuffer for ShapeIntersections {
+    fn push(&mut self, intersection: Intersection) {
+        ShapeIntersections::push(self, intersection);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Computations {
     pub t: f64,
     pub point: Tuple,
     pub eye: Tuple,
     pub normal: Tuple,
-    pub reflected: Tuple,
     pub is_inside: bool,
-    pub object: &'scene Object,
+    pub object_id: u64,
     pub over_point: Tuple,
     pub under_point: Tuple,
     pub n1: f64,
     pub n2: f64,
 }
 
-pub fn hit<'a>(intersections: &'a mut Vec<Intersection>) -> Option<&'a Intersection<'a>> {
-    intersections.sort_by(|i1, i2| i1.t.partial_cmp(&i2.t).unwrap());
+impl Computations {
+    // Works out everything shading needs to know about where a ray hit,
+    // given every intersection along the ray for the refractive indices.
+    pub fn from_hit(intersection: &Intersection, ray: &Ray, all_intersections: &[Intersection], world: &World) -> Computations {
+        let (n1, n2) = intersection.refractive_indices_for(all_intersections, world);
+        Computations::with_indices(intersection, ray, n1, n2, world)
+    }
+
+    // The same as calling from_hit for each of the intersections in turn,
+    // but keeping track of which objects the ray is inside in a single
+    // pass rather than starting over from the beginning for every one.
+    pub fn prepare_many(intersections: &[Intersection], ray: &Ray, world: &World) -> Vec<Computations> {
+        let refractive_index = |container: Option<&u64>| match container {
+            Some(&id) => world.get_object(id).unwrap().get_material().refractive,
+            None => 1.0,
+        };
+
+        let mut containers: Vec<u64> = vec![];
+        let mut all_computations = Vec::with_capacity(intersections.len());
+        for intersection in intersections {
+            let n1 = refractive_index(containers.last());
+            match containers
+                .iter()
+                .position(|&container| container == intersection.object_id) {
+                Some(index) => {
+                    containers.remove(index);
+                },
+                None => {
+                    containers.push(intersection.object_id)
+                },
+            };
+            let n2 = refractive_index(containers.last());
+            all_computations.push(Computations::with_indices(intersection, ray, n1, n2, world));
+        }
+        all_computations
+    }
+
+    fn with_indices(intersection: &Intersection, ray: &Ray, n1: f64, n2: f64, world: &World) -> Computations {
+        let point = ray.position_at(intersection.t);
+        let eye = ray.direction.negate();
+        let mut normal = intersection.object(world).normal_at(point);
+
+        let is_inside: bool;
+        if normal.dot(eye) < 0. {
+            is_inside = true;
+            normal = normal.negate();
+        } else {
+            is_inside = false;
+        }
+
+        let over_point = point.add(normal.multiply(float::EPSILON));
+        let under_point = point.subtract(normal.multiply(float::EPSILON));
+
+        Computations {
+            t: intersection.t,
+            point,
+            eye,
+            normal,
+            is_inside,
+            object_id: intersection.object_id,
+            over_point,
+            under_point,
+            n1,
+            n2,
+        }
+    }
+
+    pub fn object<'a>(&self, world: &'a World) -> &'a Object {
+        world
+            .get_object(self.object_id)
+            .expect("computations must refer to objects in the world")
+    }
+}
+
+// Finds the nearest intersection in front of the ray's origin. The
+// intersections must already be sorted, as World::intersect returns them.
+pub fn hit(intersections: &[Intersection]) -> Option<&Intersection> {
+    debug_assert!(
+        intersections.windows(2).all(|pair| pair[0].t <= pair[1].t),
+        "intersections must be sorted before looking for a hit"
+    );
     intersections
         .iter()
         .filter(|i| i.t >= 0.)
@@ -124,10 +215,10 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let i1 = Intersection::new(1., &s);
-        let i2 = Intersection::new(2., &s);
-        let mut intersections = vec![i1.clone(), i2.clone()];
-        let hit = hit(&mut intersections).unwrap();
+        let i1 = Intersection::new(1., s.get_id());
+        let i2 = Intersection::new(2., s.get_id());
+        let intersections = vec![i1, i2];
+        let hit = hit(&intersections).unwrap();
         assert_eq!(hit.t, i1.t);
     }
 
@@ -137,10 +228,10 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let i1 = Intersection::new(-1., &s);
-        let i2 = Intersection::new(1., &s);
-        let mut intersections = vec![i1.clone(), i2.clone()];
-        let hit = hit(&mut intersections).unwrap();
+        let i1 = Intersection::new(-1., s.get_id());
+        let i2 = Intersection::new(1., s.get_id());
+        let intersections = vec![i1, i2];
+        let hit = hit(&intersections).unwrap();
         assert_eq!(hit.t, i2.t);
     }
 
@@ -150,10 +241,10 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let i1 = Intersection::new(-2., &s);
-        let i2 = Intersection::new(-1., &s);
-        let mut intersections = vec![i1.clone(), i2.clone()];
-        let hit = hit(&mut intersections);
+        let i1 = Intersection::new(-2., s.get_id());
+        let i2 = Intersection::new(-1., s.get_id());
+        let intersections = vec![i1, i2];
+        let hit = hit(&intersections);
         assert!(hit.is_none());
     }
 
@@ -163,17 +254,31 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let i1 = Intersection::new(5., &s);
-        let i2 = Intersection::new(7., &s);
-        let i3 = Intersection::new(-3., &s);
-        let i4 = Intersection::new(2., &s);
-        let mut intersections = vec![i1.clone(), i2.clone(), i3.clone(), i4.clone()];
-        let hit = hit(&mut intersections).unwrap();
+        let i1 = Intersection::new(5., s.get_id());
+        let i2 = Intersection::new(7., s.get_id());
+        let i3 = Intersection::new(-3., s.get_id());
+        let i4 = Intersection::new(2., s.get_id());
+        let mut intersections = vec![i1, i2, i3, i4];
+        // Sorting is left to whoever collects the intersections.
+        intersections.sort_by(|i1, i2| i1.t.partial_cmp(&i2.t).unwrap());
+        let hit = hit(&intersections).unwrap();
         assert_eq!(hit.t, i4.t);
     }
 
+    #[cfg(debug_assertions)]
     #[test]
-    fn test_prepare_computations_outside() {
+    #[should_panic(expected = "intersections must be sorted")]
+    fn test_hit_rejects_unsorted_intersections() {
+        let s = Object::Sphere(Sphere::new(
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        ));
+        let intersections = vec![Intersection::new(5., s.get_id()), Intersection::new(2., s.get_id())];
+        hit(&intersections);
+    }
+
+    #[test]
+    fn test_computations_from_hit_outside() {
         let ray = Ray::new(
             Tuple::point(0., 0., -5.),
             Tuple::vector(0., 0., 1.)
@@ -182,9 +287,10 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let intersection = Intersection::new(4., &s);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let intersection = Intersection::new(4., s.get_id());
+        let world = World::new(light::Light::new(Tuple::point(-10., 10., -10.), color::WHITE), vec![s]);
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         assert_eq!(computations.t, intersection.t);
         assert!(computations.point.is_equal(Tuple::point(0., 0., -1.)));
@@ -194,7 +300,7 @@ mod tests {
     }
 
     #[test]
-    fn test_prepare_computations_inside() {
+    fn test_computations_from_hit_inside() {
         let ray = Ray::new(
             Tuple::point(0., 0., 0.),
             Tuple::vector(0., 0., 1.)
@@ -203,9 +309,10 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         ));
-        let intersection = Intersection::new(1., &s);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let intersection = Intersection::new(1., s.get_id());
+        let world = World::new(light::Light::new(Tuple::point(-10., 10., -10.), color::WHITE), vec![s]);
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         assert_eq!(computations.t, intersection.t);
         assert!(computations.point.is_equal(Tuple::point(0., 0., 1.)));
@@ -215,7 +322,7 @@ mod tests {
     }
 
     #[test]
-    fn test_prepare_computations_n1_n2() {
+    fn test_computations_from_hit_n1_n2() {
         let ta = transform::scaling(2., 2., 2.);
         let ma = material::DEFAULT_MATERIAL.with_refractive(1.5);
         let sphere_a = Object::Sphere(
@@ -238,10 +345,7 @@ mod tests {
             tuple::Tuple::point(-10., 10., -10.),
             color::Color::new(1., 1., 1.)
         );
-        let world = World {
-            light: light,
-            objects: vec![sphere_a, sphere_b, sphere_c],
-        };
+        let world = World::new(light, vec![sphere_a, sphere_b, sphere_c]);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -4.),
@@ -259,8 +363,63 @@ mod tests {
         for i in 0..6 {
             let all_intersections = world.intersect(&ray);
             let hit = &all_intersections[i];
-            let computations = hit.prepare_computations(&ray, all_intersections.clone());
+            let computations = Computations::from_hit(hit, &ray, &all_intersections, &world);
             assert_eq!((computations.n1, computations.n2), expected_values[i]);
         }
     }
+
+    #[test]
+    fn test_prepare_many_matches_from_hit() {
+        let a = Sphere::new(
+            transform::scaling(2., 2., 2.),
+            material::DEFAULT_MATERIAL.with_refractive(1.5),
+        );
+        let b = Sphere::new(
+            transform::translation(0., 0., -0.25),
+            material::DEFAULT_MATERIAL.with_refractive(2.0),
+        );
+        let c = Sphere::new(
+            transform::translation(0., 0., 0.25),
+            material::DEFAULT_MATERIAL.with_refractive(2.5),
+        );
+        let light = light::Light::new(Tuple::point(-10., 10., -10.), color::WHITE);
+        let world = World::new(light, vec![Object::Sphere(a), Object::Sphere(b), Object::Sphere(c)]);
+        let ray = Ray::new(
+            Tuple::point(0., 0., -4.),
+            Tuple::vector(0., 0., 1.),
+        );
+
+        let intersections = world.intersect(&ray);
+        let all_computations = Computations::prepare_many(&intersections, &ray, &world);
+        assert_eq!(all_computations.len(), 6);
+        for (intersection, computations) in intersections.iter().zip(all_computations.iter()) {
+            let expected = Computations::from_hit(intersection, &ray, &intersections, &world);
+            assert_eq!(computations.t, expected.t);
+            assert_eq!(computations.object_id, expected.object_id);
+            assert!(computations.point.is_equal(expected.point));
+            assert!(computations.normal.is_equal(expected.normal));
+            assert_eq!((computations.n1, computations.n2), (expected.n1, expected.n2));
+        }
+    }
+
+    #[test]
+    fn test_refractive_indices_for_coincident_surfaces() {
+        let sphere_a = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL.with_refractive(1.5)));
+        let sphere_b = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL.with_refractive(2.0)));
+        let (id_a, id_b) = (sphere_a.get_id(), sphere_b.get_id());
+        let light = light::Light::new(Tuple::point(-10., 10., -10.), color::WHITE);
+        let world = World::new(light, vec![sphere_a, sphere_b]);
+
+        let intersections = [
+            Intersection::new(4., id_a),
+            Intersection::new(4., id_b),
+            Intersection::new(6., id_a),
+            Intersection::new(6., id_b),
+        ];
+        let expected_values = [(1.0, 1.5), (1.5, 2.0), (2.0, 2.0), (2.0, 1.0)];
+        for (intersection, expected) in intersections.iter().zip(expected_values.iter()) {
+            let indices = intersection.refractive_indices_for(&intersections, &world);
+            assert_eq!(indices, *expected);
+        }
+    }
 }
@@ -1,15 +1,233 @@
-use crate::{color, tuple};
+use serde::{Deserialize, Serialize};
 
+use crate::{canvas, color, matrix, tuple, uv};
+use crate::matrix::Matrix4Methods;
+use crate::tuple::TupleMethods;
+
+// How quickly a light dims with distance. Lights don't dim at all by
+// default, which is how the rest of the renderer has always worked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Falloff {
+    #[default]
+    None,
+    // The physically correct inverse square law, with the light's
+    // intensity being what it gives off one unit away.
+    Quadratic,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Light {
     pub intensity: color::Color,
     pub position: tuple::Tuple,
+    #[serde(default)]
+    pub falloff: Falloff,
 }
 
+// Keeps lights made from photometric quantities within a range that still
+// tone maps sensibly.
+const MAX_INTENSITY: f64 = 100.;
+
+// Lumens per watt at the peak sensitivity of the eye.
+const LUMENS_PER_WATT: f64 = 683.;
+
 impl Light {
     pub fn new(position: tuple::Tuple, intensity: color::Color) -> Light {
         Light {
             intensity: intensity,
             position: position,
+            falloff: Falloff::None,
+        }
+    }
+
+    // A light the color of a black body at the given temperature, as
+    // bright as the given luminous flux, roughly converted to watts.
+    pub fn from_temperature(position: tuple::Tuple, kelvin: f64, lumens: f64) -> Light {
+        let scale = lumens / LUMENS_PER_WATT;
+        let color = color::Color::from_kelvin(kelvin);
+        let intensity = color::Color::new(
+            (color.r * scale).clamp(0., MAX_INTENSITY),
+            (color.g * scale).clamp(0., MAX_INTENSITY),
+            (color.b * scale).clamp(0., MAX_INTENSITY),
+        );
+        Light::new(position, intensity)
+    }
+
+    // Warm white daylight. There are no directional lights, so the sun is
+    // placed far enough away that its rays are as good as parallel, and
+    // without any falloff.
+    pub fn sun() -> Light {
+        Light::from_temperature(tuple::Tuple::point(-1e6, 1e6, -1e6), 5500., LUMENS_PER_WATT)
+    }
+
+    // The warm orange glow of a candle, dimming quickly with distance.
+    pub fn candle(position: tuple::Tuple) -> Light {
+        Light::from_temperature(position, 1850., LUMENS_PER_WATT)
+            .with_falloff(Falloff::Quadratic)
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    // How bright the light is by the time it reaches the given point.
+    pub fn intensity_at(&self, point: tuple::Tuple) -> color::Color {
+        match self.falloff {
+            Falloff::None => self.intensity,
+            Falloff::Quadratic => {
+                let distance = self.position.subtract(point).magnitude();
+                self.intensity.multiply(1. / (distance * distance))
+            }
+        }
+    }
+}
+
+// An environment light surrounds the entire scene; rather than being sampled
+// at a point, it is looked up by direction in an equirectangular image.
+#[derive(Serialize, Deserialize)]
+pub struct EnvironmentLight {
+    pub canvas: canvas::Canvas,
+    pub intensity: f64,
+    pub transform: matrix::Matrix4,
+    pub inverse_transform: matrix::Matrix4,
+}
+
+impl EnvironmentLight {
+    pub fn new(canvas: canvas::Canvas, intensity: f64, transform: matrix::Matrix4) -> EnvironmentLight {
+        EnvironmentLight {
+            canvas,
+            intensity,
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+        }
+    }
+
+    pub fn sample_direction(&self, direction: tuple::Tuple) -> color::Color {
+        let local_direction = self.inverse_transform
+            .multiply_tuple(direction)
+            .normalize();
+
+        let (u, v) = uv::spherical_map(local_direction);
+        self.sample_bilinear(u, v).multiply(self.intensity)
+    }
+
+    fn sample_bilinear(&self, u: f64, v: f64) -> color::Color {
+        let width = self.canvas.width as i64;
+        let height = self.canvas.height as i64;
+
+        // Pixel centers sit at half-integer coordinates; u wraps around
+        // horizontally whereas v is clamped at the poles.
+        let x = u * width as f64 - 0.5;
+        let y = ((1. - v) * height as f64 - 0.5).clamp(0., (height - 1) as f64);
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let left = (x0 as i64).rem_euclid(width) as usize;
+        let right = (x0 as i64 + 1).rem_euclid(width) as usize;
+        let top = y0 as usize;
+        let bottom = (y0 as i64 + 1).min(height - 1) as usize;
+
+        let upper = self.canvas.get_pixel(left, top).multiply(1. - fx)
+            .add(self.canvas.get_pixel(right, top).multiply(fx));
+        let lower = self.canvas.get_pixel(left, bottom).multiply(1. - fx)
+            .add(self.canvas.get_pixel(right, bottom).multiply(fx));
+        upper.multiply(1. - fy).add(lower.multiply(fy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::tuple::Tuple;
+    use super::*;
+
+    fn white_canvas(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                canvas.set_pixel(x, y, color::WHITE);
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_sample_direction_all_white_environment() {
+        let environment = EnvironmentLight::new(
+            white_canvas(8, 4),
+            1.0,
+            matrix::IDENTITY,
+        );
+        let directions = vec![
+            Tuple::vector(0., 1., 0.),
+            Tuple::vector(0., -1., 0.),
+            Tuple::vector(1., 0., 0.),
+            Tuple::vector(0., 0., -1.),
+            Tuple::vector(-1., 2., 3.),
+        ];
+        for direction in directions {
+            assert_eq!(environment.sample_direction(direction), color::WHITE);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_direction_scaled_by_intensity() {
+        let environment = EnvironmentLight::new(
+            white_canvas(8, 4),
+            0.5,
+            matrix::IDENTITY,
+        );
+        let color = environment.sample_direction(Tuple::vector(0., 0., 1.));
+        assert_eq!(color, color::Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_sample_direction_picks_hemisphere() {
+        let mut canvas = Canvas::new(4, 2);
+        for x in 0..4 {
+            canvas.set_pixel(x, 0, color::WHITE);
+        }
+        let environment = EnvironmentLight::new(canvas, 1.0, matrix::IDENTITY);
+        assert_eq!(environment.sample_direction(Tuple::vector(0., 1., 0.)), color::WHITE);
+        assert_eq!(environment.sample_direction(Tuple::vector(0., -1., 0.)), color::BLACK);
+    }
+
+    #[test]
+    fn test_from_temperature() {
+        let position = Tuple::point(0., 5., 0.);
+        let light = Light::from_temperature(position, 6600., 1366.);
+        assert_eq!(light.intensity, color::Color::new(2., 2., 2.));
+        assert_eq!(light.falloff, Falloff::None);
+
+        let blinding = Light::from_temperature(position, 6600., 1e9);
+        assert_eq!(blinding.intensity, color::Color::new(100., 100., 100.));
+    }
+
+    #[test]
+    fn test_sun_and_candle() {
+        let sun = Light::sun();
+        assert_eq!(sun.intensity, color::Color::from_kelvin(5500.));
+        assert_eq!(sun.intensity_at(Tuple::point(0., 0., 0.)), sun.intensity);
+
+        let candle = Light::candle(Tuple::point(0., 1., 0.));
+        assert_eq!(candle.falloff, Falloff::Quadratic);
+        assert!(candle.intensity.r > candle.intensity.g && candle.intensity.g > candle.intensity.b);
+    }
+
+    #[test]
+    fn test_intensity_at_with_quadratic_falloff() {
+        let light = Light::new(Tuple::point(0., 0., 0.), color::WHITE)
+            .with_falloff(Falloff::Quadratic);
+        let test_cases = vec![
+            (Tuple::point(0., 1., 0.), color::WHITE),
+            (Tuple::point(2., 0., 0.), color::Color::new(0.25, 0.25, 0.25)),
+            (Tuple::point(0., 0., -10.), color::Color::new(0.01, 0.01, 0.01)),
+        ];
+
+        for (point, expected_intensity) in test_cases {
+            assert_eq!(light.intensity_at(point), expected_intensity);
+        }
+    }
+}
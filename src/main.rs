@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
 use std::f64::consts::PI;
 
 use crate::camera::Camera;
@@ -5,6 +7,8 @@ use crate::ppm::Saveable;
 use crate::tuple::Tuple;
 use crate::tuple::TupleMethods;
 
+mod bounds;
+mod bvh;
 mod camera;
 mod canvas;
 mod color;
@@ -13,32 +17,50 @@ mod cube;
 mod cylinder;
 mod examples;
 mod float;
+mod group;
+mod hook;
+mod instance;
 mod intersection;
 mod light;
 mod material;
 mod matrix;
+mod mesh;
+mod noise;
+mod obj;
 mod object;
 mod pattern;
 mod plane;
 mod ppm;
 mod ray;
 mod shape;
+mod sky;
 mod sphere;
+mod stl;
+mod terrain;
 mod transform;
+mod triangle;
 mod tuple;
+mod uv;
 mod world;
 
 fn main() {
-    let world = examples::chapter_thirteen_scene();
+    let mut world = examples::chapter_thirteen_scene();
+    world.precompute();
 
     let from = Tuple::point(0., 3.5, -5.);
     let to = Tuple::point(0., 1., 0.);
     let up = Tuple::vector(0., 1., 0.);
-    let view = transform::view(from, to, up);
+    let view = transform::look_at(from, to, up);
     let camera = Camera::new(view, 800, 800, PI/2.);
 
     println!("Rendering scene...");
-    let canvas = camera.render(world);
+    let canvas = match camera.render(&world) {
+        Ok(canvas) => canvas,
+        Err(error) => {
+            println!("Whoops! Couldn't render the scene: {}", error);
+            return;
+        },
+    };
 
     println!("Saving file...");
     let result = canvas.save("test.ppm");
@@ -1,19 +1,29 @@
-use crate::{color, light, material, pattern, tuple};
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{color, light, material, matrix, pattern, tuple};
+use crate::camera::Camera;
+use crate::canvas::Canvas;
 use crate::color::Color;
 use crate::material::Coloring::{SolidColor, SurfacePattern};
 use crate::object::Object;
 use crate::pattern::Pattern;
 use crate::pattern::PatternMethods;
+use crate::light::Light;
 use crate::shape::Shape;
-use crate::tuple::TupleMethods;
+use crate::sphere::Sphere;
+use crate::tuple::{Tuple, TupleMethods};
+use crate::world::World;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Coloring {
     SolidColor(Color),
     SurfacePattern(Pattern),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Material {
     pub color: Coloring,
     pub ambient: f64,
@@ -23,6 +33,7 @@ pub struct Material {
     pub reflective: f64,
     pub transparency: f64,
     pub refractive: f64,
+    pub casts_shadow: bool,
 }
 
 pub const DEFAULT_MATERIAL:Material = Material {
@@ -34,6 +45,7 @@ pub const DEFAULT_MATERIAL:Material = Material {
     reflective: 0.0,
     transparency: 0.0,
     refractive: 1.0,
+    casts_shadow: true,
 };
 
 impl Material {
@@ -51,6 +63,14 @@ impl Material {
             reflective: self.reflective,
             transparency: self.transparency,
             refractive: refractive,
+            casts_shadow: self.casts_shadow,
+        }
+    }
+
+    pub fn color_at(&self, object: &Object, point: tuple::Tuple) -> color::Color {
+        match &self.color {
+            SolidColor(color) => *color,
+            SurfacePattern(pattern) => pattern.color_at(object, point),
         }
     }
 
@@ -62,10 +82,8 @@ impl Material {
                     normal: tuple::Tuple,
                     is_shadowed: bool) -> color::Color {
         // Combine the surface color with the light's color/intensity
-        let effective_color = match &self.color {
-            SolidColor(color) => *color,
-            SurfacePattern(pattern) => pattern.color_at(object, point),
-        }.hadamard(light.intensity);
+        let intensity = light.intensity_at(point);
+        let effective_color = self.color_at(object, point).hadamard(intensity);
         let ambient = effective_color.multiply(self.ambient);
 
         if is_shadowed == true {
@@ -99,7 +117,7 @@ impl Material {
                 } else {
                     // Compute the specular contribution
                     let factor = reflected_dot_eye.powf(self.shininess);
-                    specular = light.intensity.multiply(self.specular * factor);
+                    specular = intensity.multiply(self.specular * factor);
                 }
             }
 
@@ -109,6 +127,44 @@ impl Material {
     }
 }
 
+// Renders a unit sphere in the material, for previewing materials as
+// thumbnails. The sphere is lit from the usual three directions: a bright key
+// light up and to the left, a dimmer fill light to the right and a back
+// light behind and above it. A world only has the one light, so the sphere
+// is rendered once per light and the renders added together. Since there is
+// nothing else in the scene, that comes out the same as lighting it with all
+// three at once, provided the ambient light and the gray backdrop are only
+// counted in the key light's render.
+pub fn material_preview(material: Material, size: usize) -> Canvas {
+    let camera = Camera::look_at(
+        Tuple::point(0., 0., -3.),
+        Tuple::point(0., 0., 0.),
+        Tuple::vector(0., 1., 0.),
+        size,
+        size,
+        PI / 3.,
+    );
+    let unlit = Material { ambient: 0., ..material.clone() };
+    let lights = vec![
+        (Light::new(Tuple::point(-5., 5., -5.), color::WHITE), material, Color::new(0.2, 0.2, 0.2)),
+        (Light::new(Tuple::point(5., 2., -5.), Color::new(0.4, 0.4, 0.4)), unlit.clone(), color::BLACK),
+        (Light::new(Tuple::point(0., 5., 5.), Color::new(0.6, 0.6, 0.6)), unlit, color::BLACK),
+    ];
+
+    let mut preview = Canvas::new(size, size);
+    for (light, material, background) in lights {
+        let sphere = Object::Sphere(Sphere::new(matrix::IDENTITY, material));
+        let world = World::new(light, vec![sphere]).with_background_color(background);
+        let canvas = camera.render_unchecked(&world);
+        for y in 0..size {
+            for x in 0..size {
+                preview.set_pixel(x, y, preview.get_pixel(x, y).add(canvas.get_pixel(x, y)));
+            }
+        }
+    }
+    preview
+}
+
 #[cfg(test)]
 mod tests {
     use crate::color::Color;
@@ -120,6 +176,12 @@ mod tests {
     use crate::tuple::Tuple;
     use super::*;
 
+    #[test]
+    fn test_default_material_casts_shadow() {
+        assert!(Material::new().casts_shadow);
+        assert!(Material::new().with_refractive(1.5).casts_shadow);
+    }
+
     #[test]
     fn test_lighting_eye_between_light_and_surface() {
         let material = Material::new();
@@ -205,6 +267,55 @@ mod tests {
         assert_eq!(color, Color::new(0.1, 0.1, 0.1));
     }
 
+    #[test]
+    fn test_lighting_configurations() {
+        let sqrt2_over_2 = 2.0_f64.sqrt() / 2.;
+        // (geometry, eye vector, light position, in shadow, expected color)
+        let cases = vec![
+            ("eye looks straight down the normal, with the light right behind it",
+             Tuple::vector(0., 0., -1.), Tuple::point(0., 0., -10.), false, Color::new(1.9, 1.9, 1.9)),
+            ("eye raised 45 degrees off the normal, so the highlight is lost",
+             Tuple::vector(0., sqrt2_over_2, -sqrt2_over_2), Tuple::point(0., 0., -10.), false, Color::new(1.0, 1.0, 1.0)),
+            ("light raised 45 degrees off the normal, dimming the diffuse term",
+             Tuple::vector(0., 0., -1.), Tuple::point(0., 10., -10.), false, Color::new(0.7364, 0.7364, 0.7364)),
+            ("light raised 45 degrees and eye lowered 45, right in the reflection",
+             Tuple::vector(0., -sqrt2_over_2, -sqrt2_over_2), Tuple::point(0., 10., -10.), false, Color::new(1.6364, 1.6364, 1.6364)),
+            ("light on the far side of the surface, leaving only ambient",
+             Tuple::vector(0., 0., -1.), Tuple::point(0., 0., 10.), false, Color::new(0.1, 0.1, 0.1)),
+            ("light far away along the normal; point lights have no falloff",
+             Tuple::vector(0., 0., -1.), Tuple::point(0., 0., -1000.), false, Color::new(1.9, 1.9, 1.9)),
+            ("point in shadow, leaving only ambient",
+             Tuple::vector(0., 0., -1.), Tuple::point(0., 0., -10.), true, Color::new(0.1, 0.1, 0.1)),
+        ];
+
+        let material = Material::new();
+        let position = Tuple::point(0., 0., 0.);
+        let normal = Tuple::vector(0., 0., -1.);
+        let sphere = Object::Sphere(
+            Sphere::new(
+                matrix::IDENTITY,
+                material::DEFAULT_MATERIAL,
+            )
+        );
+        for (geometry, eye, light_position, is_shadowed, expected_value) in cases {
+            let light = light::Light::new(light_position, color::WHITE);
+            let color = material.lighting(&light, &sphere, position, eye, normal, is_shadowed);
+            assert_eq!(color, expected_value, "{}", geometry);
+        }
+    }
+
+    #[test]
+    fn test_lighting_with_quadratic_falloff() {
+        let material = Material::new();
+        let sphere = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let light = light::Light::new(Tuple::point(0., 0., -2.), color::WHITE)
+            .with_falloff(light::Falloff::Quadratic);
+        let eye = Tuple::vector(0., 0., -1.);
+        let normal = Tuple::vector(0., 0., -1.);
+        let color = material.lighting(&light, &sphere, Tuple::point(0., 0., 0.), eye, normal, false);
+        assert_eq!(color, Color::new(0.475, 0.475, 0.475));
+    }
+
     #[test]
     fn test_lighting_with_pattern() {
         let pattern = Striped::new(
@@ -221,6 +332,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let sphere = Object::Sphere(
             Sphere::new(
@@ -242,4 +354,19 @@ mod tests {
         let c2 = material.lighting(&light, &sphere, p2, eye, normal, false);
         assert_eq!(c2, color::BLACK);
     }
+
+    #[test]
+    fn test_material_preview() {
+        let red = Material {
+            color: SolidColor(Color::new(1., 0.2, 0.2)),
+            ..DEFAULT_MATERIAL
+        };
+        let preview = material_preview(red, 21);
+        assert_eq!(preview.width, 21);
+        assert_eq!(preview.height, 21);
+        assert_eq!(preview.get_pixel(0, 0), Color::new(0.2, 0.2, 0.2));
+        let center = preview.get_pixel(10, 10);
+        assert!(center.r > 0.2 && center.r > center.g);
+        assert!(preview.get_pixel(6, 6) != preview.get_pixel(14, 14));
+    }
 }
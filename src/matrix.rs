@@ -1,3 +1,7 @@
+use std::ops::{Deref, Index};
+
+use serde::{Deserialize, Serialize};
+
 use crate::float;
 use crate::tuple;
 use crate::tuple::TupleMethods;
@@ -66,14 +70,39 @@ impl Matrix3Methods for Matrix3 {
     }
 }
 
-pub type Matrix4 = [[f64; 4]; 4];
+// Wrapping the rows in a newtype keeps a matrix from being mistaken for any
+// other nested array, such as a list of tuples.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Matrix4([[f64; 4]; 4]);
+
+impl From<[[f64; 4]; 4]> for Matrix4 {
+    fn from(rows: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4(rows)
+    }
+}
+
+impl Deref for Matrix4 {
+    type Target = [[f64; 4]; 4];
 
-pub const IDENTITY: Matrix4 = [
+    fn deref(&self) -> &[[f64; 4]; 4] {
+        &self.0
+    }
+}
+
+impl Index<usize> for Matrix4 {
+    type Output = [f64; 4];
+
+    fn index(&self, row: usize) -> &[f64; 4] {
+        &self.0[row]
+    }
+}
+
+pub const IDENTITY: Matrix4 = Matrix4([
     [1., 0., 0., 0.],
     [0., 1., 0., 0.],
     [0., 0., 1., 0.],
     [0., 0., 0., 1.]
-];
+]);
 
 pub trait Matrix4Methods {
     fn is_equal(&self, other: Matrix4) -> bool;
@@ -98,13 +127,13 @@ impl Matrix4Methods for Matrix4 {
     }
 
     fn multiply_matrix(&self, other: Matrix4) -> Matrix4 {
-        let mut m: Matrix4 = [[0.; 4]; 4];
+        let mut m = [[0.; 4]; 4];
         for r in 0..4 {
             for c in 0..4 {
                 m[r][c] = self[r].dot([other[0][c], other[1][c], other[2][c], other[3][c]]);
             }
         }
-        m
+        Matrix4(m)
     }
 
     fn multiply_tuple(&self, t: tuple::Tuple) -> tuple::Tuple {
@@ -116,13 +145,13 @@ impl Matrix4Methods for Matrix4 {
     }
 
     fn transpose(&self) -> Matrix4 {
-        let mut m: Matrix4 = [[0.; 4]; 4];
+        let mut m = [[0.; 4]; 4];
         for r in 0..4 {
             for c in 0..4 {
                 m[r][c] = self[c][r];
             }
         }
-        m
+        Matrix4(m)
     }
 
     fn submatrix(&self, i: usize, j: usize) -> Matrix3 {
@@ -171,13 +200,13 @@ impl Matrix4Methods for Matrix4 {
         if d == 0. {
             None
         } else {
-            let mut m2: Matrix4 = [[0.; 4]; 4];
+            let mut m2 = [[0.; 4]; 4];
             for r in 0..4 {
                 for c in 0..4 {
                     m2[c][r] = (*self).cofactor(c, r)/d;
                 }
             }
-            Some(m2)
+            Some(Matrix4(m2))
         }
     }
 }
@@ -189,68 +218,83 @@ mod tests {
 
     #[test]
     fn test_is_equal() {
-        let m1 = [
+        let m1 = Matrix4::from([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.]
-        ];
-        let m2 = [
+        ]);
+        let m2 = Matrix4::from([
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.]
-        ];
+        ]);
         assert!(m1.is_equal(m2));
 
-        let m3 = [
+        let m3 = Matrix4::from([
             [2., 3., 4., 5.],
             [6., 7., 8., 9.],
             [8., 7., 6., 5.],
             [4., 3., 2., 1.]
-        ];
+        ]);
         assert!(!m1.is_equal(m3));
     }
 
     #[test]
-    fn test_multiply_matrix() {
-        let m1 = [
+    fn test_from_and_index() {
+        let rows = [
             [1., 2., 3., 4.],
             [5., 6., 7., 8.],
             [9., 8., 7., 6.],
             [5., 4., 3., 2.]
         ];
-        let m2 = [
+        let m = Matrix4::from(rows);
+        assert_eq!(m[2], [9., 8., 7., 6.]);
+        assert_eq!(m[1][3], 8.);
+        assert_eq!(*m, rows);
+        assert_eq!(m, Matrix4::from(rows));
+    }
+
+    #[test]
+    fn test_multiply_matrix() {
+        let m1 = Matrix4::from([
+            [1., 2., 3., 4.],
+            [5., 6., 7., 8.],
+            [9., 8., 7., 6.],
+            [5., 4., 3., 2.]
+        ]);
+        let m2 = Matrix4::from([
             [-2., 1., 2., 3.],
             [3., 2., 1., -1.],
             [4., 3., 6., 5.],
             [1., 2., 7., 8.]
-        ];
-        let expected_value = [
+        ]);
+        let expected_value = Matrix4::from([
             [20., 22., 50., 48.],
             [44., 54., 114., 108.],
             [40., 58., 110., 102.],
             [16., 26., 46., 42.]
-        ];
+        ]);
         assert!(m1.multiply_matrix(m2).is_equal(expected_value));
 
-        let m3 = [
+        let m3 = Matrix4::from([
             [0., 1., 2., 4.],
             [1., 2., 4., 8.],
             [2., 4., 8., 16.],
             [4., 8., 16., 32.]
-        ];
+        ]);
         assert!(m3.multiply_matrix(IDENTITY).is_equal(m3));
     }
 
     #[test]
     fn test_multiply_by_tuple() {
-        let m = [
+        let m = Matrix4::from([
             [1., 2., 3., 4.],
             [2., 4., 4., 2.],
             [8., 6., 4., 1.],
             [0., 0., 0., 1.]
-        ];
+        ]);
         let t = [1., 2., 3., 1.];
         let expected_value = [18., 24., 33., 1.];
         assert!(m.multiply_tuple(t).is_equal(expected_value));
@@ -258,18 +302,18 @@ mod tests {
 
     #[test]
     fn test_transpose() {
-        let m = [
+        let m = Matrix4::from([
             [0., 9., 3., 0.],
             [9., 8., 0., 8.],
             [1., 8., 5., 3.],
             [0., 0., 5., 8.]
-        ];
-        let expected_value = [
+        ]);
+        let expected_value = Matrix4::from([
             [0., 9., 1., 0.],
             [9., 8., 8., 0.],
             [3., 0., 5., 5.],
             [0., 8., 3., 8.]
-        ];
+        ]);
         assert!(m.transpose().is_equal(expected_value));
     }
 
@@ -298,12 +342,12 @@ mod tests {
 
     #[test]
     fn test_submatrix_4x4() {
-        let m = [
+        let m = Matrix4::from([
             [-6., 1., 1., 6.],
             [-8., 5., 8., 6.],
             [-1., 0., 8., 2.],
             [-7., 1., -1., 1.]
-        ];
+        ]);
         let expected_value = [
             [-6., 1., 6.],
             [-8., 8., 6.],
@@ -345,43 +389,43 @@ mod tests {
 
     #[test]
     fn test_determinant_4x4() {
-        let m = [
+        let m = Matrix4::from([
             [-2., -8., 3., 5.],
             [-3., 1., 7., 3.],
             [1., 2., -9., 6.],
             [-6., 7., 7., -9.]
-        ];
+        ]);
         assert!(float::is_equal(m.determinant(), -4071.));
     }
 
     #[test]
     fn test_inverse_4x4() {
-        let m = [
+        let m = Matrix4::from([
             [-5., 2., 6., -8.],
             [1., -5., 1., 8.],
             [7., 7., -6., -7.],
             [1., -3., 7., 4.]
-        ];
-        let expected_value = [
+        ]);
+        let expected_value = Matrix4::from([
             [0.21805, 0.45113, 0.24060, -0.04511],
             [-0.80827, -1.45677, -0.44361, 0.52068],
             [-0.07895, -0.22368, -0.05263, 0.19737],
             [-0.52256, -0.81391, -0.30075, 0.30639]
-        ];
+        ]);
         assert!(m.inverse().unwrap().is_equal(expected_value));
 
-        let a = [
+        let a = Matrix4::from([
             [3., -9., 7., 3.],
             [3., -8., 2., -9.],
             [-4., 4., 4., 1.],
             [-6., 5., -1., 1.]
-        ];
-        let b = [
+        ]);
+        let b = Matrix4::from([
             [8., 2., 2., 2.],
             [3., -1., 7., 0.],
             [7., 0., 5., 4.],
             [6., -2., 0., 5.]
-        ];
+        ]);
         let c = a.multiply_matrix(b);
         assert!(c.multiply_matrix(b.inverse().unwrap()).is_equal(a));
     }
@@ -0,0 +1,130 @@
+use crate::matrix;
+use crate::group::Group;
+use crate::material::Material;
+use crate::object::Object;
+use crate::triangle::{SmoothTriangle, Triangle};
+use crate::tuple::Tuple;
+
+// Builds a group of flat triangles from a list of vertices and faces, where
+// each face is a triple of indices into the vertex list.
+pub fn mesh_from_faces(vertices: &[Tuple], faces: &[[usize; 3]], material: Material) -> Object {
+    let triangles = faces
+        .iter()
+        .map(|face| {
+            Object::Triangle(
+                Triangle::new(
+                    vertices[face[0]],
+                    vertices[face[1]],
+                    vertices[face[2]],
+                    matrix::IDENTITY,
+                    material.clone(),
+                )
+            )
+        })
+        .collect();
+    Object::Group(Group::new(triangles))
+}
+
+// Same as above, except that each vertex also has a normal, which produces
+// smooth triangles instead.
+pub fn mesh_from_faces_normals(vertices: &[Tuple], normals: &[Tuple], faces: &[[usize; 3]], material: Material) -> Object {
+    let triangles = faces
+        .iter()
+        .map(|face| {
+            Object::SmoothTriangle(
+                SmoothTriangle::new(
+                    vertices[face[0]],
+                    vertices[face[1]],
+                    vertices[face[2]],
+                    normals[face[0]],
+                    normals[face[1]],
+                    normals[face[2]],
+                    matrix::IDENTITY,
+                    material.clone(),
+                )
+            )
+        })
+        .collect();
+    Object::Group(Group::new(triangles))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{float, material};
+    use crate::object::Object;
+    use crate::ray::Ray;
+    use crate::tuple::{Tuple, TupleMethods};
+    use super::*;
+
+    fn tetrahedron_vertices() -> Vec<Tuple> {
+        vec![
+            Tuple::point(0., 0., 0.),
+            Tuple::point(1., 0., 0.),
+            Tuple::point(0., 1., 0.),
+            Tuple::point(0., 0., 1.),
+        ]
+    }
+
+    const TETRAHEDRON_FACES: [[usize; 3]; 4] = [
+        [0, 2, 1],
+        [0, 1, 3],
+        [0, 3, 2],
+        [1, 2, 3],
+    ];
+
+    #[test]
+    fn test_mesh_from_faces_tetrahedron() {
+        let vertices = tetrahedron_vertices();
+        let center = Tuple::point(0.25, 0.25, 0.25);
+        let mesh = mesh_from_faces(&vertices, &TETRAHEDRON_FACES, material::DEFAULT_MATERIAL);
+        let children = match &mesh {
+            Object::Group(group) => &group.children,
+            _ => panic!("Expected a group"),
+        };
+        assert_eq!(children.len(), 4);
+
+        // Fire a ray at a point inside each face from outside of the
+        // tetrahedron, and make sure it hits that face first.
+        for (face, child) in TETRAHEDRON_FACES.iter().zip(children.iter()) {
+            let point = vertices[face[0]].multiply(0.5)
+                .add(vertices[face[1]].multiply(0.3))
+                .add(vertices[face[2]].multiply(0.2));
+            let mut normal = child.normal_at(point);
+            if normal.dot(point.subtract(center)) < 0. {
+                normal = normal.negate();
+            }
+            let origin = point.add(normal.multiply(2.));
+            let ray = Ray::new(origin, normal.negate());
+            let mut intersections = mesh.intersect(&ray);
+            intersections.sort_by(|i1, i2| i1.t.partial_cmp(&i2.t).unwrap());
+            assert_eq!(intersections.len(), 2);
+            assert_eq!(intersections[0].object_id, child.get_id());
+            assert!(float::is_equal(intersections[0].t, 2.));
+        }
+    }
+
+    #[test]
+    fn test_mesh_from_faces_normals_tetrahedron() {
+        let vertices = tetrahedron_vertices();
+        let center = Tuple::point(0.25, 0.25, 0.25);
+        let normals: Vec<Tuple> = vertices
+            .iter()
+            .map(|vertex| vertex.subtract(center).normalize())
+            .collect();
+        let mesh = mesh_from_faces_normals(&vertices, &normals, &TETRAHEDRON_FACES, material::DEFAULT_MATERIAL);
+        let ray = Ray::new(
+            Tuple::point(0.25, 0.25, -5.),
+            Tuple::vector(0., 0., 1.),
+        );
+        let intersections = mesh.intersect(&ray);
+        assert_eq!(intersections.len(), 2);
+        let children = match &mesh {
+            Object::Group(group) => &group.children,
+            _ => panic!("Expected a group"),
+        };
+        assert!(intersections.iter().all(|i| {
+            let child = children.iter().find(|child| child.get_id() == i.object_id).unwrap();
+            matches!(child, Object::SmoothTriangle(_))
+        }));
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+// A small, fast pseudorandom number generator, good enough for shuffling
+// permutation tables and jittering samples, and reproducible from a seed.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// This is Ken Perlin's "improved" gradient noise, with the permutation
+// table shuffled from a seed instead of being hardcoded. Only the seed is
+// written out when serialized, since the table can be shuffled again.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub struct PerlinNoise3D {
+    seed: u64,
+    perm: [u8; 512],
+}
+
+impl From<u64> for PerlinNoise3D {
+    fn from(seed: u64) -> PerlinNoise3D {
+        PerlinNoise3D::new(seed)
+    }
+}
+
+impl From<PerlinNoise3D> for u64 {
+    fn from(noise: PerlinNoise3D) -> u64 {
+        noise.seed
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Picks one of twelve gradient directions pointing to the edges of a cube
+// from the low four bits of the hash, and dots it with (x, y, z).
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+impl PerlinNoise3D {
+    pub fn new(seed: u64) -> PerlinNoise3D {
+        let mut permutation = [0u8; 256];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+
+        // Fisher-Yates shuffle
+        let mut state = seed;
+        for i in (1..256).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+
+        // The table is doubled up to avoid having to wrap indices.
+        let mut perm = [0u8; 512];
+        for i in 0..512 {
+            perm[i] = permutation[i & 255];
+        }
+        PerlinNoise3D { seed, perm }
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        self.perm[i] as usize
+    }
+
+    // Returns a smoothly varying value in approximately [-1, 1], which
+    // repeats every 256 units along each axis.
+    pub fn noise(&self, x: f64, y: f64, z: f64) -> f64 {
+        // Find the unit cube that contains the point...
+        let xi = (x.floor() as i64 & 255) as usize;
+        let yi = (y.floor() as i64 & 255) as usize;
+        let zi = (z.floor() as i64 & 255) as usize;
+
+        // ... and the relative position of the point within it.
+        let x = x - x.floor();
+        let y = y - y.floor();
+        let z = z - z.floor();
+
+        let u = fade(x);
+        let v = fade(y);
+        let w = fade(z);
+
+        // Hash the coordinates of the eight corners of the cube...
+        let a = self.hash(xi) + yi;
+        let aa = self.hash(a) + zi;
+        let ab = self.hash(a + 1) + zi;
+        let b = self.hash(xi + 1) + yi;
+        let ba = self.hash(b) + zi;
+        let bb = self.hash(b + 1) + zi;
+
+        // ... and blend the gradient contributions from each of them.
+        lerp(w,
+             lerp(v,
+                  lerp(u,
+                       grad(self.perm[aa], x, y, z),
+                       grad(self.perm[ba], x - 1., y, z)),
+                  lerp(u,
+                       grad(self.perm[ab], x, y - 1., z),
+                       grad(self.perm[bb], x - 1., y - 1., z))),
+             lerp(v,
+                  lerp(u,
+                       grad(self.perm[aa + 1], x, y, z - 1.),
+                       grad(self.perm[ba + 1], x - 1., y, z - 1.)),
+                  lerp(u,
+                       grad(self.perm[ab + 1], x, y - 1., z - 1.),
+                       grad(self.perm[bb + 1], x - 1., y - 1., z - 1.))))
+    }
+
+    // Sums successive octaves of noise, each at twice the frequency and
+    // `persistence` times the amplitude of the one before, normalized so that
+    // the result stays in approximately [-1, 1].
+    pub fn octave_noise(&self, x: f64, y: f64, z: f64, octaves: usize, persistence: f64) -> f64 {
+        let mut total = 0.;
+        let mut frequency = 1.;
+        let mut amplitude = 1.;
+        let mut max_value = 0.;
+        for _ in 0..octaves {
+            total += self.noise(x * frequency, y * frequency, z * frequency) * amplitude;
+            max_value += amplitude;
+            amplitude *= persistence;
+            frequency *= 2.;
+        }
+        if max_value == 0. {
+            0.
+        } else {
+            total / max_value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float;
+    use super::*;
+
+    fn sample_points() -> Vec<(f64, f64, f64)> {
+        let mut points = vec![];
+        for i in 0..20 {
+            for j in 0..20 {
+                points.push((i as f64 * 0.37 - 3., j as f64 * 0.61 + 0.2, (i * j) as f64 * 0.13));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_noise_is_in_range() {
+        let perlin = PerlinNoise3D::new(42);
+        for (x, y, z) in sample_points() {
+            let value = perlin.noise(x, y, z);
+            assert!((-1.05..=1.05).contains(&value));
+        }
+    }
+
+    #[test]
+    fn test_noise_is_zero_at_lattice_points() {
+        let perlin = PerlinNoise3D::new(42);
+        assert!(float::is_equal(perlin.noise(0., 0., 0.), 0.));
+        assert!(float::is_equal(perlin.noise(3., -7., 12.), 0.));
+    }
+
+    #[test]
+    fn test_noise_is_smooth() {
+        let perlin = PerlinNoise3D::new(7);
+        for (x, y, z) in sample_points() {
+            let difference = perlin.noise(x, y, z) - perlin.noise(x + 0.001, y, z + 0.001);
+            assert!(difference.abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_noise_repeats_every_256_units() {
+        let perlin = PerlinNoise3D::new(7);
+        for (x, y, z) in sample_points() {
+            let value = perlin.noise(x, y, z);
+            assert!(float::is_equal(value, perlin.noise(x + 256., y, z)));
+            assert!(float::is_equal(value, perlin.noise(x, y - 256., z)));
+            assert!(float::is_equal(value, perlin.noise(x, y, z + 512.)));
+        }
+    }
+
+    #[test]
+    fn test_noise_depends_on_seed() {
+        let perlin1 = PerlinNoise3D::new(1);
+        let perlin2 = PerlinNoise3D::new(1);
+        let perlin3 = PerlinNoise3D::new(2);
+        assert_eq!(perlin1.noise(0.5, 1.25, 2.75), perlin2.noise(0.5, 1.25, 2.75));
+        assert!(sample_points()
+            .iter()
+            .any(|&(x, y, z)| perlin1.noise(x, y, z) != perlin3.noise(x, y, z)));
+    }
+
+    #[test]
+    fn test_octave_noise_is_in_range() {
+        let perlin = PerlinNoise3D::new(3);
+        for (x, y, z) in sample_points() {
+            let value = perlin.octave_noise(x, y, z, 4, 0.5);
+            assert!((-1.05..=1.05).contains(&value));
+        }
+        assert!(float::is_equal(perlin.octave_noise(0.3, 0.4, 0.5, 1, 0.5), perlin.noise(0.3, 0.4, 0.5)));
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::matrix;
+use crate::color::Color;
+use crate::group::Group;
+use crate::material::Coloring::SolidColor;
+use crate::material::Material;
+use crate::object::Object;
+use crate::triangle::Triangle;
+use crate::tuple::{Tuple, TupleMethods};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "Unable to read OBJ file: {}", error),
+            ObjError::Parse(message) => write!(f, "Unable to parse OBJ file: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(error: io::Error) -> ObjError {
+        ObjError::Io(error)
+    }
+}
+
+fn parse_numbers<const N: usize>(line: &str, tokens: &[&str]) -> Result<[f64; N], ObjError> {
+    if tokens.len() < N {
+        return Err(ObjError::Parse(format!("expected {} numbers: {}", N, line.trim())));
+    }
+    let mut numbers = [0.; N];
+    for (number, token) in numbers.iter_mut().zip(tokens) {
+        *number = token
+            .parse()
+            .map_err(|_| ObjError::Parse(format!("invalid number: {}", token)))?;
+    }
+    Ok(numbers)
+}
+
+fn average(color: [f64; 3]) -> f64 {
+    (color[0] + color[1] + color[2]) / 3.
+}
+
+// Loads the materials in an MTL file by name. MTL colors don't map exactly
+// onto our materials, so the diffuse color becomes the surface color and
+// the ambient and specular colors are averaged down to their strengths.
+// Opacity becomes transparency; anything else in the file is ignored.
+pub fn load_mtl(path: &str) -> Result<HashMap<String, Material>, ObjError> {
+    let text = fs::read_to_string(path)?;
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, arguments)) = tokens.split_first() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            let name = arguments
+                .first()
+                .ok_or_else(|| ObjError::Parse("newmtl without a name".to_string()))?;
+            current = Some((name.to_string(), Material::new()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+        match keyword {
+            "Ka" => material.ambient = average(parse_numbers(line, arguments)?),
+            "Kd" => {
+                let [r, g, b] = parse_numbers(line, arguments)?;
+                material.color = SolidColor(Color::new(r, g, b));
+            },
+            "Ks" => material.specular = average(parse_numbers(line, arguments)?),
+            "Ns" => material.shininess = parse_numbers::<1>(line, arguments)?[0],
+            "d" => material.transparency = 1. - parse_numbers::<1>(line, arguments)?[0],
+            _ => (),
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+// Resolves a vertex reference in a face, which may be followed by texture
+// and normal indices, and which counts back from the end if negative.
+fn vertex_index(token: &str, vertex_count: usize) -> Result<usize, ObjError> {
+    let index: i64 = token
+        .split('/')
+        .next()
+        .unwrap_or("")
+        .parse()
+        .map_err(|_| ObjError::Parse(format!("invalid face vertex: {}", token)))?;
+    let resolved = if index < 0 { vertex_count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(ObjError::Parse(format!("face vertex out of range: {}", token)));
+    }
+    Ok(resolved as usize)
+}
+
+// Loads the faces of an OBJ file as a group of triangles, fanning out any
+// polygons with more than three sides. Faces under a `g` or `o` statement
+// go into a child group of that name, and faces before any of them go
+// straight into the root group. Materials come from the file's `mtllib`
+// libraries via `usemtl`, and faces without one get the given material.
+pub fn load_obj(path: &str, material: Material) -> Result<Object, ObjError> {
+    let text = fs::read_to_string(path)?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut library = HashMap::new();
+    let mut current_material = material;
+    let mut vertices = vec![];
+    let mut root = vec![];
+    let mut groups: Vec<(String, Vec<Object>)> = vec![];
+    let mut current_group: Option<usize> = None;
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, arguments)) = tokens.split_first() else {
+            continue;
+        };
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_numbers(line, arguments)?;
+                vertices.push(Tuple::point(x, y, z));
+            },
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(ObjError::Parse(format!("face with fewer than 3 vertices: {}", line.trim())));
+                }
+                let indices = arguments
+                    .iter()
+                    .map(|token| vertex_index(token, vertices.len()))
+                    .collect::<Result<Vec<usize>, ObjError>>()?;
+                let children = match current_group {
+                    Some(index) => &mut groups[index].1,
+                    None => &mut root,
+                };
+                for pair in indices[1..].windows(2) {
+                    children.push(Object::Triangle(Triangle::new(
+                        vertices[indices[0]],
+                        vertices[pair[0]],
+                        vertices[pair[1]],
+                        matrix::IDENTITY,
+                        current_material.clone(),
+                    )));
+                }
+            },
+            "g" | "o" => {
+                let name = arguments.join(" ");
+                current_group = match groups.iter().position(|(existing, _)| *existing == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        groups.push((name, vec![]));
+                        Some(groups.len() - 1)
+                    },
+                };
+            },
+            "mtllib" => {
+                for file_name in arguments {
+                    let mtl_path = directory.join(file_name);
+                    library.extend(load_mtl(&mtl_path.to_string_lossy())?);
+                }
+            },
+            "usemtl" => {
+                let name = arguments.join(" ");
+                current_material = library
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| ObjError::Parse(format!("unknown material: {}", name)))?;
+            },
+            _ => (),
+        }
+    }
+
+    for (_, children) in groups {
+        if !children.is_empty() {
+            root.push(Object::Group(Group::new(children)));
+        }
+    }
+    Ok(Object::Group(Group::new(root)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::{color, material};
+    use super::*;
+
+    fn children(object: &Object) -> &Vec<Object> {
+        match object {
+            Object::Group(group) => &group.children,
+            _ => panic!("Expected a group"),
+        }
+    }
+
+    fn colored(color: Color) -> Material {
+        Material {
+            color: SolidColor(color),
+            ..Material::new()
+        }
+    }
+
+    fn solid_color(material: &Material) -> Color {
+        match material.color {
+            SolidColor(color) => color,
+            _ => panic!("Expected a solid color"),
+        }
+    }
+
+    #[test]
+    fn test_load_mtl() -> Result<(), ObjError> {
+        let contents = "\
+# Two materials
+newmtl red
+Ka 0.1 0.2 0.3
+Kd 1 0 0
+Ks 0.3 0.6 0.9
+Ns 50
+
+newmtl glass
+Kd 0.9 0.9 1
+d 0.25
+";
+        let test_file_name = "test_materials.mtl";
+        fs::write(test_file_name, contents)?;
+        let materials = load_mtl(test_file_name);
+        fs::remove_file(test_file_name)?;
+        let materials = materials?;
+
+        assert_eq!(materials.len(), 2);
+        let red = &materials["red"];
+        assert_eq!(solid_color(red), Color::new(1., 0., 0.));
+        assert!((red.ambient - 0.2).abs() < 1e-9);
+        assert!((red.specular - 0.6).abs() < 1e-9);
+        assert_eq!(red.shininess, 50.);
+        assert_eq!(red.transparency, 0.);
+        let glass = &materials["glass"];
+        assert_eq!(solid_color(glass), Color::new(0.9, 0.9, 1.));
+        assert_eq!(glass.transparency, 0.75);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_obj_groups_with_materials() -> Result<(), ObjError> {
+        let mtl = "\
+newmtl red
+Kd 1 0 0
+newmtl blue
+Kd 0 0 1
+";
+        let obj = "\
+mtllib test_groups.mtl
+v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+f 1 2 3
+g first
+usemtl red
+f 1 2 3 4
+o second
+usemtl blue
+f 1 4 5
+";
+        fs::write("test_groups.mtl", mtl)?;
+        fs::write("test_groups.obj", obj)?;
+        let model = load_obj("test_groups.obj", colored(color::WHITE));
+        fs::remove_file("test_groups.mtl")?;
+        fs::remove_file("test_groups.obj")?;
+        let model = model?;
+
+        // The first face comes before any group, so it stays at the top.
+        let top = children(&model);
+        assert_eq!(top.len(), 3);
+        assert_eq!(solid_color(top[0].get_material()), color::WHITE);
+
+        let first = children(&top[1]);
+        assert_eq!(first.len(), 2);
+        for triangle in first {
+            assert_eq!(solid_color(triangle.get_material()), Color::new(1., 0., 0.));
+        }
+
+        let second = children(&top[2]);
+        assert_eq!(second.len(), 1);
+        assert_eq!(solid_color(second[0].get_material()), Color::new(0., 0., 1.));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_obj_unknown_material() -> Result<(), ObjError> {
+        let contents = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl missing
+f 1 2 3
+";
+        let test_file_name = "test_unknown_material.obj";
+        fs::write(test_file_name, contents)?;
+        let result = load_obj(test_file_name, material::DEFAULT_MATERIAL);
+        fs::remove_file(test_file_name)?;
+
+        assert!(matches!(result, Err(ObjError::Parse(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_obj_negative_indices() -> Result<(), ObjError> {
+        let contents = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3/1/1 -2/2/1 -1/3/1
+";
+        let test_file_name = "test_negative_indices.obj";
+        fs::write(test_file_name, contents)?;
+        let model = load_obj(test_file_name, material::DEFAULT_MATERIAL);
+        fs::remove_file(test_file_name)?;
+
+        match &children(&model?)[0] {
+            Object::Triangle(triangle) => {
+                assert!(triangle.p1.is_equal(Tuple::point(0., 0., 0.)));
+                assert!(triangle.p3.is_equal(Tuple::point(0., 1., 0.)));
+            },
+            _ => panic!("Expected a triangle"),
+        }
+        Ok(())
+    }
+}
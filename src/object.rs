@@ -1,31 +1,136 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::BoundingBox;
 use crate::shape::Shape;
-use crate::{cone, cube, cylinder, material, plane, ray, sphere, tuple};
-use crate::intersection::Intersection;
+use crate::{cone, cube, cylinder, group, instance, material, plane, ray, sphere, terrain, triangle, tuple, uv};
+use crate::intersection::{IntersectionBuffer, Intersections, MAX_SHAPE_INTERSECTIONS};
+#[cfg(feature = "arrayvec")]
+use crate::intersection::ShapeIntersections;
 use crate::matrix::{Matrix4, Matrix4Methods};
-use crate::tuple::TupleMethods;
+use crate::tuple::{Tuple, TupleMethods};
+
+// Every primitive shape has to fit its intersections in ShapeIntersections.
+const _: () = assert!(
+    sphere::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS &&
+        plane::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS &&
+        cube::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS &&
+        cylinder::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS &&
+        cone::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS &&
+        triangle::MAX_INTERSECTIONS <= MAX_SHAPE_INTERSECTIONS
+);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Hands out a unique ID for every newly constructed shape.
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Object {
     Sphere(sphere::Sphere),
     Plane(plane::Plane),
     Cube(cube::Cube),
     Cylinder(cylinder::Cylinder),
     Cone(cone::Cone),
+    Triangle(triangle::Triangle),
+    SmoothTriangle(triangle::SmoothTriangle),
+    Terrain(terrain::Terrain),
+    Group(group::Group),
+    // Shared geometry can't be written out, so scenes containing instances
+    // can't be saved.
+    #[serde(skip)]
+    Instanced(instance::Instanced),
+}
+
+// Clones get IDs of their own, as do the children of cloned groups, so that
+// a copy placed in the world isn't mistaken for the original.
+impl Clone for Object {
+    fn clone(&self) -> Object {
+        let mut object = match self {
+            Object::Sphere(sphere) => Object::Sphere(sphere.clone()),
+            Object::Plane(plane) => Object::Plane(plane.clone()),
+            Object::Cube(cube) => Object::Cube(cube.clone()),
+            Object::Cylinder(cylinder) => Object::Cylinder(cylinder.clone()),
+            Object::Cone(cone) => Object::Cone(cone.clone()),
+            Object::Triangle(triangle) => Object::Triangle(triangle.clone()),
+            Object::SmoothTriangle(triangle) => Object::SmoothTriangle(triangle.clone()),
+            Object::Terrain(terrain) => Object::Terrain(terrain.clone()),
+            Object::Group(group) => Object::Group(group.clone()),
+            Object::Instanced(instance) => Object::Instanced(instance.clone()),
+        };
+        let id = next_id();
+        match &mut object {
+            Object::Sphere(sphere) => sphere.id = id,
+            Object::Plane(plane) => plane.id = id,
+            Object::Cube(cube) => cube.id = id,
+            Object::Cylinder(cylinder) => cylinder.id = id,
+            Object::Cone(cone) => cone.id = id,
+            Object::Triangle(triangle) => triangle.id = id,
+            Object::SmoothTriangle(triangle) => triangle.id = id,
+            Object::Terrain(terrain) => terrain.id = id,
+            Object::Group(group) => group.id = id,
+            Object::Instanced(instance) => instance.id = id,
+        }
+        object
+    }
 }
 
 impl Object {
-    pub fn intersect(&self, world_ray: &ray::Ray) -> Vec<Intersection> {
+    pub fn intersect(&self, world_ray: &ray::Ray) -> Intersections {
+        let mut intersections = Intersections::new();
+        self.extend_intersections(world_ray, &mut intersections);
+        intersections
+    }
+
+    // Intersects a primitive shape without putting its intersections on the
+    // heap. Groups, terrains and instances can be hit any number of times,
+    // so there's nothing returned for them.
+    #[cfg(feature = "arrayvec")]
+    pub fn intersect_shape(&self, world_ray: &ray::Ray) -> Option<ShapeIntersections> {
+        if let Object::Terrain(_) | Object::Group(_) | Object::Instanced(_) = self {
+            return None;
+        }
+        let mut shape_intersections = ShapeIntersections::new();
+        self.intersect_local(world_ray, &mut shape_intersections);
+        Some(shape_intersections)
+    }
+
+    // Adds the object's intersections with the ray to the ones found so far.
+    pub fn extend_intersections(&self, world_ray: &ray::Ray, intersections: &mut Intersections) {
+        #[cfg(feature = "arrayvec")]
+        {
+            if let Some(shape_intersections) = self.intersect_shape(world_ray) {
+                intersections.extend(shape_intersections);
+                return;
+            }
+        }
+        if let Object::Group(group) = self {
+            return group.extend_intersections(world_ray, intersections);
+        }
+        self.intersect_local(world_ray, intersections);
+    }
+
+    // Intersects anything but a group with the ray in its own object space.
+    fn intersect_local(&self, world_ray: &ray::Ray, intersections: &mut dyn IntersectionBuffer) {
         let local_ray = world_ray.transform(self.get_inverse_transform());
-        let ts = match self {
-            Object::Sphere(sphere) => sphere.intersect(&local_ray),
-            Object::Plane(plane) => plane.intersect(&local_ray),
-            Object::Cube(cube) => cube.intersect(&local_ray),
-            Object::Cylinder(cylinder) => cylinder.intersect(&local_ray),
-            Object::Cone(cone) => cone.intersect(&local_ray),
-        };
-        ts.iter()
-            .map(|&t| Intersection::new(t, self))
-            .collect()
+        let id = self.get_id();
+        match self {
+            Object::Sphere(sphere) => sphere.intersect(&local_ray, id, intersections),
+            Object::Plane(plane) => plane.intersect(&local_ray, id, intersections),
+            Object::Cube(cube) => cube.intersect(&local_ray, id, intersections),
+            Object::Cylinder(cylinder) => cylinder.intersect(&local_ray, id, intersections),
+            Object::Cone(cone) => cone.intersect(&local_ray, id, intersections),
+            Object::Triangle(triangle) => triangle.intersect(&local_ray, id, intersections),
+            Object::SmoothTriangle(triangle) => triangle.intersect(&local_ray, id, intersections),
+            Object::Terrain(terrain) => terrain.intersect(&local_ray, id, intersections),
+            Object::Instanced(instance) => instance.intersect(&local_ray, id, intersections),
+            Object::Group(_) => unreachable!(),
+        }
     }
 
     pub fn normal_at(&self, world_point: tuple::Tuple) -> tuple::Tuple {
@@ -36,7 +141,17 @@ impl Object {
             Object::Cube(cube) => cube.normal_at(local_point),
             Object::Cylinder(cylinder) => cylinder.normal_at(local_point),
             Object::Cone(cone) => cone.normal_at(local_point),
+            Object::Triangle(triangle) => triangle.normal_at(local_point),
+            Object::SmoothTriangle(triangle) => triangle.normal_at(local_point),
+            Object::Terrain(terrain) => terrain.normal_at(local_point),
+            Object::Instanced(instance) => instance.normal_at(local_point),
+            Object::Group(_) => panic!("Groups have no surface of their own; only their children are ever hit"),
         };
+        self.world_normal_from_local(local_normal)
+    }
+
+    // Carries a normal from object space back into world space.
+    pub fn world_normal_from_local(&self, local_normal: tuple::Tuple) -> tuple::Tuple {
         let mut world_normal = self
             .get_inverse_transform()
             .transpose()
@@ -45,6 +160,143 @@ impl Object {
         world_normal.normalize()
     }
 
+    // Returns the texture coordinates of a point on the surface of the object.
+    pub fn uv_at(&self, world_point: tuple::Tuple) -> (f64, f64) {
+        let local_point = self.get_inverse_transform().multiply_tuple(world_point);
+        match self {
+            Object::Sphere(_) => uv::spherical_map(local_point),
+            Object::Plane(_) => uv::planar_map(local_point),
+            Object::Cube(_) => uv::cube_map(local_point),
+            Object::Cylinder(_) => uv::cylindrical_map(local_point),
+            Object::Cone(_) => uv::cylindrical_map(local_point),
+            Object::Triangle(_) => uv::planar_map(local_point),
+            Object::SmoothTriangle(_) => uv::planar_map(local_point),
+            // The height grid spans -1 to 1, so it is stretched over the
+            // unit square exactly once.
+            Object::Terrain(_) => ((local_point[0] + 1.) / 2., (local_point[2] + 1.) / 2.),
+            // Shared shapes don't say how they'd like to be mapped.
+            Object::Instanced(_) => uv::planar_map(local_point),
+            Object::Group(_) => panic!("Groups have no surface of their own; only their children are ever hit"),
+        }
+    }
+
+    // A point picked at random from the surface of the object, in world
+    // space. Groups pick one of their children at random first, without
+    // regard to how big each one is.
+    pub fn random_point(&self, rng: &mut dyn RngCore) -> Tuple {
+        let local_point = match self {
+            Object::Sphere(sphere) => sphere.random_point(rng),
+            Object::Plane(plane) => plane.random_point(rng),
+            Object::Cube(cube) => cube.random_point(rng),
+            Object::Cylinder(cylinder) => cylinder.random_point(rng),
+            Object::Cone(cone) => cone.random_point(rng),
+            Object::Triangle(triangle) => triangle.random_point(rng),
+            Object::SmoothTriangle(triangle) => triangle.random_point(rng),
+            Object::Terrain(terrain) => terrain.random_point(rng),
+            Object::Instanced(instance) => instance.random_point(rng),
+            // Children already have the group's transform baked into them.
+            Object::Group(group) => {
+                assert!(!group.children.is_empty(), "An empty group has no surface to pick a point from");
+                let child = &group.children[rng.random_range(0..group.children.len())];
+                return child.random_point(rng);
+            },
+        };
+        self.get_transform().multiply_tuple(local_point)
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        match self {
+            Object::Sphere(sphere) => sphere.is_bounded(),
+            Object::Plane(plane) => plane.is_bounded(),
+            Object::Cube(cube) => cube.is_bounded(),
+            Object::Cylinder(cylinder) => cylinder.is_bounded(),
+            Object::Cone(cone) => cone.is_bounded(),
+            Object::Triangle(triangle) => triangle.is_bounded(),
+            Object::SmoothTriangle(triangle) => triangle.is_bounded(),
+            Object::Terrain(terrain) => terrain.is_bounded(),
+            Object::Group(group) => group.children.iter().all(|child| child.is_bounded()),
+            Object::Instanced(instance) => instance.is_bounded(),
+        }
+    }
+
+    // Returns the axis-aligned box enclosing the object in world space.
+    pub fn bounds(&self) -> BoundingBox {
+        let local_bounds = match self {
+            Object::Sphere(sphere) => sphere.local_bounds(),
+            Object::Plane(plane) => plane.local_bounds(),
+            Object::Cube(cube) => cube.local_bounds(),
+            Object::Cylinder(cylinder) => cylinder.local_bounds(),
+            Object::Cone(cone) => cone.local_bounds(),
+            Object::Triangle(triangle) => triangle.local_bounds(),
+            Object::SmoothTriangle(triangle) => triangle.local_bounds(),
+            Object::Terrain(terrain) => terrain.local_bounds(),
+            Object::Instanced(instance) => instance.local_bounds(),
+            // Children already have the group's transform baked into them.
+            Object::Group(group) => return group.children
+                .iter()
+                .fold(BoundingBox::empty(), |bounds, child| bounds.union(&child.bounds())),
+        };
+        local_bounds.transform(self.get_transform())
+    }
+
+    pub fn get_transform(&self) -> Matrix4 {
+        match self {
+            Object::Sphere(sphere) => sphere.transform,
+            Object::Plane(plane) => plane.transform,
+            Object::Cube(cube) => cube.transform,
+            Object::Cylinder(cylinder) => cylinder.transform,
+            Object::Cone(cone) => cone.transform,
+            Object::Triangle(triangle) => triangle.transform,
+            Object::SmoothTriangle(triangle) => triangle.transform,
+            Object::Terrain(terrain) => terrain.transform,
+            Object::Instanced(instance) => instance.transform,
+            Object::Group(group) => group.transform,
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        let inverse_transform = transform.inverse().unwrap();
+        match self {
+            Object::Sphere(sphere) => {
+                sphere.transform = transform;
+                sphere.inverse_transform = inverse_transform;
+            }
+            Object::Plane(plane) => {
+                plane.transform = transform;
+                plane.inverse_transform = inverse_transform;
+            }
+            Object::Cube(cube) => {
+                cube.transform = transform;
+                cube.inverse_transform = inverse_transform;
+            }
+            Object::Cylinder(cylinder) => {
+                cylinder.transform = transform;
+                cylinder.inverse_transform = inverse_transform;
+            }
+            Object::Cone(cone) => {
+                cone.transform = transform;
+                cone.inverse_transform = inverse_transform;
+            }
+            Object::Triangle(triangle) => {
+                triangle.transform = transform;
+                triangle.inverse_transform = inverse_transform;
+            }
+            Object::SmoothTriangle(triangle) => {
+                triangle.transform = transform;
+                triangle.inverse_transform = inverse_transform;
+            }
+            Object::Terrain(terrain) => {
+                terrain.transform = transform;
+                terrain.inverse_transform = inverse_transform;
+            }
+            Object::Instanced(instance) => {
+                instance.transform = transform;
+                instance.inverse_transform = inverse_transform;
+            }
+            Object::Group(group) => group.set_transform(transform),
+        }
+    }
+
     pub fn get_inverse_transform(&self) -> Matrix4 {
         match self {
             Object::Sphere(sphere) => sphere.inverse_transform,
@@ -52,6 +304,11 @@ impl Object {
             Object::Cube(cube) => cube.inverse_transform,
             Object::Cylinder(cylinder) => cylinder.inverse_transform,
             Object::Cone(cone) => cone.inverse_transform,
+            Object::Triangle(triangle) => triangle.inverse_transform,
+            Object::SmoothTriangle(triangle) => triangle.inverse_transform,
+            Object::Terrain(terrain) => terrain.inverse_transform,
+            Object::Instanced(instance) => instance.inverse_transform,
+            Object::Group(group) => group.inverse_transform,
         }
     }
 
@@ -62,23 +319,271 @@ impl Object {
             Object::Cube(cube) => &cube.material,
             Object::Cylinder(cylinder) => &cylinder.material,
             Object::Cone(cone) => &cone.material,
+            Object::Triangle(triangle) => &triangle.material,
+            Object::SmoothTriangle(triangle) => &triangle.material,
+            Object::Terrain(terrain) => &terrain.material,
+            Object::Instanced(instance) => &instance.material,
+            Object::Group(group) => &group.material,
+        }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        match self {
+            Object::Sphere(sphere) => sphere.id,
+            Object::Plane(plane) => plane.id,
+            Object::Cube(cube) => cube.id,
+            Object::Cylinder(cylinder) => cylinder.id,
+            Object::Cone(cone) => cone.id,
+            Object::Triangle(triangle) => triangle.id,
+            Object::SmoothTriangle(triangle) => triangle.id,
+            Object::Terrain(terrain) => terrain.id,
+            Object::Instanced(instance) => instance.id,
+            Object::Group(group) => group.id,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        match self {
+            Object::Sphere(sphere) => sphere.visible,
+            Object::Plane(plane) => plane.visible,
+            Object::Cube(cube) => cube.visible,
+            Object::Cylinder(cylinder) => cylinder.visible,
+            Object::Cone(cone) => cone.visible,
+            Object::Triangle(triangle) => triangle.visible,
+            Object::SmoothTriangle(triangle) => triangle.visible,
+            Object::Terrain(terrain) => terrain.visible,
+            Object::Instanced(instance) => instance.visible,
+            Object::Group(group) => group.visible,
+        }
+    }
+
+    // Hidden objects are left out of the camera's view, and out of reflections
+    // and refractions, but still cast shadows. Only objects placed directly in
+    // the world are checked, so hide a whole group rather than its children.
+    pub fn set_visible(&mut self, visible: bool) {
+        match self {
+            Object::Sphere(sphere) => sphere.visible = visible,
+            Object::Plane(plane) => plane.visible = visible,
+            Object::Cube(cube) => cube.visible = visible,
+            Object::Cylinder(cylinder) => cylinder.visible = visible,
+            Object::Cone(cone) => cone.visible = visible,
+            Object::Triangle(triangle) => triangle.visible = visible,
+            Object::SmoothTriangle(triangle) => triangle.visible = visible,
+            Object::Terrain(terrain) => terrain.visible = visible,
+            Object::Instanced(instance) => instance.visible = visible,
+            Object::Group(group) => group.visible = visible,
         }
     }
 
-    // TODO: This is a hack; ideally we really need an object ID in each shape
     pub fn is_equal(&self, other: &Object) -> bool {
-        match (self, other) {
-            (Object::Sphere(s1), Object::Sphere(s2)) =>
-                s1.transform.is_equal(s2.transform),
-            (Object::Plane(p1), Object::Plane(p2)) =>
-                p1.transform.is_equal(p2.transform),
-            (Object::Cube(c1), Object::Cube(c2)) =>
-                c1.transform.is_equal(c2.transform),
-            (Object::Cylinder(c1), Object::Cylinder(c2)) =>
-                c1.transform.is_equal(c2.transform),
-            (Object::Cone(c1), Object::Cone(c2)) =>
-                c1.transform.is_equal(c2.transform),
-            _ => false,
+        self.get_id() == other.get_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::{float, material, matrix, transform};
+    use crate::cone::Cone;
+    use crate::cube::Cube;
+    use crate::cylinder::Cylinder;
+    use crate::group::Group;
+    use crate::instance::Instanced;
+    use crate::terrain::Terrain;
+    use crate::triangle::Triangle;
+    use crate::matrix::Matrix4Methods;
+    use crate::object::Object;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    #[test]
+    fn test_get_transform() {
+        let t = transform::translation(2., 3., 4.);
+        let object = Object::Sphere(Sphere::new(t, material::DEFAULT_MATERIAL));
+        assert!(object.get_transform().is_equal(t));
+    }
+
+    #[test]
+    fn test_set_transform_updates_inverse() {
+        let mut object = Object::Sphere(Sphere::new(transform::translation(2., 3., 4.), material::DEFAULT_MATERIAL));
+        let t = transform::scaling(2., 2., 2.);
+        object.set_transform(t);
+        assert!(object.get_transform().is_equal(t));
+        assert!(object.get_inverse_transform().is_equal(transform::scaling(0.5, 0.5, 0.5)));
+
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.),
+        );
+        let ts: Vec<f64> = object.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [3., 7.]);
+    }
+
+    #[test]
+    fn test_world_normal_from_local() {
+        let t = transform::scaling(1., 0.5, 1.).multiply_matrix(transform::rotation_z(PI/5.));
+        let sphere = Sphere::new(t, material::DEFAULT_MATERIAL);
+        let object = Object::Sphere(sphere.clone());
+        let world_point = Tuple::point(0., 2_f64.sqrt()/2., -2_f64.sqrt()/2.);
+        let local_point = object.get_inverse_transform().multiply_tuple(world_point);
+        let world_normal = object.world_normal_from_local(sphere.normal_at(local_point));
+        assert!(world_normal.is_equal(object.normal_at(world_point)));
+        assert!(world_normal.is_equal(Tuple::vector(0., 0.97014, -0.24254)));
+    }
+
+    #[test]
+    fn test_tangent_at_is_perpendicular_to_normal() {
+        let sphere = Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let cube = Cube::new(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let cylinder = Cylinder::new_capped(matrix::IDENTITY, material::DEFAULT_MATERIAL, 0., 2.);
+        let sphere_frame = |point: Tuple| (sphere.normal_at(point), sphere.tangent_at(point));
+        let cube_frame = |point: Tuple| (cube.normal_at(point), cube.tangent_at(point));
+        let cylinder_frame = |point: Tuple| (cylinder.normal_at(point), cylinder.tangent_at(point));
+        // The normal, tangent and bitangent at a point on a shape.
+        type Frame<'a> = &'a dyn Fn(Tuple) -> (Tuple, (Tuple, Tuple));
+        let test_cases: Vec<(Frame, Tuple)> = vec![
+            // Around the equator and at both poles of the sphere
+            (&sphere_frame, Tuple::point(1., 0., 0.)),
+            (&sphere_frame, Tuple::point(0., 1., 0.)),
+            (&sphere_frame, Tuple::point(0., -1., 0.)),
+            (&sphere_frame, Tuple::point(0.6, 0.48, 0.64)),
+            // One point on each face of the cube
+            (&cube_frame, Tuple::point(1., 0.5, -0.8)),
+            (&cube_frame, Tuple::point(-1., -0.2, 0.9)),
+            (&cube_frame, Tuple::point(-0.4, 1., -0.1)),
+            (&cube_frame, Tuple::point(0.3, -1., -0.7)),
+            (&cube_frame, Tuple::point(-0.6, 0.3, 1.)),
+            (&cube_frame, Tuple::point(0.4, 0.4, -1.)),
+            // On the walls and both caps of the cylinder
+            (&cylinder_frame, Tuple::point(0., 1.5, -1.)),
+            (&cylinder_frame, Tuple::point(-0.6, 1., 0.8)),
+            (&cylinder_frame, Tuple::point(0.3, 2., -0.2)),
+            (&cylinder_frame, Tuple::point(-0.4, 0., 0.5)),
+        ];
+
+        for (frame, point) in test_cases {
+            let (normal, (tangent, bitangent)) = frame(point);
+            let normal = normal.normalize();
+            assert!(float::is_equal(tangent.magnitude(), 1.));
+            assert!(float::is_equal(bitangent.magnitude(), 1.));
+            assert!(float::is_equal(tangent.dot(bitangent), 0.));
+            assert!(float::is_equal(tangent.dot(normal), 0.));
+            assert!(float::is_equal(bitangent.dot(normal), 0.));
+        }
+    }
+
+    #[cfg(feature = "arrayvec")]
+    #[test]
+    fn test_intersect_shape() {
+        let ray = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let sphere = Object::Sphere(Sphere::new(crate::matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let ts: Vec<f64> = sphere.intersect_shape(&ray).unwrap().iter().map(|i| i.t).collect();
+        assert_eq!(ts, [4., 6.]);
+
+        let cylinder = Object::Cylinder(Cylinder::new_capped(
+            transform::rotation_x(PI/2.),
+            material::DEFAULT_MATERIAL,
+            -1.,
+            1.,
+        ));
+        assert_eq!(cylinder.intersect_shape(&ray).unwrap().len(), 2);
+
+        let group = Object::Group(Group::new(vec![sphere.clone()]));
+        assert!(group.intersect_shape(&ray).is_none());
+    }
+
+    #[test]
+    fn test_random_point_in_group() {
+        let left = Object::Sphere(Sphere::new(transform::translation(-5., 0., 0.), material::DEFAULT_MATERIAL));
+        let right = Object::Cube(Cube::new(transform::translation(5., 0., 0.), material::DEFAULT_MATERIAL));
+        let mut group = Object::Group(Group::new(vec![left, right]));
+        group.set_transform(transform::translation(0., 10., 0.));
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = [0; 2];
+        for _ in 0..100 {
+            let point = group.random_point(&mut rng);
+            if point[0] < 0. {
+                let from_center = point.subtract(Tuple::point(-5., 10., 0.));
+                assert!(float::is_equal(from_center.magnitude(), 1.));
+                counts[0] += 1;
+            } else {
+                assert!((0..3).all(|axis| (point[axis] - [5., 10., 0.][axis]).abs() <= 1. + float::EPSILON));
+                counts[1] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&count| count > 20));
+    }
+
+    #[test]
+    fn test_is_bounded() {
+        let m = material::DEFAULT_MATERIAL;
+        let t = transform::scaling(2., 2., 2.);
+        let test_cases = vec![
+            (Object::Sphere(Sphere::new(t, m.clone())), true),
+            (Object::Cube(Cube::new(t, m.clone())), true),
+            (Object::Plane(Plane::new(t, m.clone())), false),
+            (Object::Cylinder(Cylinder::new_capped(t, m.clone(), -1., 1.)), true),
+            (Object::Cylinder(Cylinder::new_infinite(t, m.clone())), false),
+            (Object::Cone(Cone::new_capped(t, m.clone(), -1., 0.)), true),
+            (Object::Cone(Cone::new_infinite(t, m.clone())), false),
+            (Object::Triangle(Triangle::new(
+                Tuple::point(0., 1., 0.),
+                Tuple::point(-1., 0., 0.),
+                Tuple::point(1., 0., 0.),
+                t,
+                m.clone(),
+            )), true),
+            (Object::Terrain(Terrain::new(vec![0., 0.5, 0.5, 1.], 2, 2, t, m.clone())), true),
+            (Object::Group(Group::new(vec![
+                Object::Sphere(Sphere::new(t, m.clone())),
+                Object::Plane(Plane::new(t, m.clone())),
+            ])), false),
+            (Object::Instanced(Instanced::new(Arc::new(Sphere::new(t, m.clone())), t, m.clone())), true),
+            (Object::Instanced(Instanced::new(Arc::new(Plane::new(t, m.clone())), t, m.clone())), false),
+        ];
+
+        for (object, expected_value) in test_cases {
+            assert_eq!(object.is_bounded(), expected_value);
+            assert_eq!(object.bounds().volume().is_finite(), expected_value);
         }
     }
+
+    #[test]
+    fn test_bounds() {
+        let sphere = Object::Sphere(Sphere::new(
+            transform::translation(1., 2., 3.).multiply_matrix(transform::scaling(2., 2., 2.)),
+            material::DEFAULT_MATERIAL,
+        ));
+        let bounds = sphere.bounds();
+        assert!(bounds.min.is_equal(Tuple::point(-1., 0., 1.)));
+        assert!(bounds.max.is_equal(Tuple::point(3., 4., 5.)));
+
+        let plane = Object::Plane(Plane::new(transform::translation(0., -1., 0.), material::DEFAULT_MATERIAL));
+        assert_eq!(plane.bounds().volume(), f64::INFINITY);
+
+        let group = Object::Group(Group::new(vec![
+            sphere,
+            Object::Sphere(Sphere::new(transform::translation(-5., 0., 0.), material::DEFAULT_MATERIAL)),
+        ]));
+        let bounds = group.bounds();
+        assert!(bounds.min.is_equal(Tuple::point(-6., -1., -1.)));
+        assert!(bounds.max.is_equal(Tuple::point(3., 4., 5.)));
+
+        // Instances move the shared shape's own box, not the shared shape.
+        let instance = Object::Instanced(Instanced::new(
+            Arc::new(Cube::new(transform::translation(10., 0., 0.), material::DEFAULT_MATERIAL)),
+            transform::translation(0., 0., 5.),
+            material::DEFAULT_MATERIAL,
+        ));
+        let bounds = instance.bounds();
+        assert!(bounds.min.is_equal(Tuple::point(-1., -1., 4.)));
+        assert!(bounds.max.is_equal(Tuple::point(1., 1., 6.)));
+    }
 }
@@ -1,11 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{color, float, matrix, noise, transform};
 use crate::color::Color;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::object::Object;
-use crate::pattern::Pattern::{Checker3DPattern, Checker2DPattern, GradientPattern, RingPattern, Ring3DPattern,  StripedPattern, TestPattern};
+use crate::noise::PerlinNoise3D;
+use crate::pattern::Pattern::{Checker3DPattern, Checker2DPattern, GradientPattern, PlanetPattern, RingPattern, Ring3DPattern, SolidPattern, StripedPattern, TestPattern, UvDebugPattern};
 use crate::shape::Shape;
-use crate::tuple::Tuple;
+use crate::tuple::{Tuple, TupleMethods};
 
-#[derive(Clone)]
+// Patterns are nested inside Coloring::SurfacePattern, which already uses
+// the "type" tag, so they need a tag of their own.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "pattern")]
 pub enum Pattern {
     StripedPattern(Striped),
     GradientPattern(Gradient),
@@ -14,12 +21,76 @@ pub enum Pattern {
     Checker3DPattern(Checker3D),
     Checker2DPattern(Checker2D),
     TestPattern(Test),
+    UvDebugPattern(UvDebug),
+    SolidPattern(Solid),
+    PlanetPattern(Planet),
 }
 
 impl Pattern {
+    pub fn solid(color: Color) -> Pattern {
+        SolidPattern(Solid::new(color))
+    }
+
+    pub fn debug_uv() -> Pattern {
+        UvDebugPattern(UvDebug)
+    }
+
+    // A checkerboard fine enough to pass for noise at most viewing distances.
+    pub fn white_noise() -> Pattern {
+        Checker3DPattern(
+            Checker3D::new(color::WHITE, color::BLACK, transform::scaling(0.01, 0.01, 0.01))
+        )
+    }
+
     pub fn color_at(&self, object: &Object, world_point: Tuple) -> Color {
+        if let UvDebugPattern(_) = self {
+            let (u, v) = object.uv_at(world_point);
+            return self.color_at_uv(u, v);
+        }
+        if let (Object::Sphere(_), Some((tile_u, tile_v))) = (object, self.spherical_tiles()) {
+            let (u, v) = tile_spherical(object.uv_at(world_point), tile_u, tile_v);
+            return self.color_at_uv(u, v);
+        }
+
         let object_point = object.get_inverse_transform().multiply_tuple(world_point);
         let pattern_point = self.get_inverse_transform().multiply_tuple(object_point);
+        self.local_color_at(pattern_point)
+    }
+
+    // Averages the pattern over the parallelogram spanned by `du` and `dv`
+    // centered on the point, to smooth out patterns like checkerboards that
+    // alias badly when seen at a grazing angle. The jitter is seeded from
+    // the point itself so renders stay reproducible. A single sample is
+    // taken at the point itself, just like color_at.
+    pub fn sample_at(&self, object: &Object, world_point: Tuple, du: Tuple, dv: Tuple, samples: usize) -> Color {
+        if samples <= 1 {
+            return self.color_at(object, world_point);
+        }
+
+        let mut state = world_point
+            .iter()
+            .fold(0u64, |seed, coordinate| seed.rotate_left(21) ^ coordinate.to_bits());
+        let mut jitter = || (noise::splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64 - 0.5;
+
+        let mut total = color::BLACK;
+        for _ in 0..samples {
+            let sample_point = world_point
+                .add(du.multiply(jitter()))
+                .add(dv.multiply(jitter()));
+            total = total.add(self.color_at(object, sample_point));
+        }
+        total.multiply(1. / samples as f64)
+    }
+
+    // Evaluates the pattern at a pair of texture coordinates; patterns that
+    // are defined over space are painted onto the uv square as though it
+    // were the unit square of the xz plane.
+    pub fn color_at_uv(&self, u: f64, v: f64) -> Color {
+        let pattern_point = self.get_inverse_transform().multiply_tuple(Tuple::point(u, 0., v));
+        self.local_color_at(pattern_point)
+    }
+
+    fn local_color_at(&self, pattern_point: Tuple) -> Color {
         match self {
             StripedPattern(striped) => striped.color_at(pattern_point),
             GradientPattern(gradient) => gradient.color_at(pattern_point),
@@ -28,6 +99,19 @@ impl Pattern {
             Checker3DPattern(checker3d) => checker3d.color_at(pattern_point),
             Checker2DPattern(checker2d) => checker2d.color_at(pattern_point),
             TestPattern(test) => test.color_at(pattern_point),
+            UvDebugPattern(uv_debug) => uv_debug.color_at(pattern_point),
+            SolidPattern(solid) => solid.color_at(pattern_point),
+            PlanetPattern(planet) => planet.color_at(pattern_point),
+        }
+    }
+
+    // How many times stripes and checkers are tiled around and down a
+    // sphere, if they're painted on with texture coordinates.
+    fn spherical_tiles(&self) -> Option<(f64, f64)> {
+        match self {
+            StripedPattern(striped) => striped.spherical_tiles,
+            Checker2DPattern(checker2d) => checker2d.spherical_tiles,
+            _ => None,
         }
     }
 
@@ -40,16 +124,72 @@ impl Pattern {
             Checker3DPattern(checker3d) => checker3d.inverse_transform,
             Checker2DPattern(checker2d) => checker2d.inverse_transform,
             TestPattern(test) => test.inverse_transform,
+            PlanetPattern(planet) => planet.inverse_transform,
+            UvDebugPattern(_) | SolidPattern(_) => matrix::IDENTITY,
         }
     }
 }
 
-#[derive(Clone)]
+// Scales spherical texture coordinates so a pattern painted with them is
+// repeated tile_u times around the sphere and tile_v times from pole to
+// pole. Stripes and checkers alternate every tile, so unless tile_u is an
+// even number, the tiles either side of where u wraps from 1 back to 0
+// don't line up and leave a visible seam. Over the last stretch of u, a
+// quarter of a tile wide, the tiles are squeezed up to the next even
+// number, which meets up with u = 0 again.
+pub fn tile_spherical(uv: (f64, f64), tile_u: f64, tile_v: f64) -> (f64, f64) {
+    assert!(tile_u > 0. && tile_v > 0., "tile counts must be positive, not {} and {}", tile_u, tile_v);
+    let (u, v) = uv;
+    let fade_width = 1. / (tile_u * 4.);
+    let whole_tiles = (tile_u / 2.).ceil() * 2.;
+    // Adding the extra tiles in proportion to the square of the way through
+    // the fade leaves them the same width where it starts, and never runs
+    // backwards.
+    let fade = float::saturate((u - (1. - fade_width)) / fade_width);
+    (u * tile_u + (whole_tiles - tile_u) * fade * fade, v * tile_v)
+}
+
+// Either side of a two-toned pattern can be a plain color or another
+// pattern, which is positioned relative to the pattern it sits inside.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorOrPattern {
+    Color(Color),
+    Pattern(Box<Pattern>),
+}
+
+impl ColorOrPattern {
+    pub fn resolve(&self, pattern_point: Tuple) -> Color {
+        match self {
+            ColorOrPattern::Color(color) => *color,
+            ColorOrPattern::Pattern(pattern) => {
+                let sub_pattern_point = pattern.get_inverse_transform().multiply_tuple(pattern_point);
+                pattern.local_color_at(sub_pattern_point)
+            }
+        }
+    }
+}
+
+impl From<Color> for ColorOrPattern {
+    fn from(color: Color) -> Self {
+        ColorOrPattern::Color(color)
+    }
+}
+
+impl From<Pattern> for ColorOrPattern {
+    fn from(pattern: Pattern) -> Self {
+        ColorOrPattern::Pattern(Box::new(pattern))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Striped {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
+    #[serde(default)]
+    spherical_tiles: Option<(f64, f64)>,
 }
 
 pub trait PatternMethods {
@@ -57,66 +197,107 @@ pub trait PatternMethods {
 }
 
 impl Striped {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Striped {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Striped {
         Striped {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
+            spherical_tiles: None,
         }
     }
+
+    // Paints the stripes onto spheres by their texture coordinates instead,
+    // tiled with tile_spherical.
+    pub fn with_spherical_tiling(mut self, tile_u: f64, tile_v: f64) -> Self {
+        assert!(tile_u > 0. && tile_v > 0., "tile counts must be positive, not {} and {}", tile_u, tile_v);
+        self.spherical_tiles = Some((tile_u, tile_v));
+        self
+    }
+
+    pub fn new_solid(color: Color, other_color: Color, transform: Matrix4) -> Striped {
+        Striped::new(color, other_color, transform)
+    }
 }
 
 impl PatternMethods for Striped {
     fn color_at(&self, point: Tuple) -> Color {
         if point[0].floor() % 2. == 0. {
-            self.color
+            self.color.resolve(point)
         } else {
-            self.other_color
+            self.other_color.resolve(point)
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Gradient {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
+    #[serde(default)]
+    axis: GradientAxis,
+}
+
+// Which way a gradient runs. The linear axes repeat every unit, whereas the
+// radial ones fade out from the origin, reaching the other color at a
+// distance of one and staying there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GradientAxis {
+    #[default]
+    X,
+    Y,
+    Z,
+    Radial2D,
+    Radial3D,
 }
 
 impl Gradient {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Gradient {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Gradient {
         Gradient {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
+            axis: GradientAxis::default(),
         }
     }
+
+    pub fn with_axis(mut self, axis: GradientAxis) -> Self {
+        self.axis = axis;
+        self
+    }
 }
 
 impl PatternMethods for Gradient {
     fn color_at(&self, point: Tuple) -> Color {
-        let distance = self.other_color.subtract(self.color);
-        let fraction = point[0] - point[0].floor();
-        return self.color.add(distance.multiply(fraction));
+        let color = self.color.resolve(point);
+        let distance = self.other_color.resolve(point).subtract(color);
+        let fraction = match self.axis {
+            GradientAxis::X => point[0] - point[0].floor(),
+            GradientAxis::Y => point[1] - point[1].floor(),
+            GradientAxis::Z => point[2] - point[2].floor(),
+            GradientAxis::Radial2D => float::saturate((point[0]*point[0] + point[2]*point[2]).sqrt()),
+            GradientAxis::Radial3D => float::saturate((point[0]*point[0] + point[1]*point[1] + point[2]*point[2]).sqrt()),
+        };
+        return color.add(distance.multiply(fraction));
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ring {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
 }
 
 impl Ring {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Ring {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Ring {
         Ring {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
         }
@@ -126,26 +307,26 @@ impl Ring {
 impl PatternMethods for Ring {
     fn color_at(&self, point: Tuple) -> Color {
         if (point[0]*point[0] + point[2]*point[2]).sqrt().floor()%2.0 == 0.0 {
-            self.color
+            self.color.resolve(point)
         } else {
-            self.other_color
+            self.other_color.resolve(point)
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ring3D {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
 }
 
 impl Ring3D {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Ring3D {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Ring3D {
         Ring3D {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
         }
@@ -155,26 +336,26 @@ impl Ring3D {
 impl PatternMethods for Ring3D {
     fn color_at(&self, point: Tuple) -> Color {
         if (point[0]*point[0] + point[1]*point[1] + point[2]*point[2]).sqrt().floor()%2.0 == 0.0 {
-            self.color
+            self.color.resolve(point)
         } else {
-            self.other_color
+            self.other_color.resolve(point)
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Checker3D {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
 }
 
 impl Checker3D {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Checker3D {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Checker3D {
         Checker3D {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
         }
@@ -184,43 +365,54 @@ impl Checker3D {
 impl PatternMethods for Checker3D {
     fn color_at(&self, point: Tuple) -> Color {
         if (point[0].floor() + point[1].floor() + point[2].floor())%2.0 == 0.0 {
-            self.color
+            self.color.resolve(point)
         } else {
-            self.other_color
+            self.other_color.resolve(point)
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Checker2D {
-    color: Color,
-    other_color: Color,
+    color: ColorOrPattern,
+    other_color: ColorOrPattern,
     transform: Matrix4,
     inverse_transform: Matrix4,
+    #[serde(default)]
+    spherical_tiles: Option<(f64, f64)>,
 }
 
 impl Checker2D {
-    pub fn new(color: Color, other_color: Color, transform: Matrix4) -> Checker2D {
+    pub fn new(color: impl Into<ColorOrPattern>, other_color: impl Into<ColorOrPattern>, transform: Matrix4) -> Checker2D {
         Checker2D {
-            color: color,
-            other_color: other_color,
+            color: color.into(),
+            other_color: other_color.into(),
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
+            spherical_tiles: None,
         }
     }
+
+    // Paints the checkers onto spheres by their texture coordinates instead,
+    // tiled with tile_spherical.
+    pub fn with_spherical_tiling(mut self, tile_u: f64, tile_v: f64) -> Self {
+        assert!(tile_u > 0. && tile_v > 0., "tile counts must be positive, not {} and {}", tile_u, tile_v);
+        self.spherical_tiles = Some((tile_u, tile_v));
+        self
+    }
 }
 
 impl PatternMethods for Checker2D {
     fn color_at(&self, point: Tuple) -> Color {
         if (point[0].floor() + point[2].floor())%2.0 == 0.0 {
-            self.color
+            self.color.resolve(point)
         } else {
-            self.other_color
+            self.other_color.resolve(point)
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Test {
     transform: Matrix4,
     inverse_transform: Matrix4,
@@ -242,10 +434,93 @@ impl PatternMethods for Test {
 }
 
 
+// Shows the texture coordinates of a surface directly, with u in the red
+// channel and v in the green, which makes seams in a uv mapping easy to spot.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UvDebug;
+
+impl PatternMethods for UvDebug {
+    fn color_at(&self, point: Tuple) -> Color {
+        Color::new(point[0], point[2], 0.)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Solid {
+    color: Color,
+}
+
+impl Solid {
+    pub fn new(color: Color) -> Solid {
+        Solid {
+            color,
+        }
+    }
+}
+
+impl PatternMethods for Solid {
+    fn color_at(&self, _point: Tuple) -> Color {
+        self.color
+    }
+}
+
+// Terrain for a whole world, painted from a height map of several octaves
+// of noise: deep and then shallow water below sea level, then sand, grass,
+// bare rock and finally snow on the highest ground. The noise itself only
+// varies by about a unit, so scale the pattern down for smaller continents.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Planet {
+    sea_level: f64,
+    #[serde(rename = "seed")]
+    noise: Box<PerlinNoise3D>,
+    transform: Matrix4,
+    inverse_transform: Matrix4,
+}
+
+const PLANET_OCTAVES: usize = 6;
+const PLANET_PERSISTENCE: f64 = 0.5;
+
+impl Planet {
+    pub fn new(sea_level: f64, seed: u64, transform: Matrix4) -> Planet {
+        Planet {
+            sea_level,
+            noise: Box::new(PerlinNoise3D::new(seed)),
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+        }
+    }
+
+    pub fn height_at(&self, point: Tuple) -> f64 {
+        self.noise.octave_noise(point[0], point[1], point[2], PLANET_OCTAVES, PLANET_PERSISTENCE)
+    }
+}
+
+impl PatternMethods for Planet {
+    fn color_at(&self, point: Tuple) -> Color {
+        let height = self.height_at(point) - self.sea_level;
+        if height < -0.1 {
+            Color::new(0.02, 0.1, 0.4)
+        } else if height < 0. {
+            Color::new(0.1, 0.4, 0.7)
+        } else if height < 0.1 {
+            Color::new(0.85, 0.8, 0.55)
+        } else if height < 0.3 {
+            Color::new(0.2, 0.5, 0.15)
+        } else if height < 0.6 {
+            Color::new(0.5, 0.5, 0.5)
+        } else {
+            color::WHITE
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{color, matrix, transform};
+    use crate::{color, material, matrix, transform};
     use crate::material::Coloring::SurfacePattern;
+    use std::f64::consts::PI;
+
+    use crate::cube::Cube;
     use crate::material::Material;
     use crate::sphere::Sphere;
     use crate::object::Object;
@@ -310,6 +585,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let transform = transform::scaling(2., 2., 2.);
         let object = Object::Sphere(
@@ -338,6 +614,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let object = Object::Sphere(
             Sphere::new(matrix::IDENTITY, material)
@@ -367,6 +644,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let object = Object::Sphere(
             Sphere::new(object_transform, material)
@@ -387,6 +665,119 @@ mod tests {
         assert_eq!(pattern.color_at(Tuple::point(0.75, 0., 0.)), Color::new(0.25, 0.25, 0.25));
     }
 
+    #[test]
+    fn test_local_color_at_gradient_axes() {
+        let test_cases = vec![
+            (GradientAxis::X, Tuple::point(0.25, 0.5, 0.75), Color::new(0.75, 0.75, 0.75)),
+            (GradientAxis::Y, Tuple::point(0.25, 0.5, 0.75), Color::new(0.5, 0.5, 0.5)),
+            (GradientAxis::Z, Tuple::point(0.25, 0.5, 0.75), Color::new(0.25, 0.25, 0.25)),
+            (GradientAxis::Radial2D, Tuple::point(0.3, 5., 0.4), Color::new(0.5, 0.5, 0.5)),
+            (GradientAxis::Radial2D, Tuple::point(3., 0., 4.), color::BLACK),
+            (GradientAxis::Radial3D, Tuple::point(0., 0.6, 0.), Color::new(0.4, 0.4, 0.4)),
+            (GradientAxis::Radial3D, Tuple::point(0., 5., 0.), color::BLACK),
+        ];
+
+        for (axis, point, expected_color) in test_cases {
+            let pattern = Gradient::new(color::WHITE, color::BLACK, matrix::IDENTITY).with_axis(axis);
+            assert_eq!(pattern.color_at(point), expected_color);
+        }
+    }
+
+    #[test]
+    fn test_color_at_radial_3d_gradient_on_sphere() {
+        let pattern = GradientPattern(
+            Gradient::new(color::WHITE, color::BLACK, matrix::IDENTITY).with_axis(GradientAxis::Radial3D)
+        );
+        let object = Object::Sphere(
+            Sphere::new(transform::translation(2., 0., 0.), material::DEFAULT_MATERIAL)
+        );
+        assert_eq!(pattern.color_at(&object, Tuple::point(2., 0., 0.)), color::WHITE);
+        assert_eq!(pattern.color_at(&object, Tuple::point(2., 1., 0.)), color::BLACK);
+        assert_eq!(pattern.color_at(&object, Tuple::point(2., 0., -0.999)), Color::new(0.001, 0.001, 0.001));
+    }
+
+    #[test]
+    fn test_sample_at_single_sample_matches_color_at() {
+        let pattern = Checker3DPattern(Checker3D::new(color::WHITE, color::BLACK, matrix::IDENTITY));
+        let object = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let du = Tuple::vector(1., 0., 0.);
+        let dv = Tuple::vector(0., 0., 1.);
+        for &point in [Tuple::point(0.5, 0.5, 0.5), Tuple::point(1.5, 0.5, 0.5)].iter() {
+            assert_eq!(pattern.sample_at(&object, point, du, dv, 1), pattern.color_at(&object, point));
+        }
+    }
+
+    #[test]
+    fn test_sample_at_averages_across_checker_edge() {
+        let pattern = Checker3DPattern(Checker3D::new(color::WHITE, color::BLACK, matrix::IDENTITY));
+        let object = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let point = Tuple::point(0.999, 0.5, 0.5);
+        let du = Tuple::vector(1., 0., 0.);
+        let dv = Tuple::vector(0., 0., 0.1);
+
+        let sampled = pattern.sample_at(&object, point, du, dv, 400);
+        assert!(sampled.r > 0.4 && sampled.r < 0.6);
+        assert_eq!(sampled, pattern.sample_at(&object, point, du, dv, 400));
+
+        // Samples that stay inside a single square all agree.
+        let small_du = Tuple::vector(0.01, 0., 0.);
+        let inside = Tuple::point(0.5, 0.5, 0.5);
+        assert_eq!(pattern.sample_at(&object, inside, small_du, dv, 16), color::WHITE);
+    }
+
+    #[test]
+    fn test_checker_of_stripes() {
+        let stripes = StripedPattern(Striped::new(
+            color::WHITE,
+            color::BLACK,
+            transform::scaling(0.25, 0.25, 0.25),
+        ));
+        let pattern = Checker2D::new(stripes, Color::new(1., 0., 0.), matrix::IDENTITY);
+        assert_eq!(pattern.color_at(Tuple::point(0.1, 0., 0.1)), color::WHITE);
+        assert_eq!(pattern.color_at(Tuple::point(0.3, 0., 0.1)), color::BLACK);
+        assert_eq!(pattern.color_at(Tuple::point(1.1, 0., 0.1)), Color::new(1., 0., 0.));
+        assert_eq!(pattern.color_at(Tuple::point(1.1, 0., 1.1)), color::WHITE);
+    }
+
+    #[test]
+    fn test_stripes_of_patterns() {
+        let checkers = Checker3DPattern(Checker3D::new(
+            Color::new(1., 0., 0.),
+            Color::new(0., 0., 1.),
+            transform::scaling(0.5, 0.5, 0.5),
+        ));
+        let rings = RingPattern(Ring::new(color::WHITE, color::BLACK, transform::scaling(0.1, 0.1, 0.1)));
+        let pattern = Striped::new(checkers, rings, matrix::IDENTITY);
+        assert_eq!(pattern.color_at(Tuple::point(0.25, 0., 0.)), Color::new(1., 0., 0.));
+        assert_eq!(pattern.color_at(Tuple::point(0.75, 0., 0.)), Color::new(0., 0., 1.));
+        assert_eq!(pattern.color_at(Tuple::point(1.05, 0., 0.)), color::WHITE);
+        assert_eq!(pattern.color_at(Tuple::point(1.15, 0., 0.)), color::BLACK);
+    }
+
+    #[test]
+    fn test_striped_new_solid() {
+        let pattern = Striped::new_solid(color::WHITE, color::BLACK, matrix::IDENTITY);
+        assert_eq!(pattern.color_at(Tuple::point(0.5, 0., 0.)), color::WHITE);
+        assert_eq!(pattern.color_at(Tuple::point(1.5, 0., 0.)), color::BLACK);
+    }
+
+    #[test]
+    fn test_gradient_between_patterns() {
+        let stripes = StripedPattern(Striped::new(color::WHITE, color::BLACK, transform::scaling(0.5, 1., 1.)));
+        let pattern = Gradient::new(stripes, Color::new(0.5, 0., 0.), matrix::IDENTITY);
+        assert_eq!(pattern.color_at(Tuple::point(0.25, 0., 0.)), Color::new(0.875, 0.75, 0.75));
+        assert_eq!(pattern.color_at(Tuple::point(0.75, 0., 0.)), Color::new(0.375, 0., 0.));
+    }
+
+    #[test]
+    fn test_rings_of_gradients() {
+        let gradient = GradientPattern(Gradient::new(color::WHITE, color::BLACK, matrix::IDENTITY));
+        let pattern = RingPattern(Ring::new(gradient, color::BLACK, transform::translation(5., 0., 0.)));
+        let object = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        assert_eq!(pattern.color_at(&object, Tuple::point(5.25, 0., 0.)), Color::new(0.75, 0.75, 0.75));
+        assert_eq!(pattern.color_at(&object, Tuple::point(6.5, 0., 0.)), color::BLACK);
+    }
+
     #[test]
     fn test_local_color_at_ring() {
         let pattern = Ring::new(
@@ -435,4 +826,175 @@ mod tests {
         assert_eq!(pattern.color_at(Tuple::point(0., 0., 0.99)), color::WHITE);
         assert_eq!(pattern.color_at(Tuple::point(0., 0., 1.01)), color::BLACK);
     }
+
+    #[test]
+    fn test_color_at_uv_debug() {
+        let pattern = UvDebugPattern(UvDebug);
+        assert_eq!(pattern.color_at_uv(0.25, 0.75), Color::new(0.25, 0.75, 0.));
+        let json = serde_json::to_string(&pattern).unwrap();
+        assert!(matches!(serde_json::from_str(&json).unwrap(), UvDebugPattern(_)));
+    }
+
+    #[test]
+    fn test_color_at_uv_debug_on_sphere() {
+        let pattern = UvDebugPattern(UvDebug);
+        let sphere = Object::Sphere(
+            Sphere::new(transform::scaling(2., 2., 2.), Material::new())
+        );
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(2., 0., 0.)), Color::new(0.25, 0.5, 0.));
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(0., 2., 0.)), Color::new(0.5, 1., 0.));
+    }
+
+    #[test]
+    fn test_color_at_uv_for_spatial_pattern() {
+        let pattern = StripedPattern(
+            Striped::new(color::WHITE, color::BLACK, transform::scaling(0.5, 1., 1.))
+        );
+        assert_eq!(pattern.color_at_uv(0.25, 0.5), color::WHITE);
+        assert_eq!(pattern.color_at_uv(0.75, 0.5), color::BLACK);
+    }
+
+    #[test]
+    fn test_solid() {
+        let pattern = Pattern::solid(color::GREY);
+        let sphere = Object::Sphere(
+            Sphere::new(transform::scaling(2., 2., 2.), Material::new())
+        );
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(2., 0., 0.)), color::GREY);
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(0., -2., 0.)), color::GREY);
+        assert_eq!(pattern.color_at_uv(0.3, 0.7), color::GREY);
+    }
+
+    #[test]
+    fn test_debug_uv() {
+        assert_eq!(Pattern::debug_uv().color_at_uv(0.5, 0.25), Color::new(0.5, 0.25, 0.));
+    }
+
+    #[test]
+    fn test_white_noise_varies_over_short_distances() {
+        let pattern = Pattern::white_noise();
+        let sphere = Object::Sphere(
+            Sphere::new(matrix::IDENTITY, Material::new())
+        );
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(0.005, 0., 0.)), color::WHITE);
+        assert_eq!(pattern.color_at(&sphere, Tuple::point(0.015, 0., 0.)), color::BLACK);
+    }
+
+    #[test]
+    fn test_planet_equator_has_several_terrains() {
+        let pattern = PlanetPattern(Planet::new(0., 42, transform::scaling(0.25, 0.25, 0.25)));
+        let sphere = Object::Sphere(
+            Sphere::new(matrix::IDENTITY, Material::new())
+        );
+        let mut terrains: Vec<Color> = vec![];
+        for i in 0..360 {
+            let angle = (i as f64).to_radians();
+            let color = pattern.color_at(&sphere, Tuple::point(angle.cos(), 0., angle.sin()));
+            if !terrains.contains(&color) {
+                terrains.push(color);
+            }
+        }
+        assert!(terrains.len() >= 3);
+    }
+
+    #[test]
+    fn test_planet_sea_level() {
+        let point = Tuple::point(0.3, 0.2, 0.7);
+        let height = Planet::new(0., 7, matrix::IDENTITY).height_at(point);
+        let deep = Planet::new(height + 0.2, 7, matrix::IDENTITY);
+        let snowy = Planet::new(height - 1., 7, matrix::IDENTITY);
+        assert_eq!(deep.color_at(point), Color::new(0.02, 0.1, 0.4));
+        assert_eq!(snowy.color_at(point), color::WHITE);
+    }
+
+    #[test]
+    fn test_planet_serializes_its_seed() {
+        let planet = Planet::new(0.1, 42, matrix::IDENTITY);
+        let json = serde_json::to_string(&planet).unwrap();
+        assert!(json.contains("\"seed\":42"));
+        let restored: Planet = serde_json::from_str(&json).unwrap();
+        let point = Tuple::point(1.3, -0.4, 2.2);
+        assert_eq!(restored.height_at(point), planet.height_at(point));
+    }
+
+    #[test]
+    fn test_tile_spherical() {
+        let test_cases = vec![
+            ((0., 0.), 4., 2., (0., 0.)),
+            ((0.5, 0.25), 4., 2., (2., 0.5)),
+            ((1., 1.), 4., 2., (4., 2.)),
+            // Well away from the seam, fractional tiles are left alone...
+            ((0.5, 0.5), 3.7, 1., (1.85, 0.5)),
+            ((0.9, 0.5), 3.7, 1., (3.33, 0.5)),
+            // ... but the seam lands on an even number of tiles.
+            ((1., 0.5), 3.7, 1., (4., 0.5)),
+            ((1., 0.5), 4.3, 1., (6., 0.5)),
+            ((1., 0.5), 3., 1., (4., 0.5)),
+        ];
+        for (uv, tile_u, tile_v, (expected_u, expected_v)) in test_cases {
+            let (u, v) = tile_spherical(uv, tile_u, tile_v);
+            assert!(float::is_equal(u, expected_u));
+            assert!(float::is_equal(v, expected_v));
+        }
+    }
+
+    #[test]
+    fn test_tile_spherical_stripes_meet_at_seam() {
+        let pattern = StripedPattern(Striped::new(color::WHITE, color::BLACK, matrix::IDENTITY));
+        let color_at = |u: f64| {
+            let (s, t) = tile_spherical((u, 0.5), 4.3, 1.);
+            pattern.color_at_uv(s, t)
+        };
+        // Scaling u by 4.3 alone would leave the sliver of a fifth stripe
+        // before the seam, the same color as the first stripe after it.
+        // Squeezing in six whole stripes keeps them alternating instead.
+        assert_eq!(color_at(0.001), color::WHITE);
+        assert_eq!(color_at(0.999), color::BLACK);
+    }
+
+    #[test]
+    fn test_tile_spherical_never_runs_backwards() {
+        for tile_u in [0.5, 2.2, 3.7, 4., 4.3, 9.9] {
+            let mut previous = 0.;
+            for step in 1..=10000 {
+                let (u, _) = tile_spherical((step as f64 / 10000., 0.5), tile_u, 1.);
+                assert!(u >= previous);
+                previous = u;
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "tile counts must be positive")]
+    fn test_tile_spherical_rejects_no_tiles() {
+        tile_spherical((0.5, 0.5), 0., 1.);
+    }
+
+    #[test]
+    fn test_spherical_tiling_on_spheres() {
+        let stripes = StripedPattern(
+            Striped::new(color::WHITE, color::BLACK, matrix::IDENTITY).with_spherical_tiling(4., 1.)
+        );
+        let sphere = Object::Sphere(Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        // Around the equator, u runs from 0 at -z through 0.25 at +x; four
+        // tiles put a stripe boundary every sixteenth of a turn.
+        let point_at = |u: f64, y: f64| {
+            let theta = 2. * PI * (0.5 - u);
+            let radius = (1. - y * y).sqrt();
+            Tuple::point(radius * theta.sin(), y, radius * theta.cos())
+        };
+        assert_eq!(stripes.color_at(&sphere, point_at(0.1, 0.)), color::WHITE);
+        assert_eq!(stripes.color_at(&sphere, point_at(0.3, 0.)), color::BLACK);
+
+        let checkers = Checker2DPattern(
+            Checker2D::new(color::WHITE, color::BLACK, matrix::IDENTITY).with_spherical_tiling(4., 2.)
+        );
+        assert_eq!(checkers.color_at(&sphere, point_at(0.1, 0.5)), color::BLACK);
+        assert_eq!(checkers.color_at(&sphere, point_at(0.1, -0.5)), color::WHITE);
+
+        // Anything other than a sphere keeps the pattern in space.
+        let cube = Object::Cube(Cube::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let point = Tuple::point(0.5, 0., -1.);
+        assert_eq!(stripes.color_at(&cube, point), color::WHITE);
+    }
 }
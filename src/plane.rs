@@ -1,15 +1,38 @@
-use crate::{material, matrix, ray, tuple};
+use std::f64::consts::PI;
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{material, matrix, object, ray, transform, tuple};
 use crate::float::EPSILON;
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
 use crate::material::Material;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::shape::Shape;
 use crate::tuple::TupleMethods;
 
-#[derive(Clone)]
+// A ray can cross a plane at most once.
+pub const MAX_INTERSECTIONS: usize = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Plane {
     pub transform: matrix::Matrix4,
     pub inverse_transform: matrix::Matrix4,
     pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    // Makes the plane face down instead of up, for ceilings and the like.
+    #[serde(default)]
+    pub flip_normal: bool,
 }
 
 impl Plane {
@@ -18,30 +41,77 @@ impl Plane {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
+            flip_normal: false,
+        }
+    }
+
+    // A plane facing down, for lighting a room from below the ceiling.
+    pub fn ceiling(transform: Matrix4, material: Material) -> Plane {
+        Plane {
+            flip_normal: true,
+            ..Plane::new(transform, material)
         }
     }
+
+    // A plane whose normal points along the given axis, so that walls don't
+    // need to be rotated into place by hand. The rotation is applied before
+    // the transform passed in.
+    pub fn vertical(axis: Axis, transform: Matrix4, material: Material) -> Plane {
+        let orientation = match axis {
+            Axis::X => transform::rotation_z(-PI/2.),
+            Axis::Y => matrix::IDENTITY,
+            Axis::Z => transform::rotation_x(PI/2.),
+        };
+        Plane::new(transform.multiply_matrix(orientation), material)
+    }
 }
 
 impl Shape for Plane {
-    fn intersect(&self, local_ray: &ray::Ray) -> Vec<f64> {
-        if local_ray.direction[1].abs() < EPSILON {
-            vec![]
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        // A ray parallel to the plane never meets it.
+        if local_ray.direction[1].abs() >= EPSILON {
+            intersections.push(Intersection::new(-local_ray.origin[1] / local_ray.direction[1], object_id));
+        }
+    }
+
+    fn normal_at(&self, _local_point: tuple::Tuple) -> tuple::Tuple {
+        if self.flip_normal {
+            tuple::Tuple::vector(0., -1., 0.)
         } else {
-            vec![-local_ray.origin[1] / local_ray.direction[1]]
+            tuple::Tuple::vector(0., 1., 0.)
         }
     }
 
-    fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
-        tuple::Tuple::vector(0., 1., 0.)
+    fn is_bounded(&self) -> bool {
+        false
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            tuple::Tuple::point(-f64::INFINITY, 0., -f64::INFINITY),
+            tuple::Tuple::point(f64::INFINITY, 0., f64::INFINITY),
+        )
+    }
+
+    // There's no picking evenly from an infinite plane, so this just gives
+    // the origin, which is at least on it.
+    fn random_point(&self, _rng: &mut dyn RngCore) -> tuple::Tuple {
+        tuple::Tuple::point(0., 0., 0.)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{float, material, matrix};
-    use crate::plane::Plane;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::{float, material, matrix, transform};
+    use crate::object::Object;
+    use crate::plane::{Axis, Plane};
     use crate::ray::Ray;
-    use crate::shape::Shape;
+    use crate::shape::{intersect_ts, Shape};
     use crate::tuple::{Tuple, TupleMethods};
 
     #[test]
@@ -69,7 +139,7 @@ mod tests {
             Tuple::point(0., 10., 0.),
             Tuple::vector(0., 0., 1.)
         );
-        let ts = plane.intersect(&local_ray);
+        let ts = intersect_ts(&plane, &local_ray);
         assert_eq!(ts.len(), 0);
     }
 
@@ -82,7 +152,7 @@ mod tests {
             Tuple::point(0., 0., 0.),
             Tuple::vector(0., 0., 1.)
         );
-        let ts = plane.intersect(&local_ray);
+        let ts = intersect_ts(&plane, &local_ray);
         assert_eq!(ts.len(), 0);
     }
 
@@ -96,7 +166,7 @@ mod tests {
             Tuple::point(0., 1., 0.),
             Tuple::vector(0., -1., 0.)
         );
-        let ts = plane.intersect(&local_ray);
+        let ts = intersect_ts(&plane, &local_ray);
         assert_eq!(ts.len(), 1);
         assert!(float::is_equal(ts[0], 1.0));
     }
@@ -111,8 +181,64 @@ mod tests {
             Tuple::point(0., -1., 0.),
             Tuple::vector(0., 1., 0.)
         );
-        let ts = plane.intersect(&local_ray);
+        let ts = intersect_ts(&plane, &local_ray);
         assert_eq!(ts.len(), 1);
         assert!(float::is_equal(ts[0], 1.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_normal_at_flipped() {
+        let plane = Plane::ceiling(
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL
+        );
+        assert!(plane.flip_normal);
+        let normal = plane.normal_at(Tuple::point(3., 0., -2.));
+        assert!(normal.is_equal(Tuple::vector(0., -1., 0.)));
+    }
+
+    #[test]
+    fn test_vertical() {
+        let test_cases = vec![
+            (Axis::X, Tuple::vector(1., 0., 0.)),
+            (Axis::Y, Tuple::vector(0., 1., 0.)),
+            (Axis::Z, Tuple::vector(0., 0., 1.)),
+        ];
+
+        for (axis, expected) in test_cases {
+            let plane = Object::Plane(Plane::vertical(
+                axis,
+                matrix::IDENTITY,
+                material::DEFAULT_MATERIAL
+            ));
+            let normal = plane.normal_at(Tuple::point(0., 0., 0.));
+            assert!(normal.is_equal(expected));
+        }
+    }
+
+    #[test]
+    fn test_vertical_back_wall() {
+        let wall = Object::Plane(Plane::vertical(
+            Axis::Z,
+            transform::translation(0., 0., 10.),
+            material::DEFAULT_MATERIAL
+        ));
+        let ray = Ray::new(
+            Tuple::point(0., 1., 0.),
+            Tuple::vector(0., 0., 1.)
+        );
+        let ts: Vec<f64> = wall.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts.len(), 1);
+        assert!(float::is_equal(ts[0], 10.));
+    }
+
+    #[test]
+    fn test_random_point() {
+        let plane = Plane::new(
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL
+        );
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(plane.random_point(&mut rng), Tuple::point(0., 0., 0.));
+    }
+}
@@ -1,41 +1,49 @@
 use std::fs;
 use std::fs::File;
-use std::io::{Error, Write};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
 
 use crate::canvas;
-use crate::color;
+use crate::{color, float};
 
 const MAX_LINE_WIDTH: usize = 70;
 const MAX_COLOR_COMPONENT_WIDTH: usize = 3;
+const DEFAULT_JPEG_QUALITY: u8 = 90;
 
-fn scale_and_clamp(f: f64) -> u8 {
-    if f < 0.0 {
-        0
-    } else if f >= 1.0 {
-        255
-    } else {
-        (f*256.) as u8
-    }
+pub(crate) fn scale_and_clamp(f: f64) -> u8 {
+    (float::saturate(f)*256.).min(255.) as u8
 }
 
 pub trait Saveable {
-    fn write_header(&self, file: &mut File);
+    fn write_header(&self, file: &mut dyn Write);
 
     fn write_color_component(&self, current_line: &mut String, f: f64);
 
-    fn write_separator(&self, file: &mut File, current_line: &mut String);
+    fn write_separator(&self, file: &mut dyn Write, current_line: &mut String);
+
+    fn write_color(&self, file: &mut dyn Write, current_line: &mut String, c: color::Color);
+
+    fn write_pixel_row(&self, file: &mut dyn Write, y: usize);
 
-    fn write_color(&self, file: &mut File, current_line: &mut String, c: color::Color);
+    fn write_body(&self, file: &mut dyn Write);
 
-    fn write_pixel_row(&self, file: &mut File, y: usize);
+    fn to_ppm_bytes(&self) -> Vec<u8>;
 
-    fn write_body(&self, file: &mut File);
+    fn save_ppm(&self, file_name: &str) -> Result<(), Error>;
 
+    fn save_png(&self, file_name: &str) -> Result<(), Error>;
+
+    // Quality runs from 1, the smallest file, to 100, the best looking one.
+    fn save_jpeg(&self, file_name: &str, quality: u8) -> Result<(), Error>;
+
+    // Picks the image format from the file extension, ignoring case.
     fn save(&self, file_name: &str) -> Result<(), Error>;
 }
 
 impl Saveable for canvas::Canvas {
-    fn write_header(&self, file: &mut File) {
+    fn write_header(&self, file: &mut dyn Write) {
         write!(file, "P3\n{} {}\n255\n", self.width, self.height).unwrap()
     }
 
@@ -43,7 +51,7 @@ impl Saveable for canvas::Canvas {
         current_line.push_str(&scale_and_clamp(f).to_string());
     }
 
-    fn write_separator(&self, file: &mut File, current_line: &mut String) {
+    fn write_separator(&self, file: &mut dyn Write, current_line: &mut String) {
         if current_line.len() >= MAX_LINE_WIDTH - MAX_COLOR_COMPONENT_WIDTH {
             write!(file, "{}\n", current_line).unwrap();
             current_line.clear();
@@ -52,7 +60,7 @@ impl Saveable for canvas::Canvas {
         }
     }
 
-    fn write_color(&self, file: &mut File, current_line: &mut String, c: color::Color) {
+    fn write_color(&self, file: &mut dyn Write, current_line: &mut String, c: color::Color) {
         self.write_color_component(current_line, c.r);
         self.write_separator(file, current_line);
         self.write_color_component( current_line, c.g);
@@ -60,7 +68,7 @@ impl Saveable for canvas::Canvas {
         self.write_color_component( current_line, c.b);
     }
 
-    fn write_pixel_row(&self, file: &mut File, y: usize) {
+    fn write_pixel_row(&self, file: &mut dyn Write, y: usize) {
         let mut current_line = String::new();
         for x in 0..self.width {
             let c = self.get_pixel(x, y);
@@ -72,18 +80,120 @@ impl Saveable for canvas::Canvas {
         write!(file, "{}\n", current_line).unwrap();
     }
 
-    fn write_body(&self, file: &mut File) {
+    fn write_body(&self, file: &mut dyn Write) {
         for y in 0..self.height {
             self.write_pixel_row(file, y);
         }
     }
 
-    fn save(&self, file_name: &str) -> Result<(), Error> {
+    fn to_ppm_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_header(&mut bytes);
+        self.write_body(&mut bytes);
+        bytes
+    }
+
+    fn save_ppm(&self, file_name: &str) -> Result<(), Error> {
         let mut file = File::create(file_name)?;
         self.write_header(&mut file);
         self.write_body(&mut file);
         Ok(())
     }
+
+    fn save_png(&self, file_name: &str) -> Result<(), Error> {
+        self.to_rgb_image()
+            .save_with_format(file_name, image::ImageFormat::Png)
+            .map_err(from_image_error)
+    }
+
+    fn save_jpeg(&self, file_name: &str, quality: u8) -> Result<(), Error> {
+        if !(1..=100).contains(&quality) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("JPEG quality must be between 1 and 100, not {}", quality),
+            ));
+        }
+        let file = File::create(file_name)?;
+        JpegEncoder::new_with_quality(BufWriter::new(file), quality)
+            .encode_image(&self.to_rgb_image())
+            .map_err(from_image_error)
+    }
+
+    fn save(&self, file_name: &str) -> Result<(), Error> {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") | Some("p3") => self.save_ppm(file_name),
+            Some("png") => self.save_png(file_name),
+            Some("jpg") | Some("jpeg") => self.save_jpeg(file_name, DEFAULT_JPEG_QUALITY),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported image format for {}; use .ppm, .png or .jpg", file_name),
+            )),
+        }
+    }
+}
+
+impl canvas::Canvas {
+    fn to_rgb_image(&self) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            image::Rgb(self.get_pixel(x as usize, y as usize).to_u8_array())
+        })
+    }
+}
+
+fn from_image_error(error: image::ImageError) -> Error {
+    match error {
+        image::ImageError::IoError(error) => error,
+        error => Error::other(error),
+    }
+}
+
+fn invalid_ppm(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+impl canvas::Canvas {
+    // Reads back a plain (P3) PPM image, such as one written by save or
+    // to_ppm_bytes, scaling the color components to between 0 and 1.
+    pub fn from_ppm_bytes(bytes: &[u8]) -> Result<canvas::Canvas, Error> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| invalid_ppm("PPM data is not valid text"))?;
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(|line| line.split_whitespace());
+
+        if tokens.next() != Some("P3") {
+            return Err(invalid_ppm("only plain P3 PPM data is supported"));
+        }
+        let mut next_number = || -> Result<usize, Error> {
+            tokens
+                .next()
+                .ok_or_else(|| invalid_ppm("PPM data ended unexpectedly"))?
+                .parse::<usize>()
+                .map_err(|_| invalid_ppm("PPM data contains an invalid number"))
+        };
+        let width = next_number()?;
+        let height = next_number()?;
+        let max_value = next_number()?;
+        if max_value == 0 {
+            return Err(invalid_ppm("PPM maximum color value must be positive"));
+        }
+
+        let mut canvas = canvas::Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let r = next_number()? as f64 / max_value as f64;
+                let g = next_number()? as f64 / max_value as f64;
+                let b = next_number()? as f64 / max_value as f64;
+                canvas.set_pixel(x, y, color::Color::new(r, g, b));
+            }
+        }
+        Ok(canvas)
+    }
 }
 
 #[cfg(test)]
@@ -148,4 +258,163 @@ P3
         fs::remove_file(test_file_name)?;
         Ok(())
     }
+
+    #[test]
+    fn test_to_ppm_bytes() {
+        let mut canvas = canvas::Canvas::new(5, 3);
+        canvas.set_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+        canvas.set_pixel(2, 1, Color::new(0.0, 0.5, 0.0));
+        canvas.set_pixel(4, 2, Color::new(-0.5, 0.0, 1.0));
+        let expected_value = "\
+P3
+5 3
+255
+255 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+0 0 0 0 0 0 0 128 0 0 0 0 0 0 0
+0 0 0 0 0 0 0 0 0 0 0 0 0 0 255
+";
+        assert_eq!(canvas.to_ppm_bytes(), expected_value.as_bytes());
+    }
+
+    #[test]
+    fn test_from_ppm_bytes_round_trip() -> Result<(), Error> {
+        let (w, h) = (12, 7);
+        let mut canvas = canvas::Canvas::new(w, h);
+        for y in 0..h {
+            for x in 0..w {
+                let c = Color::new(
+                    (x * 20) as f64 / 255.,
+                    (y * 30) as f64 / 255.,
+                    ((x + y) * 13) as f64 / 255.,
+                );
+                canvas.set_pixel(x, y, c);
+            }
+        }
+
+        let round_tripped = canvas::Canvas::from_ppm_bytes(&canvas.to_ppm_bytes())?;
+        assert_eq!(round_tripped.width, w);
+        assert_eq!(round_tripped.height, h);
+        for y in 0..h {
+            for x in 0..w {
+                let expected_value = canvas.get_pixel(x, y);
+                let actual_value = round_tripped.get_pixel(x, y);
+                assert!(actual_value.r == expected_value.r &&
+                    actual_value.g == expected_value.g &&
+                    actual_value.b == expected_value.b);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ppm_bytes_with_comments() -> Result<(), Error> {
+        let bytes = b"P3\n# a comment\n2 1\n255\n255 0 0 0 0 255 # trailing\n";
+        let canvas = canvas::Canvas::from_ppm_bytes(bytes)?;
+        assert_eq!(canvas.get_pixel(0, 0), Color::new(1., 0., 0.));
+        assert_eq!(canvas.get_pixel(1, 0), Color::new(0., 0., 1.));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ppm_bytes_invalid() {
+        let cases: Vec<&[u8]> = vec![
+            b"P6\n1 1\n255\n",
+            b"P3\n2 2\n255\n0 0 0",
+            b"P3\n1 1\n255\n0 zero 0",
+        ];
+        for bytes in cases {
+            let error = canvas::Canvas::from_ppm_bytes(bytes).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    fn gradient_canvas() -> canvas::Canvas {
+        let mut canvas = canvas::Canvas::new(4, 2);
+        for y in 0..2 {
+            for x in 0..4 {
+                canvas.set_pixel(x, y, Color::new(x as f64 / 4., y as f64, 0.5));
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_save_detects_ppm() -> Result<(), Error> {
+        let canvas = gradient_canvas();
+        for &test_file_name in ["test_save.ppm", "test_save.P3"].iter() {
+            canvas.save(test_file_name)?;
+            assert_eq!(fs::read(test_file_name)?, canvas.to_ppm_bytes());
+            fs::remove_file(test_file_name)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_detects_png() -> Result<(), Error> {
+        let canvas = gradient_canvas();
+        let test_file_name = "test_save.PNG";
+        canvas.save(test_file_name)?;
+        let image = image::open(test_file_name).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (4, 2));
+        assert_eq!(image.get_pixel(2, 1).0, [128, 255, 128]);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 128]);
+        fs::remove_file(test_file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_detects_jpeg() -> Result<(), Error> {
+        let mut canvas = canvas::Canvas::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                canvas.set_pixel(x, y, color::WHITE);
+            }
+        }
+        for &test_file_name in ["test_save.jpg", "test_save.JPEG"].iter() {
+            canvas.save(test_file_name)?;
+            let image = image::open(test_file_name).unwrap().to_rgb8();
+            assert_eq!(image.dimensions(), (8, 8));
+            assert!(image.pixels().all(|p| p.0.iter().all(|&c| c >= 250)));
+            fs::remove_file(test_file_name)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_jpeg() -> Result<(), Error> {
+        let mut canvas = canvas::Canvas::new(10, 10);
+        for y in 0..10 {
+            for x in 0..10 {
+                canvas.set_pixel(x, y, color::WHITE);
+            }
+        }
+        let test_file_name = "test_save_jpeg.jpg";
+        canvas.save_jpeg(test_file_name, 75)?;
+        let image = image::open(test_file_name).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (10, 10));
+        assert!(image.pixels().all(|p| p.0.iter().all(|&c| c >= 250)));
+        fs::remove_file(test_file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_jpeg_rejects_invalid_quality() {
+        let canvas = gradient_canvas();
+        let test_file_name = "test_save_jpeg_quality.jpg";
+        for &quality in [0, 101, 255].iter() {
+            let error = canvas.save_jpeg(test_file_name, quality).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+            assert!(!Path::new(test_file_name).exists());
+        }
+    }
+
+    #[test]
+    fn test_save_rejects_unknown_format() {
+        let canvas = gradient_canvas();
+        for &test_file_name in ["test_save.gif", "test_save"].iter() {
+            let error = canvas.save(test_file_name).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+            assert!(!Path::new(test_file_name).exists());
+        }
+    }
 }
@@ -2,7 +2,7 @@ use crate::{matrix, tuple};
 use crate::matrix::Matrix4Methods;
 use crate::tuple::TupleMethods;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Ray {
     pub origin: tuple::Tuple,
     pub direction: tuple::Tuple,
@@ -16,6 +16,31 @@ impl Ray {
         }
     }
 
+    pub fn reflected(origin: tuple::Tuple, incoming_direction: tuple::Tuple, normal: tuple::Tuple) -> Ray {
+        Ray::new(origin, incoming_direction.reflect(normal))
+    }
+
+    // Bends the incoming direction according to Snell's law, where n_ratio
+    // is the index of refraction being left divided by the one being
+    // entered. There is no refracted ray under total internal reflection.
+    pub fn refracted(origin: tuple::Tuple, incoming: tuple::Tuple, normal: tuple::Tuple, n_ratio: f64) -> Option<Ray> {
+        let eye = incoming.negate();
+        // cos(theta_i) is the same as the dot product of the two vectors
+        let cos_i = eye.dot(normal);
+        // Find sin(theta_t)^2 via trigonometric identity
+        let sin2_t = n_ratio * n_ratio * (1. - cos_i*cos_i);
+        if sin2_t > 1. {
+            return None;
+        }
+
+        // Find cos(theta_t) via trigonometric identity
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = normal
+            .multiply(n_ratio * cos_i - cos_t)
+            .subtract(eye.multiply(n_ratio));
+        Some(Ray::new(origin, direction))
+    }
+
     pub fn position_at(&self, t: f64) -> tuple::Tuple {
         self.origin.add(self.direction.multiply(t))
     }
@@ -30,7 +55,8 @@ impl Ray {
 
 #[cfg(test)]
 mod tests {
-    use crate::transform;
+    use std::f64::consts::FRAC_1_SQRT_2;
+    use crate::{float, transform};
     use crate::tuple::Tuple;
     use super::*;
 
@@ -66,4 +92,44 @@ mod tests {
         assert!(transformed_r.origin.is_equal(Tuple::point(2., 6., 12.)));
         assert!(transformed_r.direction.is_equal(Tuple::vector(0., 3., 0.)));
     }
+
+    #[test]
+    fn test_reflected() {
+        let origin = Tuple::point(0., 1., 0.);
+        let incoming = Tuple::vector(1., -1., 0.);
+        let normal = Tuple::vector(0., 1., 0.);
+        let r = Ray::reflected(origin, incoming, normal);
+        assert!(r.origin.is_equal(origin));
+        assert!(r.direction.is_equal(Tuple::vector(1., 1., 0.)));
+    }
+
+    #[test]
+    fn test_refracted_straight_through() {
+        let origin = Tuple::point(0., 0., 0.);
+        let incoming = Tuple::vector(0., -1., 0.);
+        let normal = Tuple::vector(0., 1., 0.);
+        let r = Ray::refracted(origin, incoming, normal, 1. / 1.5).unwrap();
+        assert!(r.origin.is_equal(origin));
+        assert!(r.direction.is_equal(Tuple::vector(0., -1., 0.)));
+    }
+
+    #[test]
+    fn test_refracted_bends_toward_normal() {
+        let incoming = Tuple::vector(1., -1., 0.).normalize();
+        let normal = Tuple::vector(0., 1., 0.);
+        let n_ratio = 1. / 1.5;
+        let r = Ray::refracted(Tuple::point(0., 0., 0.), incoming, normal, n_ratio).unwrap();
+
+        // Snell's law: sin(theta_t) = n_ratio * sin(theta_i)
+        let sin_t = r.direction[0] / r.direction.magnitude();
+        assert!(float::is_equal(sin_t, n_ratio * FRAC_1_SQRT_2));
+        assert!(r.direction[1] < 0.);
+    }
+
+    #[test]
+    fn test_refracted_total_internal_reflection() {
+        let incoming = Tuple::vector(1., -1., 0.).normalize();
+        let normal = Tuple::vector(0., 1., 0.);
+        assert!(Ray::refracted(Tuple::point(0., 0., 0.), incoming, normal, 1.5).is_none());
+    }
 }
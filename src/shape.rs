@@ -1,6 +1,29 @@
+use rand::RngCore;
+
+use crate::bounds::BoundingBox;
+use crate::intersection::IntersectionBuffer;
+#[cfg(test)]
+use crate::intersection::Intersections;
 use crate::{ray, tuple};
 
 pub trait Shape {
-    fn intersect(&self, ray: &ray::Ray) -> Vec<f64>;
+    // Adds wherever the ray crosses the shape to the caller's intersections,
+    // recorded against the given object ID.
+    fn intersect(&self, ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer);
     fn normal_at(&self, point: tuple::Tuple) -> tuple::Tuple;
+    // Whether the shape fits inside a box of finite size.
+    fn is_bounded(&self) -> bool;
+    // The axis-aligned box enclosing the shape in object space.
+    fn local_bounds(&self) -> BoundingBox;
+    // A point picked uniformly at random from the surface, in object space,
+    // for sampling light given off by the shape.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple;
+}
+
+// The distances along the ray at which it crosses the shape.
+#[cfg(test)]
+pub fn intersect_ts(shape: &dyn Shape, ray: &ray::Ray) -> Vec<f64> {
+    let mut intersections = Intersections::new();
+    shape.intersect(ray, 0, &mut intersections);
+    intersections.iter().map(|i| i.t).collect()
 }
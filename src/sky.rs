@@ -0,0 +1,182 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::tuple::{Tuple, TupleMethods};
+
+// The sky's luminance comes out in thousands of candelas per square meter,
+// which is far too bright to show as is.
+const LUMINANCE_SCALE: f64 = 0.05;
+
+// The coefficients of the Perez distribution that describes how the sky
+// brightens towards the sun and the horizon.
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    // theta is the angle from the zenith and gamma the angle from the sun.
+    fn at(&self, theta: f64, gamma: f64) -> f64 {
+        (1. + self.a * (self.b / theta.cos()).exp()) *
+            (1. + self.c * (self.d * gamma).exp() + self.e * gamma.cos().powi(2))
+    }
+}
+
+// A simplified version of Preetham's analytic model of the daylight sky.
+// The sun's elevation is measured up from the horizon, its azimuth around
+// from the z axis towards the x axis, both in radians. Turbidity is how
+// hazy the air is, from about 2 for a clear sky to 10 or so for a murky one.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PhysicalSky {
+    pub sun_elevation: f64,
+    pub sun_azimuth: f64,
+    pub turbidity: f64,
+}
+
+impl PhysicalSky {
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64) -> PhysicalSky {
+        PhysicalSky {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+        }
+    }
+
+    pub fn sun_direction(&self) -> Tuple {
+        Tuple::vector(
+            self.sun_elevation.cos() * self.sun_azimuth.sin(),
+            self.sun_elevation.sin(),
+            self.sun_elevation.cos() * self.sun_azimuth.cos(),
+        )
+    }
+
+    pub fn sample_direction(&self, direction: Tuple) -> Color {
+        let direction = direction.normalize();
+        // The model only covers the sky above the horizon, so anything
+        // below it is treated as if it were just above it.
+        let theta = direction[1].max(0.01).acos();
+        let gamma = direction.dot(self.sun_direction()).clamp(-1., 1.).acos();
+        let theta_sun = PI / 2. - self.sun_elevation;
+
+        let (zenith_luminance, zenith_x, zenith_y) = self.zenith(theta_sun);
+        let (perez_luminance, perez_x, perez_y) = self.perez();
+        let relative = |perez: &Perez| perez.at(theta, gamma) / perez.at(0., theta_sun);
+
+        let luminance = zenith_luminance * relative(&perez_luminance) * LUMINANCE_SCALE;
+        let x = zenith_x * relative(&perez_x);
+        let y = zenith_y * relative(&perez_y);
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    // The luminance and chromaticity straight up, with the sun at theta_sun
+    // from the zenith.
+    fn zenith(&self, theta_sun: f64) -> (f64, f64, f64) {
+        let t = self.turbidity;
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_sun);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.);
+
+        let theta = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.];
+        let weighted = |coefficients: [[f64; 4]; 3]| -> f64 {
+            let terms = [t * t, t, 1.];
+            terms
+                .iter()
+                .zip(coefficients.iter())
+                .map(|(term, row)| term * row.iter().zip(theta.iter()).map(|(c, a)| c * a).sum::<f64>())
+                .sum()
+        };
+        let x = weighted([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = weighted([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        (luminance, x, y)
+    }
+
+    fn perez(&self) -> (Perez, Perez, Perez) {
+        let t = self.turbidity;
+        let luminance = Perez {
+            a: 0.1787 * t - 1.4630,
+            b: -0.3554 * t + 0.4275,
+            c: -0.0227 * t + 5.3251,
+            d: 0.1206 * t - 2.5771,
+            e: -0.0670 * t + 0.3703,
+        };
+        let x = Perez {
+            a: -0.0193 * t - 0.2592,
+            b: -0.0665 * t + 0.0008,
+            c: -0.0004 * t + 0.2125,
+            d: -0.0641 * t - 0.8989,
+            e: -0.0033 * t + 0.0452,
+        };
+        let y = Perez {
+            a: -0.0167 * t - 0.2608,
+            b: -0.0950 * t + 0.0092,
+            c: -0.0079 * t + 0.2102,
+            d: -0.0441 * t - 1.6537,
+            e: -0.0109 * t + 0.0529,
+        };
+        (luminance, x, y)
+    }
+}
+
+// Converts a chromaticity and luminance to linear sRGB by way of CIE XYZ.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use crate::tuple::{Tuple, TupleMethods};
+    use super::*;
+
+    #[test]
+    fn test_sun_direction() {
+        let sky = PhysicalSky::new(PI / 2., 0., 3.);
+        assert!(sky.sun_direction().is_equal(Tuple::vector(0., 1., 0.)));
+        let sky = PhysicalSky::new(0., PI / 2., 3.);
+        assert!(sky.sun_direction().is_equal(Tuple::vector(1., 0., 0.)));
+    }
+
+    #[test]
+    fn test_zenith_is_blue() {
+        let sky = PhysicalSky::new(PI / 4., 0., 3.);
+        let color = sky.sample_direction(Tuple::vector(0., 1., 0.));
+        assert!(color.b > color.g && color.g > color.r);
+    }
+
+    #[test]
+    fn test_horizon_at_sunrise_is_lighter_and_more_orange() {
+        let sky = PhysicalSky::new(0.05, 0., 3.);
+        let zenith = sky.sample_direction(Tuple::vector(0., 1., 0.));
+        let horizon = sky.sample_direction(Tuple::vector(0., 0.05, 1.));
+        let brightness = |c: Color| c.r + c.g + c.b;
+        assert!(brightness(horizon) > brightness(zenith));
+        assert!(horizon.r / horizon.b > zenith.r / zenith.b);
+        assert!(horizon.r > horizon.b);
+    }
+
+    #[test]
+    fn test_below_horizon_is_still_lit() {
+        let sky = PhysicalSky::new(PI / 6., 1., 4.);
+        let color = sky.sample_direction(Tuple::vector(0., -1., 0.));
+        assert!(color.r > 0. && color.g > 0. && color.b > 0.);
+        assert!(color.r.is_finite() && color.g.is_finite() && color.b.is_finite());
+    }
+}
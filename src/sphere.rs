@@ -1,18 +1,33 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::BoundingBox;
 use crate::float;
+use crate::intersection::{Intersection, IntersectionBuffer};
 use crate::material;
 use crate::material::Material;
 use crate::matrix;
+use crate::object;
 use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::ray;
 use crate::shape::Shape;
 use crate::tuple;
+use crate::uv;
 use crate::tuple::{Tuple, TupleMethods};
 
-#[derive(Clone)]
+// A ray can pass through a sphere at most twice.
+pub const MAX_INTERSECTIONS: usize = 2;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Sphere {
     pub transform: matrix::Matrix4,
     pub inverse_transform: matrix::Matrix4,
     pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
 }
 
 impl Sphere {
@@ -21,35 +36,87 @@ impl Sphere {
             transform: transform,
             inverse_transform: transform.inverse().unwrap(),
             material: material,
+            id: object::next_id(),
+            visible: true,
         }
     }
+
+    // Texture coordinates for a point on the sphere that won't tear across
+    // the seam where u wraps around from 1 back to 0. Given the u of a
+    // neighboring vertex, such as another corner of the same triangle, u
+    // is moved a whole turn around the sphere whenever that brings it
+    // closer, so it may end up outside [0, 1]. Textures are expected to
+    // wrap around horizontally anyway.
+    pub fn uv_seamless(local_point: Tuple, neighbor_u: Option<f64>) -> (f64, f64) {
+        let (u, v) = uv::spherical_map(local_point);
+        let u = match neighbor_u {
+            Some(neighbor_u) if neighbor_u - u > 0.5 => u + 1.,
+            Some(neighbor_u) if u - neighbor_u > 0.5 => u - 1.,
+            _ => u,
+        };
+        (u, v)
+    }
+
+    // The tangent and bitangent at a point on the surface, for building the
+    // basis that bump and normal maps are applied in. The tangent runs
+    // around the sphere, along a line of latitude, except at the poles
+    // where there isn't one and any horizontal direction will do.
+    pub fn tangent_at(&self, local_point: Tuple) -> (Tuple, Tuple) {
+        let normal = self.normal_at(local_point).normalize();
+        let around = Tuple::vector(0., 1., 0.).cross(normal);
+        let tangent = if float::is_equal(around.magnitude(), 0.) {
+            Tuple::vector(1., 0., 0.)
+        } else {
+            around.normalize()
+        };
+        (tangent, tangent.cross(normal))
+    }
 }
 
 impl Shape for Sphere {
-    fn intersect(&self, local_ray: &ray::Ray) -> Vec<f64> {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
         let sphere_to_ray = local_ray.origin.subtract([0., 0., 0., 1.]);
         let a = local_ray.direction.dot(local_ray.direction);
         let b = 2. * local_ray.direction.dot(sphere_to_ray);
         let c = sphere_to_ray.dot(sphere_to_ray) - 1.;
         let discriminant = b*b - 4.*a*c;
 
-        if discriminant < 0. {
-            vec![]
-        } else if discriminant == 0. {
-            vec![-b/2./a]
-        } else {
-            vec![(-b - discriminant.sqrt())/2./a, (-b + discriminant.sqrt())/2./a,]
+        if discriminant == 0. {
+            intersections.push(Intersection::new(-b/2./a, object_id));
+        } else if discriminant > 0. {
+            intersections.push(Intersection::new((-b - discriminant.sqrt())/2./a, object_id));
+            intersections.push(Intersection::new((-b + discriminant.sqrt())/2./a, object_id));
         }
     }
 
     fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
         local_point.subtract(Tuple::point(0.,0.,0.))
     }
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1., -1., -1.), Tuple::point(1., 1., 1.))
+    }
+
+    // Picking the height uniformly spreads points evenly over the surface,
+    // by Archimedes' hat-box theorem.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        let y = 1. - 2. * rng.random::<f64>();
+        let phi = 2. * PI * rng.random::<f64>();
+        let radius = (1. - y*y).sqrt();
+        Tuple::point(radius * phi.cos(), y, radius * phi.sin())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::shape::intersect_ts;
     use std::f64::consts::PI;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
     use crate::matrix::Matrix4Methods;
     use crate::transform;
     use crate::tuple::Tuple;
@@ -62,7 +129,7 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         );
-        let intersections = sphere.intersect(&ray);
+        let intersections = intersect_ts(&sphere, &ray);
 
         assert_eq!(intersections.len(), 0);
     }
@@ -74,7 +141,7 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         );
-        let intersections = sphere.intersect(&ray);
+        let intersections = intersect_ts(&sphere, &ray);
 
         assert_eq!(intersections.len(), 1);
         assert_eq!(float::is_equal(intersections[0], 5.), true);
@@ -87,7 +154,7 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         );
-        let intersections = sphere.intersect(&ray);
+        let intersections = intersect_ts(&sphere, &ray);
 
         assert_eq!(intersections.len(), 2);
         assert_eq!(float::is_equal(intersections[0], -1.), true);
@@ -101,7 +168,7 @@ mod tests {
             matrix::IDENTITY,
             material::DEFAULT_MATERIAL,
         );
-        let intersections = sphere.intersect(&ray);
+        let intersections = intersect_ts(&sphere, &ray);
 
         assert_eq!(intersections.len(), 2);
         assert_eq!(float::is_equal(intersections[0], -6.), true);
@@ -117,7 +184,7 @@ mod tests {
         let world_ray = ray::Ray::new([0., 0., -5., 1.], [0., 0., 1., 0.]);
         let local_ray = world_ray.transform(sphere.inverse_transform);
 
-        let intersections = sphere.intersect(&local_ray);
+        let intersections = intersect_ts(&sphere, &local_ray);
         assert_eq!(intersections.len(), 2);
         assert_eq!(float::is_equal(intersections[0], 3.), true);
         assert_eq!(float::is_equal(intersections[1], 7.), true);
@@ -132,7 +199,7 @@ mod tests {
         let world_ray = ray::Ray::new([0., 0., -5., 1.], [0., 0., 1., 0.]);
         let local_ray = world_ray.transform(sphere.inverse_transform);
 
-        let intersections = sphere.intersect(&local_ray);
+        let intersections = intersect_ts(&sphere, &local_ray);
         assert_eq!(intersections.len(), 0);
     }
 
@@ -204,4 +271,42 @@ mod tests {
         let expected_value = Tuple::vector(0.83126, 1.14413, -0.70711);
         assert!(local_normal.is_equal(expected_value));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_random_point() {
+        let sphere = Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        let mut rng = StdRng::seed_from_u64(42);
+        let points: Vec<Tuple> = (0..1000).map(|_| sphere.random_point(&mut rng)).collect();
+        assert!(points.iter().all(|p| float::is_equal(p.subtract(Tuple::point(0., 0., 0.)).magnitude(), 1.)));
+
+        // Points should be spread evenly, so on average they're close to
+        // the center.
+        let mean = points.iter().fold(Tuple::vector(0., 0., 0.), |total, p| total.add(p.multiply(1. / 1000.)));
+        assert!((0..3).all(|axis| mean[axis].abs() < 0.1));
+    }
+
+    #[test]
+    fn test_uv_seamless() {
+        // Just either side of the seam, which runs down the -z side.
+        let east = Tuple::point(0.01, 0., -1.);
+        let west = Tuple::point(-0.01, 0., -1.);
+        let (east_u, _) = uv::spherical_map(east);
+        let (west_u, _) = uv::spherical_map(west);
+        assert!((east_u - west_u).abs() > 0.5);
+
+        let test_cases = vec![
+            (east, None, east_u),
+            (east, Some(west_u), if east_u < west_u { east_u + 1. } else { east_u - 1. }),
+            (west, Some(east_u), if west_u < east_u { west_u + 1. } else { west_u - 1. }),
+            (west, Some(0.6), west_u),
+        ];
+        for (point, neighbor_u, expected_u) in test_cases {
+            let (u, v) = Sphere::uv_seamless(point, neighbor_u);
+            assert!(float::is_equal(u, expected_u));
+            assert!(float::is_equal(v, 0.5));
+        }
+
+        let (east_u, _) = Sphere::uv_seamless(east, Some(west_u));
+        assert!((east_u - west_u).abs() < 0.01);
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::matrix;
+use crate::group::Group;
+use crate::material::Material;
+use crate::object::Object;
+use crate::triangle::Triangle;
+use crate::tuple::{Tuple, TupleMethods};
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StlError::Io(error) => write!(f, "Unable to read STL file: {}", error),
+            StlError::Parse(message) => write!(f, "Unable to parse STL file: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for StlError {
+    fn from(error: io::Error) -> StlError {
+        StlError::Io(error)
+    }
+}
+
+// Loads either a binary or an ASCII STL file as a group of triangles. The
+// normals stored in the file are ignored and recomputed from the vertices.
+pub fn load_stl(path: &str, material: Material) -> Result<Object, StlError> {
+    let bytes = fs::read(path)?;
+    let faces = if is_binary(&bytes) {
+        parse_binary(&bytes)?
+    } else {
+        let text = String::from_utf8(bytes)
+            .map_err(|_| StlError::Parse("file is neither binary nor ASCII STL".to_string()))?;
+        parse_ascii(&text)?
+    };
+
+    let triangles = faces
+        .iter()
+        .map(|[p1, p2, p3]| {
+            Object::Triangle(Triangle::new(*p1, *p2, *p3, matrix::IDENTITY, material.clone()))
+        })
+        .collect();
+    Ok(Object::Group(Group::new(triangles)))
+}
+
+// ASCII files are supposed to start with "solid", but so do plenty of binary
+// files in the wild, so the only reliable check is whether the size of the
+// file matches the triangle count in its header.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    bytes.len() == HEADER_SIZE + 4 + count*TRIANGLE_SIZE
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f64 {
+    f32::from_le_bytes([bytes[offset], bytes[offset+1], bytes[offset+2], bytes[offset+3]]) as f64
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<[Tuple; 3]>, StlError> {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    let mut faces = Vec::with_capacity(count);
+    for i in 0..count {
+        // Each record is a normal, three vertices, and a two byte attribute
+        // count; skip past the normal to get to the vertices.
+        let offset = HEADER_SIZE + 4 + i*TRIANGLE_SIZE + 12;
+        let mut vertices = [Tuple::point(0., 0., 0.); 3];
+        for (j, vertex) in vertices.iter_mut().enumerate() {
+            let vertex_offset = offset + j*12;
+            *vertex = Tuple::point(
+                read_f32(bytes, vertex_offset),
+                read_f32(bytes, vertex_offset + 4),
+                read_f32(bytes, vertex_offset + 8),
+            );
+        }
+        faces.push(vertices);
+    }
+    Ok(faces)
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<[Tuple; 3]>, StlError> {
+    let mut faces = vec![];
+    let mut vertices = vec![];
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"vertex") => {
+                if tokens.len() != 4 {
+                    return Err(StlError::Parse(format!("malformed vertex: {}", line.trim())));
+                }
+                let mut coordinates = [0.; 3];
+                for (coordinate, token) in coordinates.iter_mut().zip(&tokens[1..]) {
+                    *coordinate = token
+                        .parse()
+                        .map_err(|_| StlError::Parse(format!("invalid coordinate: {}", token)))?;
+                }
+                vertices.push(Tuple::point(coordinates[0], coordinates[1], coordinates[2]));
+            },
+            Some(&"endfacet") => {
+                if vertices.len() != 3 {
+                    return Err(StlError::Parse(format!("expected 3 vertices per facet, got {}", vertices.len())));
+                }
+                faces.push([vertices[0], vertices[1], vertices[2]]);
+                vertices.clear();
+            },
+            _ => (),
+        }
+    }
+    Ok(faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::material;
+    use crate::ray::Ray;
+    use super::*;
+
+    // The twelve triangles making up the faces of the cube spanning [-1, 1]
+    fn cube_faces() -> Vec<[[f32; 3]; 3]> {
+        let mut faces = vec![];
+        for axis in 0..3 {
+            for &side in [-1f32, 1.].iter() {
+                let corner = |u: f32, v: f32| {
+                    let mut vertex = [0f32; 3];
+                    vertex[axis] = side;
+                    vertex[(axis + 1) % 3] = u;
+                    vertex[(axis + 2) % 3] = v;
+                    vertex
+                };
+                faces.push([corner(-1., -1.), corner(1., -1.), corner(1., 1.)]);
+                faces.push([corner(-1., -1.), corner(1., 1.), corner(-1., 1.)]);
+            }
+        }
+        faces
+    }
+
+    fn count_children(object: &Object) -> usize {
+        match object {
+            Object::Group(group) => group.children.len(),
+            _ => panic!("Expected a group"),
+        }
+    }
+
+    fn center_ray() -> Ray {
+        // Slightly off-center, so that the ray doesn't pass through the
+        // shared edge of the two triangles on each face.
+        Ray::new(
+            Tuple::point(0.1, 0.3, -5.),
+            Tuple::vector(0., 0., 1.),
+        )
+    }
+
+    #[test]
+    fn test_load_stl_binary() -> Result<(), StlError> {
+        let faces = cube_faces();
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        bytes.extend_from_slice(&(faces.len() as u32).to_le_bytes());
+        for face in faces.iter() {
+            // The normal is ignored, so just leave it zeroed out.
+            bytes.extend_from_slice(&[0u8; 12]);
+            for vertex in face.iter() {
+                for coordinate in vertex.iter() {
+                    bytes.extend_from_slice(&coordinate.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&[0u8; 2]);
+        }
+
+        let test_file_name = "test_cube_binary.stl";
+        fs::write(test_file_name, &bytes)?;
+        let cube = load_stl(test_file_name, material::DEFAULT_MATERIAL)?;
+        fs::remove_file(test_file_name)?;
+
+        assert_eq!(count_children(&cube), 12);
+        let intersections = cube.intersect(&center_ray());
+        assert_eq!(intersections.len(), 2);
+        let mut ts: Vec<f64> = intersections.iter().map(|i| i.t).collect();
+        ts.sort_by(|t1, t2| t1.partial_cmp(t2).unwrap());
+        assert_eq!(ts, [4., 6.]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_stl_ascii() -> Result<(), StlError> {
+        let mut contents = String::from("solid cube\n");
+        for face in cube_faces() {
+            contents.push_str("  facet normal 0 0 0\n    outer loop\n");
+            for vertex in face.iter() {
+                contents.push_str(&format!("      vertex {} {} {}\n", vertex[0], vertex[1], vertex[2]));
+            }
+            contents.push_str("    endloop\n  endfacet\n");
+        }
+        contents.push_str("endsolid cube\n");
+
+        let test_file_name = "test_cube_ascii.stl";
+        fs::write(test_file_name, &contents)?;
+        let cube = load_stl(test_file_name, material::DEFAULT_MATERIAL)?;
+        fs::remove_file(test_file_name)?;
+
+        assert_eq!(count_children(&cube), 12);
+        assert_eq!(cube.intersect(&center_ray()).len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_stl_ascii_malformed_vertex() -> Result<(), StlError> {
+        let contents = "\
+solid broken
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 zero 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid broken
+";
+        let test_file_name = "test_broken.stl";
+        fs::write(test_file_name, contents)?;
+        let result = load_stl(test_file_name, material::DEFAULT_MATERIAL);
+        fs::remove_file(test_file_name)?;
+
+        assert!(matches!(result, Err(StlError::Parse(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{material, matrix, object, ray, tuple};
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
+use crate::material::Material;
+use crate::matrix::{Matrix4, Matrix4Methods};
+use crate::shape::Shape;
+use crate::triangle::{intersect_triangle, random_point_on_triangle};
+use crate::tuple::{Tuple, TupleMethods};
+
+// A node in the hierarchy of bounding boxes over the cells of the height
+// grid; leaves hold a single cell, which is made up of two triangles.
+#[derive(Clone, Serialize, Deserialize)]
+struct TerrainNode {
+    first_col: usize,
+    last_col: usize,
+    first_row: usize,
+    last_row: usize,
+    min_height: f64,
+    max_height: f64,
+    children: Vec<TerrainNode>,
+}
+
+// Recursively splits the range of cells in half along its longer side
+// until each node covers a single cell.
+fn build_node(heights: &[f64], cols: usize,
+              first_col: usize, last_col: usize,
+              first_row: usize, last_row: usize) -> TerrainNode {
+    if first_col == last_col && first_row == last_row {
+        let corners = [
+            heights[first_row*cols + first_col],
+            heights[first_row*cols + first_col + 1],
+            heights[(first_row + 1)*cols + first_col],
+            heights[(first_row + 1)*cols + first_col + 1],
+        ];
+        return TerrainNode {
+            first_col,
+            last_col,
+            first_row,
+            last_row,
+            min_height: corners.iter().cloned().fold(f64::INFINITY, f64::min),
+            max_height: corners.iter().cloned().fold(-f64::INFINITY, f64::max),
+            children: vec![],
+        };
+    }
+
+    let children = if last_col - first_col >= last_row - first_row {
+        let middle = (first_col + last_col) / 2;
+        vec![
+            build_node(heights, cols, first_col, middle, first_row, last_row),
+            build_node(heights, cols, middle + 1, last_col, first_row, last_row),
+        ]
+    } else {
+        let middle = (first_row + last_row) / 2;
+        vec![
+            build_node(heights, cols, first_col, last_col, first_row, middle),
+            build_node(heights, cols, first_col, last_col, middle + 1, last_row),
+        ]
+    };
+
+    TerrainNode {
+        first_col,
+        last_col,
+        first_row,
+        last_row,
+        min_height: children.iter().map(|c| c.min_height).fold(f64::INFINITY, f64::min),
+        max_height: children.iter().map(|c| c.max_height).fold(-f64::INFINITY, f64::max),
+        children,
+    }
+}
+
+// A heightfield spanning [-1, 1] in x and z in object space, with the height
+// of each grid vertex, expected to lie in [0, 1], giving its y coordinate.
+// Heights are stored row by row, with columns running along x and rows
+// running along z.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Terrain {
+    pub transform: matrix::Matrix4,
+    pub inverse_transform: matrix::Matrix4,
+    pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    pub heights: Vec<f64>,
+    pub cols: usize,
+    pub rows: usize,
+    root: TerrainNode,
+}
+
+impl Terrain {
+    pub fn new(heights: Vec<f64>, cols: usize, rows: usize, transform: Matrix4, material: Material) -> Terrain {
+        assert!(cols >= 2 && rows >= 2, "A terrain needs at least a 2x2 grid of heights");
+        assert_eq!(heights.len(), cols * rows, "Expected one height per grid vertex");
+
+        let root = build_node(&heights, cols, 0, cols - 2, 0, rows - 2);
+        Terrain {
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+            material,
+            id: object::next_id(),
+            visible: true,
+            heights,
+            cols,
+            rows,
+            root,
+        }
+    }
+
+    fn height(&self, col: usize, row: usize) -> f64 {
+        self.heights[row*self.cols + col]
+    }
+
+    fn x_at(&self, col: usize) -> f64 {
+        -1. + 2. * col as f64 / (self.cols - 1) as f64
+    }
+
+    fn z_at(&self, row: usize) -> f64 {
+        -1. + 2. * row as f64 / (self.rows - 1) as f64
+    }
+
+    fn vertex(&self, col: usize, row: usize) -> Tuple {
+        Tuple::point(self.x_at(col), self.height(col, row), self.z_at(row))
+    }
+
+    // The two triangles a grid cell is split into, each as a corner and the
+    // two edges leading away from it.
+    fn cell_triangles(&self, col: usize, row: usize) -> [(Tuple, Tuple, Tuple); 2] {
+        let p00 = self.vertex(col, row);
+        let p10 = self.vertex(col + 1, row);
+        let p01 = self.vertex(col, row + 1);
+        let p11 = self.vertex(col + 1, row + 1);
+        [
+            (p00, p10.subtract(p00), p11.subtract(p00)),
+            (p00, p11.subtract(p00), p01.subtract(p00)),
+        ]
+    }
+
+    fn hits_node(&self, node: &TerrainNode, local_ray: &ray::Ray) -> bool {
+        let minimums = [self.x_at(node.first_col), node.min_height, self.z_at(node.first_row)];
+        let maximums = [self.x_at(node.last_col + 1), node.max_height, self.z_at(node.last_row + 1)];
+
+        let mut tmin = -f64::INFINITY;
+        let mut tmax = f64::INFINITY;
+        for axis in 0..3 {
+            let origin = local_ray.origin[axis];
+            let direction = local_ray.direction[axis];
+            if direction == 0. {
+                if origin < minimums[axis] || origin > maximums[axis] {
+                    return false;
+                }
+            } else {
+                let t1 = (minimums[axis] - origin) / direction;
+                let t2 = (maximums[axis] - origin) / direction;
+                tmin = tmin.max(t1.min(t2));
+                tmax = tmax.min(t1.max(t2));
+            }
+        }
+        tmin <= tmax
+    }
+
+    fn intersect_node(&self, node: &TerrainNode, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        if !self.hits_node(node, local_ray) {
+            return;
+        }
+
+        if node.children.is_empty() {
+            for (p1, e1, e2) in self.cell_triangles(node.first_col, node.first_row) {
+                if let Some(t) = intersect_triangle(p1, e1, e2, local_ray) {
+                    intersections.push(Intersection::new(t, object_id));
+                }
+            }
+        } else {
+            for child in node.children.iter() {
+                self.intersect_node(child, local_ray, object_id, intersections);
+            }
+        }
+    }
+
+    // Bilinearly interpolates the height at a position given in (fractional)
+    // grid coordinates, clamped to the extent of the grid.
+    fn interpolated_height(&self, grid_x: f64, grid_z: f64) -> f64 {
+        let grid_x = grid_x.clamp(0., (self.cols - 1) as f64);
+        let grid_z = grid_z.clamp(0., (self.rows - 1) as f64);
+        let col = (grid_x.floor() as usize).min(self.cols - 2);
+        let row = (grid_z.floor() as usize).min(self.rows - 2);
+        let fx = grid_x - col as f64;
+        let fz = grid_z - row as f64;
+
+        let near = self.height(col, row) * (1. - fx) + self.height(col + 1, row) * fx;
+        let far = self.height(col, row + 1) * (1. - fx) + self.height(col + 1, row + 1) * fx;
+        near * (1. - fz) + far * fz
+    }
+}
+
+impl Shape for Terrain {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        self.intersect_node(&self.root, local_ray, object_id, intersections);
+    }
+
+    fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
+        // Take central differences one grid cell to either side of the point.
+        let cell_width = 2. / (self.cols - 1) as f64;
+        let cell_depth = 2. / (self.rows - 1) as f64;
+        let grid_x = (local_point[0] + 1.) / cell_width;
+        let grid_z = (local_point[2] + 1.) / cell_depth;
+
+        let dh_dx = (self.interpolated_height(grid_x + 1., grid_z) -
+            self.interpolated_height(grid_x - 1., grid_z)) / (2. * cell_width);
+        let dh_dz = (self.interpolated_height(grid_x, grid_z + 1.) -
+            self.interpolated_height(grid_x, grid_z - 1.)) / (2. * cell_depth);
+
+        Tuple::vector(-dh_dx, 1., -dh_dz).normalize()
+    }
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let lowest = self.heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = self.heights.iter().cloned().fold(-f64::INFINITY, f64::max);
+        BoundingBox::new(
+            Tuple::point(-1., lowest, -1.),
+            Tuple::point(1., highest, 1.),
+        )
+    }
+
+    // Picks one of the triangles making up the surface in proportion to its
+    // area, and then a point on it.
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        let triangles: Vec<(Tuple, Tuple, Tuple)> = (0..self.rows - 1)
+            .flat_map(|row| (0..self.cols - 1).flat_map(move |col| self.cell_triangles(col, row)))
+            .collect();
+        let areas: Vec<f64> = triangles
+            .iter()
+            .map(|(_, e1, e2)| e1.cross(*e2).magnitude() / 2.)
+            .collect();
+
+        let mut pick = rng.random::<f64>() * areas.iter().sum::<f64>();
+        let mut chosen = triangles.len() - 1;
+        for (index, area) in areas.iter().enumerate() {
+            if pick < *area {
+                chosen = index;
+                break;
+            }
+            pick -= area;
+        }
+        let (p1, e1, e2) = triangles[chosen];
+        random_point_on_triangle(p1, e1, e2, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::{float, material, matrix};
+    use crate::ray::Ray;
+    use crate::shape::{intersect_ts, Shape};
+    use crate::terrain::Terrain;
+    use crate::tuple::{Tuple, TupleMethods};
+
+    fn ramp(cols: usize, rows: usize) -> Terrain {
+        // Heights rise linearly from 0 at x = -1 to 1 at x = 1.
+        let mut heights = vec![];
+        for _ in 0..rows {
+            for col in 0..cols {
+                heights.push(col as f64 / (cols - 1) as f64);
+            }
+        }
+        Terrain::new(heights, cols, rows, matrix::IDENTITY, material::DEFAULT_MATERIAL)
+    }
+
+    #[test]
+    fn test_intersect_flat() {
+        let terrain = Terrain::new(
+            vec![0.5; 16], 4, 4,
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        );
+        let ray = Ray::new(
+            Tuple::point(0.3, 5., -0.2),
+            Tuple::vector(0., -1., 0.),
+        );
+        let ts = intersect_ts(&terrain, &ray);
+        assert_eq!(ts.len(), 1);
+        assert!(float::is_equal(ts[0], 4.5));
+    }
+
+    #[test]
+    fn test_intersect_misses_outside_grid() {
+        let terrain = ramp(5, 5);
+        let test_cases = vec![
+            (Tuple::point(1.5, 5., 0.), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0., 5., -1.5), Tuple::vector(0., -1., 0.)),
+            (Tuple::point(0., 2., -5.), Tuple::vector(0., 0., 1.)),
+        ];
+        for (origin, direction) in test_cases {
+            let ray = Ray::new(origin, direction);
+            assert_eq!(intersect_ts(&terrain, &ray).len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_intersect_ramp() {
+        let terrain = ramp(9, 7);
+        let test_cases = vec![
+            (-0.75, 0.1),
+            (0., 0.5),
+            (0.6, -0.9),
+        ];
+        for (x, z) in test_cases {
+            let ray = Ray::new(
+                Tuple::point(x, 5., z),
+                Tuple::vector(0., -1., 0.),
+            );
+            let ts = intersect_ts(&terrain, &ray);
+            assert!(!ts.is_empty());
+            let expected_height = (x + 1.) / 2.;
+            assert!(ts.iter().all(|&t| float::is_equal(5. - t, expected_height)));
+        }
+    }
+
+    #[test]
+    fn test_normal_at_flat() {
+        let terrain = Terrain::new(
+            vec![0.25; 9], 3, 3,
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        );
+        let normal = terrain.normal_at(Tuple::point(0.1, 0.25, 0.4));
+        assert!(normal.is_equal(Tuple::vector(0., 1., 0.)));
+    }
+
+    #[test]
+    fn test_normal_at_ramp() {
+        let terrain = ramp(9, 9);
+        let normal = terrain.normal_at(Tuple::point(0.2, 0.6, -0.3));
+        let expected_value = Tuple::vector(-0.5, 1., 0.).normalize();
+        assert!(normal.is_equal(expected_value));
+    }
+
+    #[test]
+    fn test_random_point() {
+        let terrain = ramp(5, 3);
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let point = terrain.random_point(&mut rng);
+            assert!(point[0].abs() <= 1. && point[2].abs() <= 1.);
+            assert!(float::is_equal(point[1], (point[0] + 1.) / 2.));
+        }
+    }
+}
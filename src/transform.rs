@@ -3,74 +3,79 @@ use crate::matrix::{Matrix4, Matrix4Methods};
 use crate::tuple::{Tuple, TupleMethods};
 
 pub fn translation(x: f64, y: f64, z: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [1., 0., 0., x],
         [0., 1., 0., y],
         [0., 0., 1., z],
         [0., 0., 0., 1.]
-    ]
+    ])
 }
 
 pub fn scaling(x: f64, y: f64, z: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [x, 0., 0., 0.],
         [0., y, 0., 0.],
         [0., 0., z, 0.],
         [0., 0., 0., 1.]
-    ]
+    ])
 }
 
 pub fn rotation_x(t: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [1.,      0.,       0., 0.],
         [0., t.cos(), -t.sin(), 0.],
         [0., t.sin(),  t.cos(), 0.],
         [0.,      0.,       0., 1.]
-    ]
+    ])
 }
 
 pub fn rotation_y(t: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [ t.cos(), 0., t.sin(), 0.],
         [      0., 1.,      0., 0.],
         [-t.sin(), 0., t.cos(), 0.],
         [      0., 0.,      0., 1.]
-    ]
+    ])
 }
 
 pub fn rotation_z(t: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [t.cos(), -t.sin(), 0., 0.],
         [t.sin(),  t.cos(), 0., 0.],
         [     0.,       0., 1., 0.],
         [     0.,       0., 0., 1.]
-    ]
+    ])
 }
 
 pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> matrix::Matrix4 {
-    [
+    Matrix4::from([
         [1., xy, xz, 0.],
         [yx, 1., yz, 0.],
         [zx, zy, 1., 0.],
         [0., 0., 0., 1.]
-    ]
+    ])
 }
 
-pub fn view(from: Tuple, to: Tuple, up: Tuple) -> Matrix4 {
+pub fn look_at(from: Tuple, to: Tuple, up: Tuple) -> Matrix4 {
     let forward = to.subtract(from).normalize();
     let up_normalized = up.normalize();
     let left = forward.cross(up_normalized);
     let true_up = left.cross(forward);
-    let orientation = [
+    let orientation = Matrix4::from([
         left,
         true_up,
         forward.negate(),
         [0., 0., 0., 1.],
-    ];
+    ]);
     let backward_transform = transform::translation(-from[0], -from[1], -from[2]);
     orientation.multiply_matrix(backward_transform)
 }
 
+#[deprecated(note = "use transform::look_at instead")]
+pub fn view(from: Tuple, to: Tuple, up: Tuple) -> Matrix4 {
+    look_at(from, to, up)
+}
+
 #[cfg(test)]
 mod tests {
     use std::f64::consts::PI;
@@ -180,46 +185,46 @@ mod tests {
     }
 
     #[test]
-    fn test_view_default() {
+    fn test_look_at_default() {
         let from = Tuple::point(0., 0., 0.);
         let to = Tuple::point(0., 0., -1.);
         let up = Tuple::vector(0., 1., 0.);
-        let view = view(from, to, up);
+        let view = look_at(from, to, up);
         assert!(view.is_equal(matrix::IDENTITY));
     }
 
     #[test]
-    fn test_view_looking_in_positive_z_direction() {
+    fn test_look_at_looking_in_positive_z_direction() {
         let from = Tuple::point(0., 0., 0.);
         let to = Tuple::point(0., 0., 1.);
         let up = Tuple::vector(0., 1., 0.);
-        let view = view(from, to, up);
+        let view = look_at(from, to, up);
         let expected_value = transform::scaling(-1., 1., -1.);
         assert!(view.is_equal(expected_value));
     }
 
     #[test]
-    fn test_view_moves_world() {
+    fn test_look_at_moves_world() {
         let from = Tuple::point(0., 0., 8.);
         let to = Tuple::point(0., 0., 0.);
         let up = Tuple::vector(0., 1., 0.);
-        let view = view(from, to, up);
+        let view = look_at(from, to, up);
         let expected_value = transform::translation(0., 0., -8.);
         assert!(view.is_equal(expected_value));
     }
 
     #[test]
-    fn test_view_arbitrary() {
+    fn test_look_at_arbitrary() {
         let from = Tuple::point(1., 3., 2.);
         let to = Tuple::point(4., -2., 8.);
         let up = Tuple::vector(1., 1., 0.);
-        let view = view(from, to, up);
-        let expected_value = [
+        let view = look_at(from, to, up);
+        let expected_value = Matrix4::from([
             [-0.50709, 0.50709, 0.67612, -2.36643],
             [0.76772, 0.60609, 0.12122, -2.82843],
             [-0.35857, 0.59761, -0.71714, 0.00000],
             [0.00000, 0.00000, 0.00000, 1.00000],
-        ];
+        ]);
         assert!(view.is_equal(expected_value));
     }
 }
@@ -0,0 +1,318 @@
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{float, material, matrix, object, ray, tuple};
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, IntersectionBuffer};
+use crate::material::Material;
+use crate::matrix::{Matrix4, Matrix4Methods};
+use crate::shape::Shape;
+use crate::tuple::{Tuple, TupleMethods};
+
+// A ray can cross a triangle at most once.
+pub const MAX_INTERSECTIONS: usize = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Triangle {
+    pub transform: matrix::Matrix4,
+    pub inverse_transform: matrix::Matrix4,
+    pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    pub normal: Tuple,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple, transform: Matrix4, material: Material) -> Triangle {
+        let e1 = p2.subtract(p1);
+        let e2 = p3.subtract(p1);
+        Triangle {
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+            material,
+            id: object::next_id(),
+            visible: true,
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal: e2.cross(e1).normalize(),
+        }
+    }
+}
+
+// A triangle whose normal is interpolated across its face from the
+// normals at each of its vertices.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SmoothTriangle {
+    pub transform: matrix::Matrix4,
+    pub inverse_transform: matrix::Matrix4,
+    pub material: material::Material,
+    #[serde(skip, default = "crate::object::next_id")]
+    pub id: u64,
+    pub visible: bool,
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub n1: Tuple,
+    pub n2: Tuple,
+    pub n3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+}
+
+impl SmoothTriangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple,
+               n1: Tuple, n2: Tuple, n3: Tuple,
+               transform: Matrix4, material: Material) -> SmoothTriangle {
+        SmoothTriangle {
+            transform,
+            inverse_transform: transform.inverse().unwrap(),
+            material,
+            id: object::next_id(),
+            visible: true,
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1: p2.subtract(p1),
+            e2: p3.subtract(p1),
+        }
+    }
+
+    // Computes the barycentric coordinates of a point on the triangle,
+    // where u weights the second vertex and v weights the third.
+    fn barycentric(&self, local_point: Tuple) -> (f64, f64) {
+        let p1_to_point = local_point.subtract(self.p1);
+        let d11 = self.e1.dot(self.e1);
+        let d12 = self.e1.dot(self.e2);
+        let d22 = self.e2.dot(self.e2);
+        let dp1 = p1_to_point.dot(self.e1);
+        let dp2 = p1_to_point.dot(self.e2);
+        let denominator = d11*d22 - d12*d12;
+        let u = (d22*dp1 - d12*dp2) / denominator;
+        let v = (d11*dp2 - d12*dp1) / denominator;
+        (u, v)
+    }
+}
+
+// This is the Möller–Trumbore algorithm, shared by both kinds of triangles.
+pub(crate) fn intersect_triangle(p1: Tuple, e1: Tuple, e2: Tuple, local_ray: &ray::Ray) -> Option<f64> {
+    let direction_cross_e2 = local_ray.direction.cross(e2);
+    let determinant = e1.dot(direction_cross_e2);
+    if determinant.abs() < float::EPSILON {
+        // The ray is parallel to the plane of the triangle
+        return None;
+    }
+
+    let f = 1.0 / determinant;
+    let p1_to_origin = local_ray.origin.subtract(p1);
+    let u = f * p1_to_origin.dot(direction_cross_e2);
+    if !(0. ..=1.).contains(&u) {
+        // The ray misses the p1-p3 edge
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * local_ray.direction.dot(origin_cross_e1);
+    if v < 0. || (u + v) > 1. {
+        // The ray misses the p1-p2 or p2-p3 edge
+        return None;
+    }
+
+    Some(f * e2.dot(origin_cross_e1))
+}
+
+// Picks a point in the parallelogram spanned by the two edges, folding it
+// back over the diagonal if it lands in the half outside the triangle.
+pub(crate) fn random_point_on_triangle(p1: Tuple, e1: Tuple, e2: Tuple, rng: &mut dyn RngCore) -> Tuple {
+    let mut u = rng.random::<f64>();
+    let mut v = rng.random::<f64>();
+    if u + v > 1. {
+        u = 1. - u;
+        v = 1. - v;
+    }
+    p1.add(e1.multiply(u)).add(e2.multiply(v))
+}
+
+impl Shape for Triangle {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        if let Some(t) = intersect_triangle(self.p1, self.e1, self.e2, local_ray) {
+            intersections.push(Intersection::new(t, object_id));
+        }
+    }
+
+    fn normal_at(&self, _local_point: tuple::Tuple) -> tuple::Tuple {
+        self.normal
+    }
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for &p in [self.p1, self.p2, self.p3].iter() {
+            bounds.add_point(p);
+        }
+        bounds
+    }
+
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        random_point_on_triangle(self.p1, self.e1, self.e2, rng)
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn intersect(&self, local_ray: &ray::Ray, object_id: u64, intersections: &mut dyn IntersectionBuffer) {
+        if let Some(t) = intersect_triangle(self.p1, self.e1, self.e2, local_ray) {
+            intersections.push(Intersection::new(t, object_id));
+        }
+    }
+
+    fn normal_at(&self, local_point: tuple::Tuple) -> tuple::Tuple {
+        let (u, v) = self.barycentric(local_point);
+        self.n2.multiply(u)
+            .add(self.n3.multiply(v))
+            .add(self.n1.multiply(1. - u - v))
+    }
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for &p in [self.p1, self.p2, self.p3].iter() {
+            bounds.add_point(p);
+        }
+        bounds
+    }
+
+    fn random_point(&self, rng: &mut dyn RngCore) -> tuple::Tuple {
+        random_point_on_triangle(self.p1, self.e1, self.e2, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::{float, material, matrix};
+    use crate::ray::Ray;
+    use crate::shape::{intersect_ts, Shape};
+    use crate::triangle::{SmoothTriangle, Triangle};
+    use crate::tuple::{Tuple, TupleMethods};
+
+    fn test_triangle() -> Triangle {
+        Triangle::new(
+            Tuple::point(0., 1., 0.),
+            Tuple::point(-1., 0., 0.),
+            Tuple::point(1., 0., 0.),
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        )
+    }
+
+    fn test_smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            Tuple::point(0., 1., 0.),
+            Tuple::point(-1., 0., 0.),
+            Tuple::point(1., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+            Tuple::vector(-1., 0., 0.),
+            Tuple::vector(1., 0., 0.),
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        )
+    }
+
+    #[test]
+    fn test_new() {
+        let triangle = test_triangle();
+        assert!(triangle.e1.is_equal(Tuple::vector(-1., -1., 0.)));
+        assert!(triangle.e2.is_equal(Tuple::vector(1., -1., 0.)));
+        assert!(triangle.normal.is_equal(Tuple::vector(0., 0., -1.)));
+    }
+
+    #[test]
+    fn test_normal_at() {
+        let triangle = test_triangle();
+        let test_cases = vec![
+            Tuple::point(0., 0.5, 0.),
+            Tuple::point(-0.5, 0.75, 0.),
+            Tuple::point(0.5, 0.25, 0.),
+        ];
+        for point in test_cases {
+            assert!(triangle.normal_at(point).is_equal(triangle.normal));
+        }
+    }
+
+    #[test]
+    fn test_intersect_misses() {
+        let triangle = test_triangle();
+        let test_cases = vec![
+            // Parallel to the triangle
+            (Tuple::point(0., -1., -2.), Tuple::vector(0., 1., 0.)),
+            // Misses the p1-p3 edge
+            (Tuple::point(1., 1., -2.), Tuple::vector(0., 0., 1.)),
+            // Misses the p1-p2 edge
+            (Tuple::point(-1., 1., -2.), Tuple::vector(0., 0., 1.)),
+            // Misses the p2-p3 edge
+            (Tuple::point(0., -1., -2.), Tuple::vector(0., 0., 1.)),
+        ];
+        for (origin, direction) in test_cases {
+            let ray = Ray::new(origin, direction);
+            assert_eq!(intersect_ts(&triangle, &ray).len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_intersect_hits() {
+        let triangle = test_triangle();
+        let ray = Ray::new(
+            Tuple::point(0., 0.5, -2.),
+            Tuple::vector(0., 0., 1.),
+        );
+        let ts = intersect_ts(&triangle, &ray);
+        assert_eq!(ts.len(), 1);
+        assert!(float::is_equal(ts[0], 2.));
+    }
+
+    #[test]
+    fn test_smooth_normal_at_interpolates() {
+        let triangle = test_smooth_triangle();
+        let normal = triangle.normal_at(Tuple::point(-0.2, 0.3, 0.));
+        assert!(normal.is_equal(Tuple::vector(-0.2, 0.3, 0.)));
+    }
+
+    #[test]
+    fn test_smooth_normal_at_vertices() {
+        let triangle = test_smooth_triangle();
+        assert!(triangle.normal_at(triangle.p1).is_equal(triangle.n1));
+        assert!(triangle.normal_at(triangle.p2).is_equal(triangle.n2));
+        assert!(triangle.normal_at(triangle.p3).is_equal(triangle.n3));
+    }
+
+    #[test]
+    fn test_random_point() {
+        let triangle = test_triangle();
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let point = triangle.random_point(&mut rng);
+            assert!(float::is_equal(point[2], 0.));
+            assert!(point[1] >= 0.);
+            assert!(point[0].abs() <= 1. - point[1] + float::EPSILON);
+        }
+    }
+}
@@ -2,10 +2,97 @@ use crate::float;
 
 pub type Tuple = [f64; 4];
 
+// The hottest arithmetic is implemented twice: once in plain scalar code and
+// once with portable SIMD, which requires a nightly compiler and is enabled
+// with the `simd` feature. Both produce bit for bit identical results.
+#[cfg(not(feature = "simd"))]
+use self::scalar as arithmetic;
+#[cfg(feature = "simd")]
+use self::simd as arithmetic;
+
+#[cfg_attr(feature = "simd", allow(dead_code))]
+mod scalar {
+    use super::Tuple;
+
+    pub fn add(a: &Tuple, b: Tuple) -> Tuple {
+        [
+            a[0]+b[0],
+            a[1]+b[1],
+            a[2]+b[2],
+            a[3]+b[3]
+        ]
+    }
+
+    pub fn subtract(a: &Tuple, b: Tuple) -> Tuple {
+        [
+            a[0]-b[0],
+            a[1]-b[1],
+            a[2]-b[2],
+            a[3]-b[3]
+        ]
+    }
+
+    pub fn multiply(a: &Tuple, s: f64) -> Tuple {
+        [s*a[0], s*a[1], s*a[2], s*a[3]]
+    }
+
+    pub fn magnitude(a: &Tuple) -> f64 {
+        (a[0]*a[0] + a[1]*a[1] + a[2]*a[2]).sqrt()
+    }
+
+    pub fn dot(a: &Tuple, b: Tuple) -> f64 {
+        a[0]*b[0] + a[1]*b[1] + a[2]*b[2] + a[3]*b[3]
+    }
+
+    pub fn normalize(a: &Tuple) -> Tuple {
+        let m = magnitude(a);
+        [a[0]/m, a[1]/m, a[2]/m, a[3]/m]
+    }
+}
+
+#[cfg(feature = "simd")]
+mod simd {
+    use std::simd::f64x4;
+    use super::Tuple;
+
+    pub fn add(a: &Tuple, b: Tuple) -> Tuple {
+        (f64x4::from_array(*a) + f64x4::from_array(b)).to_array()
+    }
+
+    pub fn subtract(a: &Tuple, b: Tuple) -> Tuple {
+        (f64x4::from_array(*a) - f64x4::from_array(b)).to_array()
+    }
+
+    pub fn multiply(a: &Tuple, s: f64) -> Tuple {
+        (f64x4::splat(s) * f64x4::from_array(*a)).to_array()
+    }
+
+    // The products are summed in the same order as the scalar code, rather
+    // than with a horizontal reduction, so that rounding is identical.
+    pub fn magnitude(a: &Tuple) -> f64 {
+        let v = f64x4::from_array(*a);
+        let p = (v * v).to_array();
+        (p[0] + p[1] + p[2]).sqrt()
+    }
+
+    pub fn dot(a: &Tuple, b: Tuple) -> f64 {
+        let p = (f64x4::from_array(*a) * f64x4::from_array(b)).to_array();
+        p[0] + p[1] + p[2] + p[3]
+    }
+
+    pub fn normalize(a: &Tuple) -> Tuple {
+        (f64x4::from_array(*a) / f64x4::splat(magnitude(a))).to_array()
+    }
+}
+
 pub trait TupleMethods {
     fn new(x: f64, y: f64, z: f64, w: f64) -> Tuple;
     fn point(x: f64, y: f64, z: f64) -> Tuple;
     fn vector(x: f64, y: f64, z: f64) -> Tuple;
+    fn is_point(&self) -> bool;
+    fn is_vector(&self) -> bool;
+    fn assert_is_point(&self);
+    fn assert_is_vector(&self);
     fn is_equal(&self, other: Tuple) -> bool;
     fn add(&self, other: Tuple) -> Tuple;
     fn subtract(&self, other: Tuple) -> Tuple;
@@ -17,6 +104,8 @@ pub trait TupleMethods {
     fn normalize(&self) -> Tuple;
     fn cross(&self, other: Tuple) -> Tuple;
     fn reflect(&self, normal: Tuple) -> Tuple;
+    fn project_onto_plane(&self, plane_normal: Tuple) -> Tuple;
+    fn reflect_across_plane(&self, plane_normal: Tuple) -> Tuple;
 }
 
 impl TupleMethods for Tuple {
@@ -32,6 +121,24 @@ impl TupleMethods for Tuple {
         [x, y, z, 0.]
     }
 
+    fn is_point(&self) -> bool {
+        float::is_equal(self[3], 1.0)
+    }
+
+    fn is_vector(&self) -> bool {
+        float::is_equal(self[3], 0.0)
+    }
+
+    // These are only checked in debug builds, to catch mistakes like adding
+    // two points together without slowing down release renders.
+    fn assert_is_point(&self) {
+        debug_assert!(self.is_point(), "expected a point (w = 1) but got {:?}", self);
+    }
+
+    fn assert_is_vector(&self) {
+        debug_assert!(self.is_vector(), "expected a vector (w = 0) but got {:?}", self);
+    }
+
     fn is_equal(&self, other: Tuple) -> bool {
         float::is_equal(self[0], other[0]) &&
             float::is_equal(self[1], other[1]) &&
@@ -40,25 +147,15 @@ impl TupleMethods for Tuple {
     }
 
     fn add(&self, other: Tuple) -> Tuple {
-        [
-            self[0]+other[0],
-            self[1]+other[1],
-            self[2]+other[2],
-            self[3]+other[3]
-        ]
+        arithmetic::add(self, other)
     }
 
     fn subtract(&self, other: Tuple) -> Tuple {
-        [
-            self[0]-other[0],
-            self[1]-other[1],
-            self[2]-other[2],
-            self[3]-other[3]
-        ]
+        arithmetic::subtract(self, other)
     }
 
     fn multiply(&self, s: f64) -> Tuple {
-        [s*self[0], s*self[1], s*self[2], s*self[3]]
+        arithmetic::multiply(self, s)
     }
 
     fn negate(&self) -> Tuple {
@@ -70,15 +167,15 @@ impl TupleMethods for Tuple {
     }
 
     fn magnitude(&self) -> f64 {
-        (self[0]*self[0] + self[1]*self[1] + self[2]*self[2]).sqrt()
+        arithmetic::magnitude(self)
     }
 
     fn dot(&self, other: Tuple) -> f64 {
-        self[0]*other[0] + self[1]*other[1] + self[2]*other[2] + self[3]*other[3]
+        arithmetic::dot(self, other)
     }
 
     fn normalize(&self) -> Tuple {
-        self.divide(self.magnitude())
+        arithmetic::normalize(self)
     }
 
     fn cross(&self, other: Tuple) -> Tuple {
@@ -93,6 +190,18 @@ impl TupleMethods for Tuple {
     fn reflect(&self, normal: Tuple) -> Tuple {
         self.subtract(normal.multiply(2. * self.dot(normal)))
     }
+
+    // The part of the vector lying in the plane with the given unit normal,
+    // with the part along the normal taken away.
+    fn project_onto_plane(&self, plane_normal: Tuple) -> Tuple {
+        self.subtract(plane_normal.multiply(self.dot(plane_normal)))
+    }
+
+    // The mirror image of the vector in the plane with the given unit
+    // normal, which works out the same as reflect.
+    fn reflect_across_plane(&self, plane_normal: Tuple) -> Tuple {
+        self.project_onto_plane(plane_normal).multiply(2.).subtract(*self)
+    }
 }
 
 #[cfg(test)]
@@ -108,6 +217,42 @@ mod tests {
         assert!([1., 2., 3., 0.].is_equal([1.000005, 1.999995, 2.999995, 0.]));
     }
 
+    #[test]
+    fn test_is_point_and_is_vector() {
+        let p = Tuple::point(4., -4., 3.);
+        let v = Tuple::vector(4., -4., 3.);
+        assert!(p.is_point());
+        assert!(!p.is_vector());
+        assert!(v.is_vector());
+        assert!(!v.is_point());
+
+        let sum_of_points = p.add(p);
+        assert!(!sum_of_points.is_point());
+        assert!(!sum_of_points.is_vector());
+        assert!(p.subtract(p).is_vector());
+    }
+
+    #[test]
+    fn test_assert_is_point_and_is_vector() {
+        Tuple::point(1., 2., 3.).assert_is_point();
+        Tuple::vector(1., 2., 3.).assert_is_vector();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "expected a point")]
+    fn test_assert_is_point_panics_for_vector() {
+        Tuple::vector(1., 2., 3.).assert_is_point();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "expected a vector")]
+    fn test_assert_is_vector_panics_for_sum_of_points() {
+        let p = Tuple::point(1., 2., 3.);
+        p.add(p).assert_is_vector();
+    }
+
     #[test]
     fn test_add() {
         let t1: Tuple = [3., -2., 5., 1.];
@@ -198,4 +343,81 @@ mod tests {
         let reflected = incident.reflect(normal);
         assert!(reflected.is_equal(Tuple::vector(1., 0., 0.)));
     }
+
+    #[test]
+    fn test_project_onto_plane() {
+        let normal = Tuple::vector(0., 1., 0.);
+        let test_cases = vec![
+            (Tuple::vector(1., -1., 0.), Tuple::vector(1., 0., 0.)),
+            (Tuple::vector(2., 3., -4.), Tuple::vector(2., 0., -4.)),
+            (Tuple::vector(0., 5., 0.), Tuple::vector(0., 0., 0.)),
+        ];
+        for (vector, expected_value) in test_cases {
+            let projected = vector.project_onto_plane(normal);
+            assert!(projected.is_equal(expected_value));
+            assert!(float::is_equal(projected.dot(normal), 0.));
+            let perpendicular = normal.multiply(vector.dot(normal));
+            assert!(projected.add(perpendicular).is_equal(vector));
+        }
+    }
+
+    #[test]
+    fn test_reflect_across_plane() {
+        let normal = Tuple::vector(2_f64.sqrt()/2., 2_f64.sqrt()/2., 0.);
+        let vectors = vec![
+            Tuple::vector(0., -1., 0.),
+            Tuple::vector(1., -1., 0.),
+            Tuple::vector(0.3, 0.2, -0.9),
+        ];
+        for vector in vectors {
+            let reflected = vector.reflect_across_plane(normal);
+            assert!(reflected.is_equal(vector.reflect(normal)));
+            assert!(float::is_equal(reflected.magnitude(), vector.magnitude()));
+        }
+        let reflected = Tuple::vector(0., -1., 0.).reflect_across_plane(normal);
+        assert!(reflected.is_equal(Tuple::vector(1., 0., 0.)));
+    }
+
+    #[cfg(feature = "simd")]
+    mod simd_matches_scalar {
+        use proptest::prelude::*;
+        use crate::tuple::{scalar, simd};
+
+        fn tuple() -> impl Strategy<Value = [f64; 4]> {
+            prop::array::uniform4(-1000.0..1000.0f64)
+        }
+
+        proptest! {
+            #[test]
+            fn test_add(a in tuple(), b in tuple()) {
+                prop_assert_eq!(simd::add(&a, b), scalar::add(&a, b));
+            }
+
+            #[test]
+            fn test_subtract(a in tuple(), b in tuple()) {
+                prop_assert_eq!(simd::subtract(&a, b), scalar::subtract(&a, b));
+            }
+
+            #[test]
+            fn test_multiply(a in tuple(), s in -1000.0..1000.0f64) {
+                prop_assert_eq!(simd::multiply(&a, s), scalar::multiply(&a, s));
+            }
+
+            #[test]
+            fn test_dot(a in tuple(), b in tuple()) {
+                prop_assert_eq!(simd::dot(&a, b), scalar::dot(&a, b));
+            }
+
+            #[test]
+            fn test_magnitude(a in tuple()) {
+                prop_assert_eq!(simd::magnitude(&a), scalar::magnitude(&a));
+            }
+
+            #[test]
+            fn test_normalize(a in tuple()) {
+                prop_assume!(scalar::magnitude(&a) > 0.);
+                prop_assert_eq!(simd::normalize(&a), scalar::normalize(&a));
+            }
+        }
+    }
 }
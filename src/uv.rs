@@ -0,0 +1,127 @@
+use std::f64::consts::PI;
+
+use crate::tuple::{Tuple, TupleMethods};
+
+// These functions map a point in object space onto the unit square of
+// texture coordinates, with u running across the surface and v up it.
+
+pub fn spherical_map(point: Tuple) -> (f64, f64) {
+    // theta is the azimuth around the y axis, and phi is the angle
+    // measured down from the +y pole.
+    let radius = Tuple::vector(point[0], point[1], point[2]).magnitude();
+    let theta = point[0].atan2(point[2]);
+    let phi = (point[1] / radius).clamp(-1., 1.).acos();
+    let u = 1. - (theta / (2. * PI) + 0.5);
+    let v = 1. - phi / PI;
+    (u, v)
+}
+
+pub fn planar_map(point: Tuple) -> (f64, f64) {
+    (point[0].rem_euclid(1.), point[2].rem_euclid(1.))
+}
+
+pub fn cylindrical_map(point: Tuple) -> (f64, f64) {
+    let theta = point[0].atan2(point[2]);
+    let u = 1. - (theta / (2. * PI) + 0.5);
+    let v = point[1].rem_euclid(1.);
+    (u, v)
+}
+
+// Each face of the cube gets the whole unit square to itself, oriented as
+// if looking straight at that face from outside the cube.
+pub fn cube_map(point: Tuple) -> (f64, f64) {
+    let (x, y, z) = (point[0], point[1], point[2]);
+    let coord = x.abs().max(y.abs()).max(z.abs());
+    if coord == x {
+        // right
+        ((1. - z).rem_euclid(2.) / 2., (y + 1.).rem_euclid(2.) / 2.)
+    } else if coord == -x {
+        // left
+        ((z + 1.).rem_euclid(2.) / 2., (y + 1.).rem_euclid(2.) / 2.)
+    } else if coord == y {
+        // up
+        ((x + 1.).rem_euclid(2.) / 2., (1. - z).rem_euclid(2.) / 2.)
+    } else if coord == -y {
+        // down
+        ((x + 1.).rem_euclid(2.) / 2., (z + 1.).rem_euclid(2.) / 2.)
+    } else if coord == z {
+        // front
+        ((x + 1.).rem_euclid(2.) / 2., (y + 1.).rem_euclid(2.) / 2.)
+    } else {
+        // back
+        ((1. - x).rem_euclid(2.) / 2., (y + 1.).rem_euclid(2.) / 2.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+    use crate::float;
+    use super::*;
+
+    fn assert_uv(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(float::is_equal(actual.0, expected.0) && float::is_equal(actual.1, expected.1),
+                "expected {:?} but got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn test_spherical_map() {
+        let cases = vec![
+            (Tuple::point(0., 0., -1.), (0.0, 0.5)),
+            (Tuple::point(1., 0., 0.), (0.25, 0.5)),
+            (Tuple::point(0., 0., 1.), (0.5, 0.5)),
+            (Tuple::point(-1., 0., 0.), (0.75, 0.5)),
+            (Tuple::point(0., 1., 0.), (0.5, 1.0)),
+            (Tuple::point(0., -1., 0.), (0.5, 0.0)),
+            (Tuple::point(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.), (0.25, 0.75)),
+        ];
+        for (point, expected) in cases {
+            assert_uv(spherical_map(point), expected);
+        }
+    }
+
+    #[test]
+    fn test_planar_map() {
+        let cases = vec![
+            (Tuple::point(0.25, 0., 0.5), (0.25, 0.5)),
+            (Tuple::point(0.25, 0., -0.25), (0.25, 0.75)),
+            (Tuple::point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (Tuple::point(1.25, 0., 0.5), (0.25, 0.5)),
+            (Tuple::point(-0.25, 0., -1.75), (0.75, 0.25)),
+        ];
+        for (point, expected) in cases {
+            assert_uv(planar_map(point), expected);
+        }
+    }
+
+    #[test]
+    fn test_cylindrical_map() {
+        let cases = vec![
+            (Tuple::point(0., 0., -1.), (0.0, 0.0)),
+            (Tuple::point(0., 0.5, -1.), (0.0, 0.5)),
+            (Tuple::point(0., 1., -1.), (0.0, 0.0)),
+            (Tuple::point(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), (0.125, 0.5)),
+            (Tuple::point(1., 0.5, 0.), (0.25, 0.5)),
+            (Tuple::point(-1., 1.25, 0.), (0.75, 0.25)),
+        ];
+        for (point, expected) in cases {
+            assert_uv(cylindrical_map(point), expected);
+        }
+    }
+
+    #[test]
+    fn test_cube_map() {
+        let cases = vec![
+            (Tuple::point(-0.5, 0.5, 1.), (0.25, 0.75)),
+            (Tuple::point(0.5, -0.5, 1.), (0.75, 0.25)),
+            (Tuple::point(0.5, 0.5, -1.), (0.25, 0.75)),
+            (Tuple::point(-1., 0.5, -0.5), (0.25, 0.75)),
+            (Tuple::point(1., 0.5, 0.5), (0.25, 0.75)),
+            (Tuple::point(-0.5, 1., -0.5), (0.25, 0.75)),
+            (Tuple::point(-0.5, -1., 0.5), (0.25, 0.75)),
+        ];
+        for (point, expected) in cases {
+            assert_uv(cube_map(point), expected);
+        }
+    }
+}
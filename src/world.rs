@@ -1,10 +1,20 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
 use crate::color::Color;
-use crate::intersection::{Computations, Intersection};
+use crate::camera::Camera;
+use crate::hook::{EventLog, NoHook, RayKind, RenderHook};
+use crate::intersection::{Computations, Intersections};
 use crate::{color, intersection, light};
-use crate::light::Light;
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use crate::light::{EnvironmentLight, Light};
 use crate::object::Object;
 use crate::ray;
 use crate::ray::Ray;
+use crate::sky::PhysicalSky;
 use crate::tuple::{Tuple, TupleMethods};
 
 pub fn schlick_reflectance_helper(n1: f64, n2: f64, cosine_of_angle: f64) -> f64 {
@@ -28,28 +38,342 @@ pub fn schlick_reflectance(computations: Computations) -> f64 {
     }
 }
 
+// What a ray sees when it misses everything in the scene. Only an
+// environment also lights the objects in the scene; the others are only
+// seen, lighting nothing.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Background {
+    Color(Color),
+    // Blends from one color looking straight down to the other looking
+    // straight up.
+    Gradient { bottom: Color, top: Color },
+    Environment(Box<EnvironmentLight>),
+    PhysicalSky(PhysicalSky),
+}
+
+impl Background {
+    pub fn sample(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Color(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = (ray.direction.normalize()[1] + 1.) / 2.;
+                bottom.multiply(1. - t).add(top.multiply(t))
+            },
+            Background::Environment(environment) => environment.sample_direction(ray.direction),
+            Background::PhysicalSky(sky) => sky.sample_direction(ray.direction),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct World {
     pub light: light::Light,
-    pub objects: Vec<Object>,
+    objects: Vec<Object>,
+    pub background: Background,
+    // Added to every shaded point regardless of its material, so that
+    // nothing in the scene ends up completely black.
+    #[serde(default = "default_ambient")]
+    pub ambient: Color,
+    // Filled in by precompute, and thrown away whenever the objects change.
+    #[serde(skip)]
+    bounds: Vec<BoundingBox>,
+    #[serde(skip)]
+    precomputed: bool,
+    #[serde(skip)]
+    bvh: Option<Bvh>,
+    // Finds objects by ID for shading, since every hit needs its object.
+    // Built when first needed, and thrown away whenever the objects change.
+    #[serde(skip)]
+    index: OnceLock<HashMap<u64, ObjectPath>>,
 }
 
 pub const MAX_RECURSIONS: usize = 5;
 
+fn default_ambient() -> Color {
+    color::BLACK
+}
+
+// Where an object sits in the world: its index among the objects placed
+// directly in the world, followed by its index among the children of each
+// group it's nested in.
+type ObjectPath = Vec<usize>;
+
+// Adds the object and everything inside it to the index, refusing objects
+// whose IDs are already taken, since rays would hit one and be shaded as
+// the other.
+fn index_object(index: &mut HashMap<u64, ObjectPath>, object: &Object, path: ObjectPath) {
+    if let Object::Group(group) = object {
+        for (child_index, child) in group.children.iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(child_index);
+            index_object(index, child, child_path);
+        }
+    }
+    let id = object.get_id();
+    assert!(index.insert(id, path).is_none(), "more than one object in the world has the ID {}", id);
+}
+
+fn build_index(objects: &[Object]) -> HashMap<u64, ObjectPath> {
+    let mut index = HashMap::new();
+    for (position, object) in objects.iter().enumerate() {
+        index_object(&mut index, object, vec![position]);
+    }
+    index
+}
+
 impl World {
     pub fn new(light: Light, objects: Vec<Object>) -> World {
+        let index = OnceLock::from(build_index(&objects));
         World {
             light: light,
             objects: objects,
+            background: Background::Color(color::BLACK),
+            ambient: color::BLACK,
+            bounds: vec![],
+            precomputed: false,
+            bvh: None,
+            index,
+        }
+    }
+
+    // A starting point for building up a scene, with nothing in it but a
+    // dim light and a black background.
+    pub fn empty() -> World {
+        let light = Light::new(
+            Tuple::point(-10., 10., -10.),
+            Color::new(0.5, 0.5, 0.5),
+        );
+        World::new(light, vec![])
+    }
+
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_background_color(mut self, color: Color) -> Self {
+        self.background = Background::Color(color);
+        self
+    }
+
+    pub fn with_ambient(mut self, ambient: Color) -> Self {
+        self.ambient = ambient;
+        self
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item=&Object> {
+        self.objects.iter()
+    }
+
+    pub fn objects_mut(&mut self) -> impl Iterator<Item=&mut Object> {
+        self.invalidate_precomputation();
+        self.invalidate_index();
+        self.objects.iter_mut()
+    }
+
+    pub fn add_object(&mut self, object: Object) {
+        self.invalidate_precomputation();
+        let position = self.objects.len();
+        // The index has to cover the objects already here before the new
+        // one can be added to it.
+        self.index();
+        index_object(self.index.get_mut().unwrap(), &object, vec![position]);
+        self.objects.push(object);
+    }
+
+    // Takes the object with the given ID out of the world, if it's there,
+    // looking inside groups the same way get_object does.
+    pub fn remove_object(&mut self, id: u64) -> Option<Object> {
+        let path = self.index().get(&id)?.clone();
+        self.invalidate_precomputation();
+        self.invalidate_index();
+        let (&last, parent_path) = path.split_last().unwrap();
+        let Some((&first, rest)) = parent_path.split_first() else {
+            return Some(self.objects.remove(last));
+        };
+        let mut parent = &mut self.objects[first];
+        for &child_index in rest {
+            parent = match parent {
+                Object::Group(group) => &mut group.children[child_index],
+                _ => unreachable!("only groups have children"),
+            };
+        }
+        match parent {
+            Object::Group(group) => Some(group.children.remove(last)),
+            _ => unreachable!("only groups have children"),
+        }
+    }
+
+    // Finds the object with the given ID, looking inside groups as well,
+    // since it's their children that rays actually hit.
+    pub fn get_object(&self, id: u64) -> Option<&Object> {
+        let path = self.index().get(&id)?;
+        let mut object = &self.objects[path[0]];
+        for &child_index in &path[1..] {
+            object = match object {
+                Object::Group(group) => &group.children[child_index],
+                _ => unreachable!("only groups have children"),
+            };
+        }
+        Some(object)
+    }
+
+    pub fn contains_object(&self, id: u64) -> bool {
+        self.index().contains_key(&id)
+    }
+
+    pub fn get_object_mut(&mut self, id: u64) -> Option<&mut Object> {
+        let path = self.index().get(&id)?.clone();
+        self.invalidate_precomputation();
+        self.invalidate_index();
+        let mut object = &mut self.objects[path[0]];
+        for &child_index in &path[1..] {
+            object = match object {
+                Object::Group(group) => &mut group.children[child_index],
+                _ => unreachable!("only groups have children"),
+            };
+        }
+        Some(object)
+    }
+
+    pub fn clear_objects(&mut self) {
+        self.invalidate_precomputation();
+        self.invalidate_index();
+        self.objects.clear();
+    }
+
+    // Swaps in a whole new scene, keeping only the background.
+    pub fn reset(&mut self, light: Light, objects: Vec<Object>) {
+        self.invalidate_precomputation();
+        self.index = OnceLock::from(build_index(&objects));
+        self.light = light;
+        self.objects = objects;
+    }
+
+    // Does the expensive setup that only needs to happen once per scene:
+    // working out the world space bounds of every object, so that rays can
+    // skip objects they come nowhere near, ordering the objects from the
+    // largest to the smallest, since big objects get hit most often, and
+    // building the BVH. Calling it again does nothing until the objects
+    // change. Cameras only borrow the world, so they can't do this for you;
+    // call it once the scene is built and before rendering.
+    pub fn precompute(&mut self) {
+        if self.precomputed {
+            return;
         }
+        let mut objects: Vec<(Object, BoundingBox)> = self.objects
+            .drain(..)
+            .map(|object| {
+                let bounds = object.bounds();
+                (object, bounds)
+            })
+            .collect();
+        objects.sort_by(|(_, b1), (_, b2)| b2.volume().partial_cmp(&b1.volume()).unwrap());
+
+        let (objects, bounds) = objects.into_iter().unzip();
+        self.objects = objects;
+        self.bounds = bounds;
+        self.index = OnceLock::from(build_index(&self.objects));
+        self.build_bvh();
+        self.precomputed = true;
+    }
+
+    // Builds a bounding volume hierarchy over the objects, so that each ray
+    // only needs to be tested against the objects near its path. It's thrown
+    // away whenever objects are added, removed or changed.
+    pub fn build_bvh(&mut self) {
+        self.bvh = Some(Bvh::build(&self.objects));
     }
 
-    pub fn intersect(&self, ray: &ray::Ray) -> Vec<Intersection> {
-        let mut all_intersections: Vec<Intersection> = vec![];
-        for object in self.objects.iter() {
-            let mut intersections = object.intersect(&ray);
-            all_intersections.append(&mut intersections)
+    pub fn has_bvh(&self) -> bool {
+        self.bvh.is_some()
+    }
+
+    pub fn precompute_if_needed(&mut self) {
+        if !self.precomputed {
+            self.precompute();
         }
+    }
+
+    pub fn is_precomputed(&self) -> bool {
+        self.precomputed
+    }
+
+    fn invalidate_precomputation(&mut self) {
+        self.bounds.clear();
+        self.precomputed = false;
+        self.bvh = None;
+    }
+
+    fn index(&self) -> &HashMap<u64, ObjectPath> {
+        self.index.get_or_init(|| build_index(&self.objects))
+    }
+
+    fn invalidate_index(&mut self) {
+        self.index = OnceLock::new();
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // Fails for scenes containing instances, since shared shapes can't be
+    // written out.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(s: &str) -> Result<World, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    #[cfg(feature = "serde_yaml")]
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    #[cfg(feature = "serde_yaml")]
+    pub fn from_yaml(s: &str) -> Result<World, serde_yaml::Error> {
+        serde_yaml::from_str(s)
+    }
+
+    pub fn intersect(&self, ray: &ray::Ray) -> Intersections {
+        self.intersect_objects(ray, false)
+    }
 
+    // Every intersection of the ray with the visible objects, both in front
+    // of and behind its origin, sorted nearest first. Useful for working out
+    // which objects a point lies inside of.
+    pub fn trace_all(&self, ray: &ray::Ray) -> Vec<intersection::Intersection> {
+        self.intersect(ray).into_iter().collect()
+    }
+
+    fn intersect_objects(&self, ray: &ray::Ray, include_hidden: bool) -> Intersections {
+        let include = |object: &Object| include_hidden || object.is_visible();
+        let mut all_intersections = if let Some(bvh) = &self.bvh {
+            bvh.intersect(ray, &self.objects, include)
+        } else {
+            let mut all_intersections = Intersections::new();
+            for (index, object) in self.objects.iter().enumerate() {
+                if !include(object) {
+                    continue;
+                }
+                if self.precomputed && !self.bounds[index].intersects(ray) {
+                    continue;
+                }
+                object.extend_intersections(ray, &mut all_intersections);
+            }
+            all_intersections
+        };
+
+        // Degenerate geometry can produce nonsensical distances, which would
+        // otherwise poison the sort.
+        all_intersections.retain(|i| i.t.is_finite());
         all_intersections.sort_by(|i1, i2| i1.t.partial_cmp(&i2.t).unwrap());
         all_intersections
     }
@@ -59,85 +383,169 @@ impl World {
         let distance = light_to_point.magnitude();
         let direction = light_to_point.normalize();
         let ray = Ray::new(point, direction);
-        let mut intersections = self.intersect(&ray);
-        let hit = intersection::hit(&mut intersections);
-        match hit {
-            Some(h) => {
-                if h.t < distance {
-                    true
-                } else {
-                    false
-                }
-            }
-            None => false
+        // Hidden objects still block the light, but those whose material
+        // doesn't cast a shadow are looked straight through.
+        self.intersect_objects(&ray, true)
+            .iter()
+            .filter(|i| i.t >= 0. && i.t < distance)
+            .any(|i| i.object(self).get_material().casts_shadow)
+    }
+
+    // Same as is_shadowed, but reporting the shadow ray to a hook, which
+    // only gets it traced if it wants colors.
+    fn is_shadowed_with<H: RenderHook>(&self, point: Tuple, hook: &mut H) -> bool {
+        hook.on_ray_cast(RayKind::Shadow);
+        if !hook.wants_colors() {
+            return false;
+        }
+        let is_shadowed = self.is_shadowed(point);
+        hook.on_shadow_test(point, is_shadowed);
+        is_shadowed
+    }
+
+    // The diffuse light falling on a surface at the point, facing along the
+    // normal, from the point light and any environment, as if nothing in
+    // the scene cast a shadow.
+    pub fn illumination_at(&self, point: Tuple, normal: Tuple) -> Color {
+        let light_direction = self.light.position.subtract(point).normalize();
+        let light_dot_normal = light_direction.dot(normal).max(0.);
+        let direct = self.light.intensity_at(point).multiply(light_dot_normal);
+        match &self.background {
+            Background::Color(_) | Background::Gradient { .. } | Background::PhysicalSky(_) => direct,
+            Background::Environment(environment) => direct.add(environment.sample_direction(normal)),
         }
     }
 
     pub fn refracted_color(&self, computations: &Computations, remaining_reflections: usize) -> Color {
+        self.refracted_color_with(computations, remaining_reflections, &mut NoHook)
+    }
+
+    fn refracted_color_with<H: RenderHook>(&self, computations: &Computations, remaining_reflections: usize, hook: &mut H) -> Color {
         if remaining_reflections <= 0 {
             return color::BLACK
         }
 
-        if computations.object.get_material().transparency == 0.0 {
+        let transparency = computations.object(self).get_material().transparency;
+        if transparency == 0.0 {
             color::BLACK
         } else {
             // Find the ratio of first index of refraction to the second.
             // (Yup, this is inverted from the definition of Snell's Law.)
             let n_ratio = computations.n1 / computations.n2;
-            // cos(theta_i) is the same as the dot product of the two vectors
-            let cos_theta_i = computations.eye.dot(computations.normal);
-            // Find sin(theta_t)^2 via trigonometric identity
-            let sin2_theta_t = n_ratio * n_ratio * (1. - cos_theta_i*cos_theta_i);
-
-            if sin2_theta_t > 1. {
-                color::BLACK
-            } else {
-                // Find cos(theta_t) via trigonometric identity
-                let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
-                // Compute the direction of the refracted ray
-                let direction = computations.normal
-                    .multiply(n_ratio * cos_theta_i - cos_theta_t)
-                    .subtract(computations.eye.multiply(n_ratio));
-                // Create the refracted ray
-                let refracted_ray = Ray::new(computations.under_point, direction);
+            let refracted_ray = Ray::refracted(
+                computations.under_point,
+                computations.eye.negate(),
+                computations.normal,
+                n_ratio,
+            );
+            match refracted_ray {
+                // Total internal reflection
+                None => {
+                    hook.on_total_internal_reflection(computations);
+                    color::BLACK
+                },
                 // Find the color of the refracted ray, making sure to multiply
                 // by the transparency value to account for any opacity
-                self.color_at(&refracted_ray, remaining_reflections - 1)
-                    .multiply(computations.object.get_material().transparency)
+                Some(refracted_ray) => {
+                    hook.on_ray_cast(RayKind::Refracted);
+                    self.color_at_with(&refracted_ray, remaining_reflections - 1, hook)
+                        .multiply(transparency)
+                },
             }
         }
     }
 
     pub fn reflected_color(&self, computations: &Computations, remaining_reflections: usize) -> Color {
+        self.reflected_color_with(computations, remaining_reflections, &mut NoHook)
+    }
+
+    fn reflected_color_with<H: RenderHook>(&self, computations: &Computations, remaining_reflections: usize, hook: &mut H) -> Color {
         if remaining_reflections <= 0 {
             return color::BLACK
         }
 
-        if computations.object.get_material().reflective == 0.0 {
+        let reflective = computations.object(self).get_material().reflective;
+        if reflective == 0.0 {
             color::BLACK
         } else {
-            let reflected_ray = Ray::new(computations.over_point, computations.reflected);
-            let reflected_color = self.color_at(&reflected_ray, remaining_reflections-1);
-            reflected_color.multiply(computations.object.get_material().reflective)
+            let reflected_ray = Ray::reflected(
+                computations.over_point,
+                computations.eye.negate(),
+                computations.normal,
+            );
+            hook.on_ray_cast(RayKind::Reflected);
+            let reflected_color = self.color_at_with(&reflected_ray, remaining_reflections-1, hook);
+            reflected_color.multiply(reflective)
+        }
+    }
+
+    // What a ray that misses everything in the scene sees.
+    pub fn environment_color(&self, ray: &Ray) -> Color {
+        self.background.sample(ray)
+    }
+
+    // Diffuse light from the environment only reaches the surface if nothing
+    // else in the scene is in the way along the normal.
+    fn environment_diffuse<H: RenderHook>(&self, object: &Object, computations: &Computations, hook: &mut H) -> Color {
+        match &self.background {
+            Background::Color(_) | Background::Gradient { .. } | Background::PhysicalSky(_) => color::BLACK,
+            Background::Environment(environment) => {
+                hook.on_ray_cast(RayKind::Environment);
+                if !hook.wants_colors() {
+                    return color::BLACK;
+                }
+                let ray = Ray::new(computations.over_point, computations.normal);
+                let intersections = self.intersect(&ray);
+                match intersection::hit(&intersections) {
+                    Some(_) => color::BLACK,
+                    None => {
+                        let material = object.get_material();
+                        material
+                            .color_at(object, computations.point)
+                            .hadamard(environment.sample_direction(computations.normal))
+                            .multiply(material.diffuse)
+                    }
+                }
+            }
         }
     }
 
     pub fn shade_hit(&self, computations: Computations, remaining_reflections: usize) -> Color {
-        let is_shadowed = self.is_shadowed(computations.over_point);
+        self.shade_hit_with(computations, remaining_reflections, &mut NoHook)
+    }
 
-        let material = computations.object.get_material();
-        let surface_color = material.lighting(
-            &self.light,
-            computations.object,
-            computations.point,
-            computations.eye,
-            computations.normal,
-            is_shadowed,
-        );
-        let reflected_color = self.reflected_color(&computations, remaining_reflections);
-        let refracted_color = self.refracted_color(&computations, remaining_reflections);
+    // The light given off by the surface itself at a hit, from the point
+    // light and any environment, before reflection and refraction.
+    fn surface_color<H: RenderHook>(&self, object: &Object, computations: &Computations, is_shadowed: bool, hook: &mut H) -> Color {
+        let environment_diffuse = self.environment_diffuse(object, computations, hook);
+        if !hook.wants_colors() {
+            return color::BLACK;
+        }
+        object
+            .get_material()
+            .lighting(
+                &self.light,
+                object,
+                computations.point,
+                computations.eye,
+                computations.normal,
+                is_shadowed,
+            )
+            .add(environment_diffuse)
+    }
+
+    fn shade_hit_with<H: RenderHook>(&self, computations: Computations, remaining_reflections: usize, hook: &mut H) -> Color {
+        let is_shadowed = self.is_shadowed_with(computations.over_point, hook);
+        let object = computations.object(self);
+        let material = object.get_material();
+        let surface_color = self.surface_color(object, &computations, is_shadowed, hook);
+        let reflected_color = self.reflected_color_with(&computations, remaining_reflections, hook);
+        let refracted_color = self.refracted_color_with(&computations, remaining_reflections, hook);
+        if !hook.wants_colors() {
+            return color::BLACK;
+        }
 
-        if material.reflective > 0. && material.transparency > 0. {
+        let color = if material.reflective > 0. && material.transparency > 0. {
             let reflectance = schlick_reflectance(computations);
             surface_color
                 .add(reflected_color.multiply(reflectance))
@@ -146,31 +554,113 @@ impl World {
             surface_color
                 .add(reflected_color)
                 .add(refracted_color)
-        }
+        };
+        let color = color.add(self.ambient);
+        hook.on_shade(&computations, color);
+        color
+    }
+
+    // Renders a single pixel as the camera would, along with a log of every
+    // ray traced, shadow tested and color worked out on the way.
+    pub fn render_pixel(&self, camera: &Camera, x: usize, y: usize) -> (Color, Vec<String>) {
+        let mut log = EventLog::default();
+        let ray = camera.ray_at(x, y);
+        log.on_primary_ray(x, y, &ray);
+        let color = self.color_at_with(&ray, camera.max_reflections, &mut log);
+        (color, log.events)
     }
 
     pub fn color_at(&self, ray: &ray::Ray, remaining_reflections: usize) -> Color {
-        let mut intersections = self.intersect(ray);
-        // TODO: See if this can be avoided
-        let intersections_copy = intersections.clone();
-        let hit = intersection::hit(&mut intersections);
+        self.color_at_with(ray, remaining_reflections, &mut NoHook)
+    }
+
+    // Same as color_at, but reporting what happens along the way to a hook.
+    pub fn color_at_with<H: RenderHook>(&self, ray: &ray::Ray, remaining_reflections: usize, hook: &mut H) -> Color {
+        let intersections = self.intersect(ray);
+        let hit = intersection::hit(&intersections);
         match hit {
-            None => color::BLACK,
+            None => {
+                hook.on_miss(ray);
+                if !hook.wants_colors() {
+                    return color::BLACK;
+                }
+                self.environment_color(ray)
+            },
             Some(intersection) => {
-                let computations = intersection.prepare_computations(&ray, intersections_copy);
-                self.shade_hit(computations, remaining_reflections)
+                hook.on_hit(ray, intersection.t, intersection.object(self));
+                let computations = Computations::from_hit(intersection, ray, &intersections, self);
+                self.shade_hit_with(computations, remaining_reflections, hook)
             }
         }
     }
+
+    // Follows a single path of light back from the ray, for seeing how a
+    // color comes about one bounce at a time. Each hit records the ray that
+    // made it, what the surface there adds to the final color once dimmed
+    // by all the bounces before it, and the object hit. Where a surface
+    // both reflects and refracts, the path carries on whichever way more
+    // light comes from. The path ends when a ray escapes the scene, hits
+    // a surface that neither reflects nor refracts, or after max_depth
+    // bounces.
+    pub fn trace_path(&self, ray: &Ray, max_depth: usize) -> Vec<(Ray, Color, &Object)> {
+        let mut path = vec![];
+        let mut ray = ray.clone();
+        let mut throughput = 1.;
+        for depth in 0..=max_depth {
+            let intersections = self.intersect(&ray);
+            let Some(intersection) = intersection::hit(&intersections) else {
+                break;
+            };
+            let computations = Computations::from_hit(intersection, &ray, &intersections, self);
+            let object = computations.object(self);
+            let material = object.get_material();
+            let is_shadowed = self.is_shadowed(computations.over_point);
+            let contribution = self
+                .surface_color(object, &computations, is_shadowed, &mut NoHook)
+                .add(self.ambient)
+                .multiply(throughput);
+            path.push((ray.clone(), contribution, object));
+            if depth == max_depth {
+                break;
+            }
+
+            let refracted_ray = Ray::refracted(
+                computations.under_point,
+                computations.eye.negate(),
+                computations.normal,
+                computations.n1 / computations.n2,
+            );
+            ray = match refracted_ray {
+                Some(refracted_ray) if material.transparency > material.reflective => {
+                    throughput *= material.transparency;
+                    refracted_ray
+                },
+                _ if material.reflective > 0. => {
+                    throughput *= material.reflective;
+                    Ray::reflected(computations.over_point, computations.eye.negate(), computations.normal)
+                },
+                _ => break,
+            };
+        }
+        path
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{color, float, matrix, plane};
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+    use crate::{color, cylinder, examples, float, matrix, plane};
+    use crate::camera::Camera;
+    use crate::hook::NoHook;
+    use crate::canvas::Canvas;
     use crate::color::Color;
-    use crate::intersection::Intersection;
+    use crate::group::Group;
+    use crate::instance::Instanced;
+    use crate::intersection::{Computations, Intersection};
     use crate::light;
-    use crate::light::Light;
+    use crate::light::{EnvironmentLight, Light};
+    use crate::matrix::Matrix4Methods;
     use crate::material;
     use crate::material::Coloring::{SolidColor, SurfacePattern};
     use crate::object::Object;
@@ -179,9 +669,11 @@ mod tests {
     use crate::ray::Ray;
     use crate::sphere;
     use crate::transform;
+    use crate::triangle::Triangle;
     use crate::tuple;
     use crate::tuple::{Tuple, TupleMethods};
-    use crate::world::{MAX_RECURSIONS, schlick_reflectance, World};
+    use crate::sky::PhysicalSky;
+    use crate::world::{Background, MAX_RECURSIONS, schlick_reflectance, World};
 
     pub fn test_world() -> World {
         let light = light::Light::new(
@@ -199,6 +691,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -211,10 +704,7 @@ mod tests {
         );
 
         let objects = vec![s1, s2];
-        return World {
-            light: light,
-            objects: objects,
-        };
+        return World::new(light, objects);
     }
 
     #[test]
@@ -233,6 +723,510 @@ mod tests {
         assert_eq!(ts, [4., 4.5, 5.5, 6.]);
     }
 
+    #[test]
+    fn test_world_objects_accessors() {
+        let mut world = test_world();
+        assert_eq!(world.len(), 2);
+        assert!(!world.is_empty());
+        assert_eq!(world.objects().count(), 2);
+
+        for object in world.objects_mut() {
+            if let Object::Sphere(sphere) = object {
+                sphere.material.ambient = 1.0;
+            }
+        }
+        assert!(world.objects().all(|object| object.get_material().ambient == 1.0));
+
+        let light = world.light;
+        let empty_world = World::new(light, vec![]);
+        assert_eq!(empty_world.len(), 0);
+        assert!(empty_world.is_empty());
+    }
+
+    fn assert_identical_renders(world1: World, world2: World) {
+        let camera = Camera::look_at(
+            Tuple::point(0., 3., -5.),
+            Tuple::point(0., 1., 0.),
+            Tuple::vector(0., 1., 0.),
+            40, 20, PI/2.
+        );
+        let canvas1 = camera.render(&world1).unwrap();
+        let canvas2 = camera.render(&world2).unwrap();
+        for y in 0..20 {
+            for x in 0..40 {
+                let c1 = canvas1.get_pixel(x, y);
+                let c2 = canvas2.get_pixel(x, y);
+                assert!(c1.r == c2.r && c1.g == c2.g && c1.b == c2.b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let world = examples::chapter_twelve_scene();
+        let json = world.to_json().unwrap();
+        let deserialized_world = World::from_json(&json).unwrap();
+        assert_eq!(deserialized_world.len(), world.len());
+        assert_identical_renders(world, deserialized_world);
+    }
+
+    #[test]
+    fn test_json_round_trip_infinite_cylinder() {
+        let cylinder = Object::Cylinder(
+            cylinder::Cylinder::new_infinite(matrix::IDENTITY, material::DEFAULT_MATERIAL)
+        );
+        let world = World::new(test_world().light, vec![cylinder]);
+        let deserialized_world = World::from_json(&world.to_json().unwrap()).unwrap();
+        let object = deserialized_world.objects().next().unwrap();
+        match object {
+            Object::Cylinder(cylinder) => {
+                assert_eq!(cylinder.minimum, -f64::INFINITY);
+                assert_eq!(cylinder.maximum, f64::INFINITY);
+            },
+            _ => panic!("Expected a cylinder"),
+        }
+    }
+
+    #[test]
+    fn test_json_deserialized_objects_get_new_ids() {
+        let world = test_world();
+        let deserialized_world = World::from_json(&world.to_json().unwrap()).unwrap();
+        for (object, deserialized_object) in world.objects().zip(deserialized_world.objects()) {
+            assert!(!object.is_equal(deserialized_object));
+        }
+    }
+
+    #[test]
+    fn test_to_json_with_instances_fails() {
+        let shared = Arc::new(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let instance = Object::Instanced(Instanced::new(shared, matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let world = World::new(test_world().light, vec![instance]);
+        assert!(world.to_json().is_err());
+    }
+
+    #[test]
+    fn test_from_json_invalid() {
+        assert!(World::from_json("{\"light\": 42}").is_err());
+    }
+
+    #[cfg(feature = "serde_yaml")]
+    #[test]
+    fn test_yaml_round_trip() {
+        let world = examples::chapter_twelve_scene();
+        let yaml = world.to_yaml().unwrap();
+        let deserialized_world = World::from_yaml(&yaml).unwrap();
+        assert_identical_renders(world, deserialized_world);
+    }
+
+    #[test]
+    fn test_intersect_world_with_many_intersections() {
+        // Five spheres in a row give more intersections than fit inline
+        // in a small intersection list.
+        let objects = (0..5)
+            .map(|i| Object::Sphere(
+                sphere::Sphere::new(transform::translation(0., 0., 4. - 2. * i as f64), material::DEFAULT_MATERIAL)
+            ))
+            .collect();
+        let world = World::new(test_world().light, objects);
+        let ray = Ray::new(
+            Tuple::point(0., 0., -10.),
+            Tuple::vector(0., 0., 1.)
+        );
+        let ts: Vec<f64> = world.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [5., 7., 7., 9., 9., 11., 11., 13., 13., 15.]);
+    }
+
+    #[test]
+    fn test_clear_objects_then_add_object() {
+        let mut world = test_world();
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        let expected_value = world.color_at(&ray, MAX_RECURSIONS);
+
+        let objects: Vec<Object> = world.objects().cloned().collect();
+        world.clear_objects();
+        assert!(world.is_empty());
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), color::BLACK);
+
+        for object in objects {
+            world.add_object(object);
+        }
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), expected_value);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut world = World::empty().with_background_color(color::WHITE);
+        let other_world = test_world();
+        let objects = other_world.objects().cloned().collect();
+        world.reset(other_world.light, objects);
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.light.intensity, color::WHITE);
+        assert!(matches!(world.background, Background::Color(c) if c == color::WHITE));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn test_precompute_orders_objects_by_size() {
+        let mut world = World::empty();
+        world.add_object(Object::Sphere(sphere::Sphere::new(
+            transform::scaling(0.5, 0.5, 0.5),
+            material::DEFAULT_MATERIAL,
+        )));
+        world.add_object(Object::Plane(plane::Plane::new(matrix::IDENTITY, material::DEFAULT_MATERIAL)));
+        world.add_object(Object::Sphere(sphere::Sphere::new(
+            transform::scaling(2., 2., 2.),
+            material::DEFAULT_MATERIAL,
+        )));
+        let ids: Vec<u64> = world.objects().map(|o| o.get_id()).collect();
+
+        world.precompute();
+        assert!(world.is_precomputed());
+        let sorted_ids: Vec<u64> = world.objects().map(|o| o.get_id()).collect();
+        assert_eq!(sorted_ids, [ids[1], ids[2], ids[0]]);
+        world.precompute();
+        assert_eq!(world.objects().map(|o| o.get_id()).collect::<Vec<u64>>(), sorted_ids);
+
+        world.add_object(Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL)));
+        assert!(!world.is_precomputed());
+        world.precompute_if_needed();
+        assert!(world.is_precomputed());
+    }
+
+    #[test]
+    fn test_precompute_does_not_change_intersections() {
+        let mut world = examples::chapter_twelve_scene();
+        let rays = [
+            Ray::new(Tuple::point(0., 1.5, -5.), Tuple::vector(0., 0., 1.)),
+            Ray::new(Tuple::point(0., 1.5, -5.), Tuple::vector(0.3, -0.2, 1.).normalize()),
+            Ray::new(Tuple::point(0., 1.5, -5.), Tuple::vector(-0.5, 0.1, 1.).normalize()),
+            Ray::new(Tuple::point(0., 20., 0.), Tuple::vector(0., -1., 0.)),
+        ];
+        // Objects hit at exactly the same distance may swap places once the
+        // world has been reordered, so compare the hits in a fixed order.
+        let hits = |world: &World| -> Vec<Vec<(f64, u64)>> {
+            rays.iter()
+                .map(|ray| {
+                    let mut hits: Vec<(f64, u64)> = world.intersect(ray)
+                        .iter()
+                        .map(|i| (i.t, i.object_id))
+                        .collect();
+                    hits.sort_by(|h1, h2| h1.partial_cmp(h2).unwrap());
+                    hits
+                })
+                .collect()
+        };
+        let before = hits(&world);
+        world.precompute();
+        assert_eq!(hits(&world), before);
+    }
+
+    fn many_spheres_world() -> World {
+        let mut world = World::new(test_world().light, vec![]);
+        world.add_object(Object::Plane(plane::Plane::new(
+            transform::translation(0., -1., 0.),
+            material::DEFAULT_MATERIAL,
+        )));
+        for i in 0..500 {
+            let (col, row) = ((i % 25) as f64, (i / 25) as f64);
+            let mut material = material::Material::new();
+            material.color = SolidColor(Color::new(col / 25., row / 20., 0.5));
+            material.reflective = if i % 7 == 0 { 0.5 } else { 0. };
+            let t = transform::translation(col - 12., (col * row).sin(), row)
+                .multiply_matrix(transform::scaling(0.3, 0.3, 0.3));
+            world.add_object(Object::Sphere(sphere::Sphere::new(t, material)));
+        }
+        world
+    }
+
+    #[test]
+    fn test_build_bvh_renders_identically() {
+        let camera = Camera::look_at(
+            Tuple::point(0., 3., -8.),
+            Tuple::point(0., 0., 5.),
+            Tuple::vector(0., 1., 0.),
+            40, 20, PI/2.
+        );
+        let world = many_spheres_world();
+        let mut accelerated_world = many_spheres_world();
+        accelerated_world.build_bvh();
+        assert!(accelerated_world.has_bvh());
+
+        let canvas1 = camera.render_hooked(&world, &mut NoHook);
+        let canvas2 = camera.render_hooked(&accelerated_world, &mut NoHook);
+        for y in 0..20 {
+            for x in 0..40 {
+                assert_eq!(canvas1.get_pixel(x, y), canvas2.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_refractive_indices_for_nested_glass_spheres() {
+        let glass = |refractive| material::Material {
+            transparency: 1.0,
+            refractive,
+            ..material::DEFAULT_MATERIAL
+        };
+        let world = World::new(
+            test_world().light,
+            vec![
+                Object::Sphere(sphere::Sphere::new(transform::scaling(3., 3., 3.), glass(1.5))),
+                Object::Sphere(sphere::Sphere::new(transform::scaling(2., 2., 2.), glass(1.7))),
+                Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, glass(2.0))),
+            ],
+        );
+        let ray = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let intersections = world.intersect(&ray);
+
+        let test_cases = vec![
+            (2., 1.0, 1.5),
+            (3., 1.5, 1.7),
+            (4., 1.7, 2.0),
+            (6., 2.0, 1.7),
+            (7., 1.7, 1.5),
+            (8., 1.5, 1.0),
+        ];
+        assert_eq!(intersections.len(), test_cases.len());
+        for (intersection, (t, n1, n2)) in intersections.iter().zip(test_cases) {
+            let computations = Computations::from_hit(intersection, &ray, &intersections, &world);
+            assert!(float::is_equal(computations.t, t));
+            assert!(float::is_equal(computations.n1, n1));
+            assert!(float::is_equal(computations.n2, n2));
+        }
+    }
+
+    #[test]
+    fn test_render_pixel() {
+        let world = test_world();
+        let camera = Camera::look_at(
+            Tuple::point(0., 0., -5.),
+            Tuple::point(0., 0., 0.),
+            Tuple::vector(0., 1., 0.),
+            11,
+            11,
+            PI/2.,
+        );
+        let (color, log) = world.render_pixel(&camera, 5, 5);
+        assert_eq!(color, Color::new(0.38066, 0.47583, 0.2855));
+        assert!(log[0].starts_with("primary ray through pixel (5, 5)"));
+        assert!(log.iter().any(|event| event.contains("hit object")));
+        assert!(log.iter().any(|event| event.ends_with(" is lit")));
+        assert!(log.last().unwrap().starts_with("shaded object"));
+
+        let (_, log) = world.render_pixel(&camera, 0, 0);
+        assert!(log.iter().any(|event| event.contains("missed everything")));
+    }
+
+    #[test]
+    fn test_render_pixel_logs_total_internal_reflection() {
+        let glass = material::Material {
+            transparency: 1.0,
+            refractive: 1.5,
+            ..material::DEFAULT_MATERIAL
+        };
+        let world = World::new(
+            test_world().light,
+            vec![Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, glass))],
+        );
+        // Looking out from inside the glass at a glancing angle to its surface
+        let camera = Camera::look_at(
+            Tuple::point(0., 0.9, 0.),
+            Tuple::point(0., 0.9, 1.),
+            Tuple::vector(0., 1., 0.),
+            1,
+            1,
+            PI/2.,
+        );
+        let (_, log) = world.render_pixel(&camera, 0, 0);
+        assert!(log.iter().any(|event| event.starts_with("total internal reflection")));
+    }
+
+    #[test]
+    fn test_trace_path() {
+        let mirror = material::Material { reflective: 0.5, ..material::DEFAULT_MATERIAL };
+        let world = World::new(
+            test_world().light,
+            vec![
+                Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, mirror)),
+                Object::Sphere(sphere::Sphere::new(
+                    transform::translation(0., 0., -10.).multiply_matrix(transform::scaling(3., 3., 3.)),
+                    material::DEFAULT_MATERIAL,
+                )),
+            ],
+        );
+        let ids: Vec<u64> = world.objects().map(|object| object.get_id()).collect();
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+
+        // Off the mirror and back into the dull sphere behind the camera
+        let path = world.trace_path(&ray, MAX_RECURSIONS);
+        assert_eq!(path.iter().map(|(_, _, object)| object.get_id()).collect::<Vec<_>>(), ids);
+        assert!(path[0].0.direction.is_equal(ray.direction));
+        assert!(path[1].0.direction.is_equal(Tuple::vector(0., 0., -1.)));
+        let total = path.iter().fold(color::BLACK, |total, (_, color, _)| total.add(*color));
+        assert_eq!(total, world.color_at(&ray, MAX_RECURSIONS));
+
+        assert_eq!(world.trace_path(&ray, 0).len(), 1);
+        let away = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 1., 0.)
+        );
+        assert!(world.trace_path(&away, MAX_RECURSIONS).is_empty());
+    }
+
+    #[test]
+    fn test_get_object() {
+        let mut world = test_world();
+        let child = Object::Sphere(sphere::Sphere::new(transform::translation(0., 3., 0.), material::DEFAULT_MATERIAL));
+        let child_id = child.get_id();
+        let group = Object::Group(Group::new(vec![child]));
+        let group_id = group.get_id();
+        world.add_object(group);
+        let first_id = world.objects().next().unwrap().get_id();
+
+        let test_cases = vec![first_id, group_id, child_id];
+        for id in test_cases {
+            assert_eq!(world.get_object(id).unwrap().get_id(), id);
+        }
+        assert!(world.get_object(u64::MAX).is_none());
+
+        world.precompute();
+        world.get_object_mut(child_id).unwrap().set_visible(false);
+        assert!(!world.is_precomputed());
+        assert!(!world.get_object(child_id).unwrap().is_visible());
+        assert!(world.get_object_mut(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_contains_object() {
+        let mut world = test_world();
+        let child = Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let child_id = child.get_id();
+        let group = Object::Group(Group::new(vec![child]));
+        let group_id = group.get_id();
+        assert!(!world.contains_object(group_id));
+
+        world.add_object(group);
+        assert_eq!(world.len(), 3);
+        assert!(world.contains_object(group_id));
+        assert!(world.contains_object(child_id));
+
+        world.remove_object(group_id);
+        assert!(!world.contains_object(group_id));
+        assert!(!world.contains_object(child_id));
+    }
+
+    #[test]
+    fn test_remove_object_inside_group() {
+        let mut world = test_world();
+        let child = Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL));
+        let child_id = child.get_id();
+        let sibling = Object::Sphere(sphere::Sphere::new(transform::translation(5., 0., 0.), material::DEFAULT_MATERIAL));
+        let sibling_id = sibling.get_id();
+        let group = Object::Group(Group::new(vec![child, sibling]));
+        let group_id = group.get_id();
+        world.add_object(Object::Group(Group::new(vec![group])));
+
+        let removed = world.remove_object(child_id).unwrap();
+        assert_eq!(removed.get_id(), child_id);
+        assert!(!world.contains_object(child_id));
+        assert!(world.contains_object(group_id));
+        assert!(world.get_object(sibling_id).is_some());
+        assert!(world.remove_object(child_id).is_none());
+    }
+
+    #[test]
+    fn test_cloned_objects_are_shaded_as_themselves() {
+        let red = Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::Material {
+            color: SolidColor(Color::new(1., 0., 0.)),
+            ..material::Material::new()
+        }));
+        let mut blue = red.clone();
+        assert!(!blue.is_equal(&red));
+        blue.set_transform(transform::translation(5., 0., 0.));
+        if let Object::Sphere(sphere) = &mut blue {
+            sphere.material.color = SolidColor(Color::new(0., 0., 1.));
+        }
+        let group = Object::Group(Group::new(vec![red]));
+        let group_clone = group.clone();
+        let world = World::new(test_world().light, vec![group, group_clone, blue]);
+        assert_eq!(world.len(), 3);
+
+        let ray = Ray::new(Tuple::point(5., 0., -5.), Tuple::vector(0., 0., 1.));
+        let color = world.color_at(&ray, MAX_RECURSIONS);
+        assert!(color.b > color.r);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than one object in the world has the ID")]
+    fn test_add_object_rejects_duplicate_ids() {
+        let mut world = test_world();
+        let sphere = sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL);
+        world.add_object(Object::Sphere(sphere.clone()));
+        world.add_object(Object::Sphere(sphere));
+    }
+
+    #[test]
+    fn test_bvh_is_invalidated_by_changes() {
+        let mut world = test_world();
+        world.build_bvh();
+        world.add_object(Object::Sphere(sphere::Sphere::new(matrix::IDENTITY, material::DEFAULT_MATERIAL)));
+        assert!(!world.has_bvh());
+
+        world.build_bvh();
+        let id = world.objects().next().unwrap().get_id();
+        assert!(world.remove_object(id).is_some());
+        assert!(!world.has_bvh());
+        assert_eq!(world.len(), 2);
+        assert!(world.remove_object(id).is_none());
+
+        world.build_bvh();
+        for object in world.objects_mut() {
+            object.set_transform(transform::translation(0., 5., 0.));
+        }
+        assert!(!world.has_bvh());
+    }
+
+    #[test]
+    fn test_trace_all() {
+        let world = test_world();
+        let ray = Ray::new(Tuple::point(0., 0., 0.75), Tuple::vector(0., 0., -1.));
+        let ts: Vec<f64> = world.trace_all(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [-0.25, 0.25, 1.25, 1.75]);
+
+        let ids: Vec<u64> = world.trace_all(&ray).iter().map(|i| i.object_id).collect();
+        let outer_id = world.objects().next().unwrap().get_id();
+        let inner_id = world.objects().nth(1).unwrap().get_id();
+        assert_eq!(ids, [outer_id, inner_id, inner_id, outer_id]);
+    }
+
+    #[test]
+    fn test_intersect_drops_degenerate_intersections() {
+        let mut world = test_world();
+        let point = Tuple::point(0., 0., 0.);
+        world.add_object(Object::Triangle(Triangle::new(
+            point, point, point,
+            matrix::IDENTITY,
+            material::DEFAULT_MATERIAL,
+        )));
+        let ray = Ray::new(Tuple::point(0., 0., -5.), Tuple::vector(0., 0., 1.));
+        let ts: Vec<f64> = world.intersect(&ray).iter().map(|i| i.t).collect();
+        assert_eq!(ts, [4., 4.5, 5.5, 6.]);
+
+        let broken_ray = Ray::new(Tuple::point(f64::NAN, 0., -5.), Tuple::vector(0., 0., 1.));
+        assert!(world.intersect(&broken_ray).is_empty());
+    }
+
     #[test]
     fn test_is_shadowed_point_is_not_collinear_with_light() {
         let world = test_world();
@@ -247,6 +1241,64 @@ mod tests {
         assert_eq!(world.is_shadowed(point), true);
     }
 
+    #[test]
+    fn test_illumination_at_ignores_shadows() {
+        let world = test_world();
+        let point = Tuple::point(10., -10., 10.);
+        let test_cases = vec![
+            (Tuple::vector(-1., 1., -1.).normalize(), Color::new(1., 1., 1.)),
+            (Tuple::vector(0., 1., 0.), Color::new(1., 1., 1.).multiply(1. / 3_f64.sqrt())),
+            (Tuple::vector(1., -1., 1.).normalize(), color::BLACK),
+        ];
+
+        for (normal, expected) in test_cases {
+            assert_eq!(world.illumination_at(point, normal), expected);
+        }
+    }
+
+    #[test]
+    fn test_intersect_world_skips_hidden_objects() {
+        let mut world = test_world();
+        for object in world.objects_mut() {
+            object.set_visible(false);
+        }
+        assert!(world.objects().all(|object| !object.is_visible()));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        assert_eq!(world.intersect(&ray).len(), 0);
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), color::BLACK);
+    }
+
+    #[test]
+    fn test_is_shadowed_hidden_object_between_light_and_point() {
+        let mut world = test_world();
+        for object in world.objects_mut() {
+            object.set_visible(false);
+        }
+        let point = Tuple::point(10., -10., 10.);
+        assert!(world.is_shadowed(point));
+    }
+
+    #[test]
+    fn test_is_shadowed_object_that_casts_no_shadow() {
+        let world = test_world();
+        let objects = world
+            .objects()
+            .map(|object| {
+                let mut object = object.clone();
+                if let Object::Sphere(sphere) = &mut object {
+                    sphere.material.casts_shadow = false;
+                }
+                object
+            })
+            .collect();
+        let world = World::new(world.light, objects);
+        let point = Tuple::point(10., -10., 10.);
+        assert!(!world.is_shadowed(point));
+    }
+
     #[test]
     fn test_is_shadowed_light_between_point_and_object() {
         let world = test_world();
@@ -268,15 +1320,33 @@ mod tests {
             Tuple::point(0., 0., -5.),
             Tuple::vector(0., 0., 1.)
         );
-        let shape = world.objects.first().unwrap();
-        let intersection = Intersection::new(4., shape);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let shape = world.objects().next().unwrap();
+        let intersection = Intersection::new(4., shape.get_id());
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         let color = world.shade_hit(computations, MAX_RECURSIONS);
         assert_eq!(color, Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn test_shade_hit_adds_world_ambient() {
+        let world = test_world();
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        let shape = world.objects().next().unwrap();
+        let intersection = Intersection::new(4., shape.get_id());
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
+        );
+        let without_ambient = world.shade_hit(computations, MAX_RECURSIONS);
+        let world = world.with_ambient(Color::new(0.1, 0.2, 0.3));
+        let with_ambient = world.shade_hit(computations, MAX_RECURSIONS);
+        assert_eq!(with_ambient, without_ambient.add(Color::new(0.1, 0.2, 0.3)));
+    }
+
     #[test]
     fn test_shade_hit_inside() {
         let mut world = test_world();
@@ -289,10 +1359,10 @@ mod tests {
             Tuple::point(0., 0., 0.),
             Tuple::vector(0., 0., 1.)
         );
-        let shape = world.objects.iter().nth(1).unwrap();
-        let intersection = Intersection::new(0.5, shape);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let shape = world.objects().nth(1).unwrap();
+        let intersection = Intersection::new(0.5, shape.get_id());
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         let color = world.shade_hit(computations, MAX_RECURSIONS);
         assert_eq!(color, Color::new(0.90498, 0.90498, 0.90498));
@@ -315,6 +1385,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
 
         let s1 = Object::Sphere(
@@ -331,6 +1402,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s2 = Object::Sphere(
             sphere::Sphere::new(t2, m2)
@@ -346,24 +1418,23 @@ mod tests {
             reflective: 0.5,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let plane = Object::Plane(
             plane::Plane::new(t3, m3)
         );
 
-        let objects = vec![s1.clone(), s2.clone(), plane.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let plane_id = plane.get_id();
+        let objects = vec![s1, s2, plane];
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -3.),
             Tuple::vector(0., -2.0_f64.sqrt() / 2., 2.0_f64.sqrt() / 2.)
         );
-        let intersection = Intersection::new(2.0_f64.sqrt(), &plane);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let intersection = Intersection::new(2.0_f64.sqrt(), plane_id);
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         let color = world.shade_hit(computations, MAX_RECURSIONS);
         assert_eq!(color, Color::new(0.87676, 0.92434, 0.82917));
@@ -380,6 +1451,107 @@ mod tests {
         assert_eq!(color, color::BLACK);
     }
 
+    fn white_environment() -> EnvironmentLight {
+        let mut canvas = Canvas::new(8, 4);
+        for y in 0..4 {
+            for x in 0..8 {
+                canvas.set_pixel(x, y, color::WHITE);
+            }
+        }
+        EnvironmentLight::new(canvas, 1.0, matrix::IDENTITY)
+    }
+
+    #[test]
+    fn test_color_at_ray_misses_with_background_color() {
+        let blue = Color::new(0.1, 0.2, 0.8);
+        let world = test_world().with_background_color(blue);
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 1., 0.)
+        );
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), blue);
+    }
+
+    #[test]
+    fn test_color_at_ray_misses_with_physical_sky() {
+        let sky = PhysicalSky::new(PI / 4., 0., 3.);
+        let world = test_world().with_background(Background::PhysicalSky(sky));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 1., 0.)
+        );
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), sky.sample_direction(ray.direction));
+    }
+
+    #[test]
+    fn test_background_sample() {
+        let red = Color::new(1., 0., 0.);
+        let blue = Color::new(0., 0., 1.);
+        let sky = PhysicalSky::new(PI / 4., 0., 3.);
+        let gradient = Background::Gradient { bottom: red, top: blue };
+        let test_cases = vec![
+            (Background::Color(red), Tuple::vector(0., 1., 0.), red),
+            (Background::Color(red), Tuple::vector(1., -1., 0.), red),
+            (Background::Gradient { bottom: red, top: blue }, Tuple::vector(0., -1., 0.), red),
+            (Background::Gradient { bottom: red, top: blue }, Tuple::vector(0., 2., 0.), blue),
+            (gradient, Tuple::vector(1., 0., 0.), Color::new(0.5, 0., 0.5)),
+            (Background::PhysicalSky(sky), Tuple::vector(0., 1., 1.), sky.sample_direction(Tuple::vector(0., 1., 1.))),
+        ];
+        for (background, direction, expected) in test_cases {
+            let ray = Ray::new(Tuple::point(0., 0., 0.), direction);
+            assert_eq!(background.sample(&ray), expected);
+        }
+    }
+
+    #[test]
+    fn test_background_color_does_not_light_objects() {
+        let world = test_world().with_background_color(color::WHITE);
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn test_empty_world() {
+        let world = World::empty();
+        assert!(world.is_empty());
+        assert!(matches!(world.background, Background::Color(c) if c == color::BLACK));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        assert_eq!(world.color_at(&ray, MAX_RECURSIONS), color::BLACK);
+    }
+
+    #[test]
+    fn test_color_at_ray_misses_with_environment() {
+        let world = test_world().with_background(Background::Environment(Box::new(white_environment())));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 1., 0.)
+        );
+        let color = world.color_at(&ray, MAX_RECURSIONS);
+        assert_eq!(color, color::WHITE);
+    }
+
+    #[test]
+    fn test_shade_hit_with_environment_diffuse() {
+        let world = test_world().with_background(Background::Environment(Box::new(white_environment())));
+        let ray = Ray::new(
+            Tuple::point(0., 0., -5.),
+            Tuple::vector(0., 0., 1.)
+        );
+        let shape = world.objects().next().unwrap();
+        let intersection = Intersection::new(4., shape.get_id());
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
+        );
+        let color = world.shade_hit(computations, MAX_RECURSIONS);
+        assert_eq!(color, Color::new(0.94066, 1.17583, 0.7055));
+    }
+
     #[test]
     fn test_color_at_ray_hits() {
         let world = test_world();
@@ -408,6 +1580,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -421,10 +1594,7 @@ mod tests {
         );
 
         let objects = vec![s1, s2];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 0.75),
@@ -435,7 +1605,7 @@ mod tests {
     }
 
     #[test]
-    fn test_prepare_computations_nonrelective_material() {
+    fn test_computations_from_hit_nonrelective_material() {
         let light = light::Light::new(
             tuple::Tuple::point(-10., 10., -10.),
             color::Color::new(1., 1., 1.)
@@ -451,6 +1621,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
 
         let s1 = Object::Sphere(
@@ -467,31 +1638,30 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s2 = Object::Sphere(
             sphere::Sphere::new(t2, m2)
         );
 
-        let objects = vec![s1.clone(), s2.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let s2_id = s2.get_id();
+        let objects = vec![s1, s2];
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 0.),
             Tuple::vector(0., 0., 1.)
         );
-        let intersection = Intersection::new(1., &s2);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let intersection = Intersection::new(1., s2_id);
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         let reflected_color = world.reflected_color(&computations, MAX_RECURSIONS);
         assert_eq!(reflected_color, color::BLACK);
     }
 
     #[test]
-    fn test_prepare_computations_reflective_material() {
+    fn test_computations_from_hit_reflective_material() {
         let light = light::Light::new(
             tuple::Tuple::point(-10., 10., -10.),
             color::Color::new(1., 1., 1.)
@@ -507,6 +1677,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
 
         let s1 = Object::Sphere(
@@ -523,6 +1694,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s2 = Object::Sphere(
             sphere::Sphere::new(t2, m2)
@@ -538,24 +1710,23 @@ mod tests {
             reflective: 0.5,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let plane = Object::Plane(
             plane::Plane::new(t3, m3)
         );
 
-        let objects = vec![s1.clone(), s2.clone(), plane.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let plane_id = plane.get_id();
+        let objects = vec![s1, s2, plane];
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -3.),
             Tuple::vector(0., -2.0_f64.sqrt() / 2., 2.0_f64.sqrt() / 2.)
         );
-        let intersection = Intersection::new(2.0_f64.sqrt(), &plane);
-        let computations = intersection.prepare_computations(
-            &ray, vec![intersection.clone()]
+        let intersection = Intersection::new(2.0_f64.sqrt(), plane_id);
+        let computations = Computations::from_hit(
+            &intersection, &ray, &[intersection], &world
         );
         let reflected_color = world.reflected_color(&computations, MAX_RECURSIONS);
         assert_eq!(reflected_color, Color::new(0.19033, 0.23792, 0.14275));
@@ -577,6 +1748,7 @@ mod tests {
             reflective: 1.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let lower_plane = Object::Plane(
             plane::Plane::new(t1, m1)
@@ -592,16 +1764,14 @@ mod tests {
             reflective: 1.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let upper_plane = Object::Plane(
             plane::Plane::new(t2, m2)
         );
 
         let objects = vec![lower_plane, upper_plane];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -3.),
@@ -628,6 +1798,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -640,10 +1811,7 @@ mod tests {
         );
 
         let objects = vec![s1.clone(), s2.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -5.),
@@ -651,7 +1819,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i1 = intersections.iter().nth(0).unwrap();
-        let computations = i1.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i1, &ray, &intersections, &world);
         let color = world.refracted_color(&computations, MAX_RECURSIONS);
         assert_eq!(color, color::BLACK);
     }
@@ -673,6 +1841,7 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -685,10 +1854,7 @@ mod tests {
         );
 
         let objects = vec![s1.clone(), s2.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -5.),
@@ -696,7 +1862,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i1 = intersections.iter().nth(0).unwrap();
-        let computations = i1.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i1, &ray, &intersections, &world);
         let color = world.refracted_color(&computations, 0);
         assert_eq!(color, color::BLACK);
     }
@@ -718,6 +1884,7 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -730,10 +1897,7 @@ mod tests {
         );
 
         let objects = vec![s1.clone(), s2.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 2.0_f64.sqrt() / 2.),
@@ -743,7 +1907,7 @@ mod tests {
         // NOTE: this time you're inside the sphere, so you need
         // to look at the second intersection not the first one.
         let i2 = intersections.iter().nth(1).unwrap();
-        let computations = i2.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i2, &ray, &intersections, &world);
         let color = world.refracted_color(&computations, MAX_RECURSIONS);
         assert_eq!(color, color::BLACK);
     }
@@ -765,6 +1929,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -780,16 +1945,14 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let s2 = Object::Sphere(
             sphere::Sphere::new(t2, m2)
         );
 
         let objects = vec![s1.clone(), s2.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 0.1),
@@ -797,7 +1960,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i3 = intersections.iter().nth(2).unwrap();
-        let computations = i3.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i3, &ray, &intersections, &world);
         let color = world.refracted_color(&computations, MAX_RECURSIONS);
         assert_eq!(color, Color::new(0., 0.99888, 0.04722));
     }
@@ -819,6 +1982,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.5,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let floor = Object::Plane(
             plane::Plane::new(t1, m1)
@@ -834,16 +1998,14 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let ball = Object::Sphere(
             sphere::Sphere::new(t2, m2)
         );
 
         let objects = vec![floor.clone(), ball.clone()];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -3.),
@@ -851,7 +2013,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i0 = intersections.iter().nth(0).unwrap();
-        let computations = i0.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i0, &ray, &intersections, &world);
         let color = world.shade_hit(computations, MAX_RECURSIONS);
         assert_eq!(color, Color::new(0.93642, 0.68642, 0.68642));
     }
@@ -872,6 +2034,7 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let glassy_sphere = Object::Sphere(
             sphere::Sphere::new(
@@ -879,10 +2042,7 @@ mod tests {
                 glass
             )
         );
-        let world = World {
-            light: light,
-            objects: vec![glassy_sphere],
-        };
+        let world = World::new(light, vec![glassy_sphere]);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 2.0_f64.sqrt()/2.),
@@ -890,7 +2050,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i1 = intersections.iter().nth(1).unwrap();
-        let computations = i1.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i1, &ray, &intersections, &world);
         let reflectance = schlick_reflectance(computations);
         assert_eq!(reflectance, 1.0);
     }
@@ -911,6 +2071,7 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let glassy_sphere = Object::Sphere(
             sphere::Sphere::new(
@@ -918,10 +2079,7 @@ mod tests {
                 glass
             )
         );
-        let world = World {
-            light: light,
-            objects: vec![glassy_sphere],
-        };
+        let world = World::new(light, vec![glassy_sphere]);
 
         let ray = Ray::new(
             Tuple::point(0., 0., 0.),
@@ -929,7 +2087,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i1 = intersections.iter().nth(1).unwrap();
-        let computations = i1.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i1, &ray, &intersections, &world);
         let reflectance = schlick_reflectance(computations);
         assert!(float::is_equal(reflectance, 0.04));
     }
@@ -950,6 +2108,7 @@ mod tests {
             reflective: 0.0,
             transparency: 1.0,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let glassy_sphere = Object::Sphere(
             sphere::Sphere::new(
@@ -957,10 +2116,7 @@ mod tests {
                 glass
             )
         );
-        let world = World {
-            light: light,
-            objects: vec![glassy_sphere],
-        };
+        let world = World::new(light, vec![glassy_sphere]);
 
         let ray = Ray::new(
             Tuple::point(0., 0.99, -2.),
@@ -968,7 +2124,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i0 = intersections.iter().nth(0).unwrap();
-        let computations = i0.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i0, &ray, &intersections, &world);
         let reflectance = schlick_reflectance(computations);
         assert!(float::is_equal(reflectance, 0.48881));
     }
@@ -990,6 +2146,7 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 1.0,
+            casts_shadow: true,
         };
         let s1 = Object::Sphere(
             sphere::Sphere::new(t1, m1)
@@ -1011,6 +2168,7 @@ mod tests {
             reflective: 0.5,
             transparency: 0.5,
             refractive: 1.5,
+            casts_shadow: true,
         };
         let floor = Object::Plane(
             plane::Plane::new(t3, m3)
@@ -1026,16 +2184,14 @@ mod tests {
             reflective: 0.0,
             transparency: 0.0,
             refractive: 0.0,
+            casts_shadow: true,
         };
         let ball = Object::Sphere(
             sphere::Sphere::new(t4, m4)
         );
 
         let objects = vec![s1, s2, ball, floor];
-        let world = World {
-            light: light,
-            objects: objects,
-        };
+        let world = World::new(light, objects);
 
         let ray = Ray::new(
             Tuple::point(0., 0., -3.),
@@ -1043,7 +2199,7 @@ mod tests {
         );
         let intersections = world.intersect(&ray);
         let i0 = intersections.iter().nth(0).unwrap();
-        let computations = i0.prepare_computations(&ray, intersections.clone());
+        let computations = Computations::from_hit(i0, &ray, &intersections, &world);
         let color = world.shade_hit(computations, 5);
         assert_eq!(color, Color::new(0.93391, 0.69643, 0.69243));
     }